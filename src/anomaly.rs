@@ -0,0 +1,264 @@
+//! Dump de contexto em anomalias (ANOMALY_DUMP): "caixa-preta" dos trades.
+//!
+//! Mantém um ring buffer com os últimos trades (id, timestamps, latência).
+//! Quando um dos eventos selecionados acontece, espera mais `ANOMALY_AFTER`
+//! trades e grava o ring inteiro num CSV com timestamp no nome, marcando o
+//! trade que disparou e os eventos de cada linha:
+//!   latency  latência acima de ALERT_LATENCY_MS
+//!   gap      ids faltando antes deste trade
+//!   ooo      fora de ordem (respeita OOO_TOLERANCE)
+//!   dup      trade_id já visto no ring
+//!
+//! Eventos durante a janela "depois" entram no mesmo arquivo; `ANOMALY_MAX_DUMPS`
+//! limita a quantidade de arquivos (rajada de gaps não enche o disco).
+
+use std::collections::VecDeque;
+use std::io::Write;
+
+use crate::audit::format_utc_compact;
+
+const DEFAULT_BEFORE: usize = 100;
+const DEFAULT_AFTER: usize = 20;
+const DEFAULT_MAX_DUMPS: u64 = 50;
+
+/// Bit set of anomaly kinds (`Events::LATENCY | Events::GAP`...).
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Events(u8);
+
+impl Events {
+    pub const NONE: Events = Events(0);
+    pub const LATENCY: Events = Events(1);
+    pub const GAP: Events = Events(2);
+    pub const OUT_OF_ORDER: Events = Events(4);
+    pub const DUPLICATE: Events = Events(8);
+    const ALL: [(Events, &'static str); 4] = [
+        (Events::LATENCY, "latency"),
+        (Events::GAP, "gap"),
+        (Events::OUT_OF_ORDER, "ooo"),
+        (Events::DUPLICATE, "dup"),
+    ];
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, other: Events) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// `latency,gap,ooo,dup` or `all`; unknown names are returned as errors.
+    fn parse(spec: &str) -> Result<Events, String> {
+        let mut events = Events::NONE;
+        for name in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            if name.eq_ignore_ascii_case("all") {
+                events = Events::ALL.iter().fold(events, |acc, (e, _)| acc | *e);
+                continue;
+            }
+            match Events::ALL
+                .iter()
+                .find(|(_, n)| name.eq_ignore_ascii_case(n))
+            {
+                Some((e, _)) => events = events | *e,
+                None => {
+                    return Err(format!(
+                        "unknown event {:?} (use latency,gap,ooo,dup or all)",
+                        name
+                    ))
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    /// `gap|latency` (empty for no event).
+    fn names(self) -> String {
+        Events::ALL
+            .iter()
+            .filter(|(e, _)| self.contains(*e))
+            .map(|(_, n)| *n)
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+}
+
+impl std::ops::BitOr for Events {
+    type Output = Events;
+    fn bitor(self, rhs: Events) -> Events {
+        Events(self.0 | rhs.0)
+    }
+}
+
+struct Row {
+    trade_id: u64,
+    trade_ts_us: u64,
+    recv_ts_us: u64,
+    latency_us: i64,
+    events: Events,
+}
+
+/// Dump armed by a trigger, waiting for the trades that follow it.
+struct Pending {
+    events: Events,
+    trigger_id: u64,
+    trigger_recv_us: u64,
+    /// Trades recorded after the trigger so far.
+    since: usize,
+}
+
+pub struct AnomalyDump {
+    enabled: Events,
+    ring: VecDeque<Row>,
+    capacity: usize,
+    after: usize,
+    pending: Option<Pending>,
+    dir: String,
+    prefix: String,
+    max_dumps: u64,
+    written: u64,
+    suppressed: u64,
+}
+
+impl AnomalyDump {
+    /// None when ANOMALY_DUMP is unset or selects no event.
+    pub fn from_env(machine_id: &str, symbol: &str) -> Option<Self> {
+        let spec = std::env::var("ANOMALY_DUMP").ok()?;
+        let enabled = match Events::parse(&spec) {
+            Ok(e) if !e.is_empty() => e,
+            Ok(_) => return None,
+            Err(e) => {
+                eprintln!("  WARNING: ANOMALY_DUMP: {}; anomaly dump disabled", e);
+                return None;
+            }
+        };
+        let before = crate::env_parse("ANOMALY_BEFORE", DEFAULT_BEFORE);
+        let after = crate::env_parse("ANOMALY_AFTER", DEFAULT_AFTER);
+        let dir = std::env::var("ANOMALY_DIR").unwrap_or_else(|_| ".".to_string());
+        let max_dumps = crate::env_parse("ANOMALY_MAX_DUMPS", DEFAULT_MAX_DUMPS);
+        eprintln!(
+            "Anomaly:    dump on {} ({} before / {} after, max {} files in {})",
+            enabled.names().replace('|', ","),
+            before,
+            after,
+            max_dumps,
+            dir
+        );
+        let capacity = before + after + 1;
+        Some(Self {
+            enabled,
+            ring: VecDeque::with_capacity(capacity),
+            capacity,
+            after,
+            pending: None,
+            dir,
+            prefix: format!("anomaly_{}_{}", machine_id, symbol),
+            max_dumps,
+            written: 0,
+            suppressed: 0,
+        })
+    }
+
+    /// Events the caller needs to detect (skip the work for the others).
+    pub fn watches(&self, events: Events) -> bool {
+        self.enabled.0 & events.0 != 0
+    }
+
+    /// True if `trade_id` is still in the ring (duplicate delivery).
+    pub fn seen(&self, trade_id: u64) -> bool {
+        self.ring.iter().any(|r| r.trade_id == trade_id)
+    }
+
+    /// Records one trade with the anomalies detected on it. Returns the path
+    /// of a dump written by this call (the "after" window just filled up).
+    pub fn record(
+        &mut self,
+        trade_id: u64,
+        trade_ts_us: u64,
+        recv_ts_us: u64,
+        latency_us: i64,
+        events: Events,
+    ) -> Option<std::io::Result<String>> {
+        let events = Events(events.0 & self.enabled.0);
+        if self.ring.len() == self.capacity {
+            self.ring.pop_front();
+        }
+        self.ring.push_back(Row {
+            trade_id,
+            trade_ts_us,
+            recv_ts_us,
+            latency_us,
+            events,
+        });
+
+        match self.pending.as_mut() {
+            Some(p) => {
+                // Evento dentro da janela "depois": mesmo arquivo
+                p.events = p.events | events;
+                p.since += 1;
+            }
+            None if !events.is_empty() => {
+                if self.written >= self.max_dumps {
+                    self.suppressed += 1;
+                    return None;
+                }
+                self.pending = Some(Pending {
+                    events,
+                    trigger_id: trade_id,
+                    trigger_recv_us: recv_ts_us,
+                    since: 0,
+                });
+            }
+            None => return None,
+        }
+        if self.pending.as_ref().is_some_and(|p| p.since >= self.after) {
+            return self.write_pending();
+        }
+        None
+    }
+
+    /// Writes a dump still waiting for its "after" trades (end of run).
+    pub fn finish(&mut self) -> Option<std::io::Result<String>> {
+        self.write_pending()
+    }
+
+    fn write_pending(&mut self) -> Option<std::io::Result<String>> {
+        let p = self.pending.take()?;
+        let stamp = format_utc_compact(p.trigger_recv_us as i64);
+        let path = format!(
+            "{}/{}_{}_{}_{}.csv",
+            self.dir.trim_end_matches('/'),
+            self.prefix,
+            p.events.names().replace('|', "-"),
+            stamp,
+            p.trigger_id
+        );
+        self.written += 1;
+        let trigger_index = self.ring.len() - 1 - p.since;
+        Some(self.write_ring(&path, trigger_index).map(|()| path))
+    }
+
+    fn write_ring(&self, path: &str, trigger_index: usize) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(
+            file,
+            "trade_id,trade_ts_us,recv_ts_us,latency_us,events,trigger"
+        )?;
+        for (i, r) in self.ring.iter().enumerate() {
+            writeln!(
+                file,
+                "{},{},{},{},{},{}",
+                r.trade_id,
+                r.trade_ts_us,
+                r.recv_ts_us,
+                r.latency_us,
+                r.events.names(),
+                u8::from(i == trigger_index)
+            )?;
+        }
+        file.flush()
+    }
+
+    /// (dumps written, triggers dropped by ANOMALY_MAX_DUMPS).
+    pub fn counts(&self) -> (u64, u64) {
+        (self.written, self.suppressed)
+    }
+}
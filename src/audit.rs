@@ -0,0 +1,131 @@
+//! Audit log de ciclo de vida da captura (AUDIT_LOG).
+//!
+//! Arquivo append-only com uma linha por evento (start, connect, first-trade,
+//! disconnect, alert, finalize), no formato `<UTC ISO-8601> event=<kind> k=v...`.
+//! Independente do display e do CSV: cada linha é escrita com um único
+//! `write` direto no arquivo (sem buffer em user space), então um crash do
+//! processo não perde os eventos já registrados.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+use crate::clock::wall_clock_us;
+
+pub struct AuditLog {
+    file: Option<File>,
+    path: String,
+}
+
+impl AuditLog {
+    /// Opens `AUDIT_LOG` in append mode; disabled when unset.
+    pub fn from_env() -> Self {
+        let Ok(path) = std::env::var("AUDIT_LOG") else {
+            return Self { file: None, path: String::new() };
+        };
+        match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => {
+                eprintln!("Audit log:  {}", path);
+                Self { file: Some(file), path }
+            }
+            Err(e) => {
+                eprintln!("  WARNING: audit log {} unavailable: {}", path, e);
+                Self { file: None, path }
+            }
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Appends one event line. `fields` is already in `k=v k=v` form.
+    pub fn event(&mut self, kind: &str, fields: &str) {
+        let Some(file) = self.file.as_mut() else {
+            return;
+        };
+        let line = format!("{} event={} {}\n", format_utc(wall_clock_us()), kind, fields);
+        if let Err(e) = file.write_all(line.as_bytes()).and_then(|_| file.sync_data()) {
+            eprintln!("  WARNING: audit log {} write failed: {}", self.path, e);
+        }
+    }
+}
+
+/// Broken-down UTC time of an epoch timestamp.
+struct Utc {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    micros: i64,
+}
+
+impl Utc {
+    fn from_epoch_us(epoch_us: i64) -> Self {
+        let secs = epoch_us.div_euclid(1_000_000);
+        let micros = epoch_us.rem_euclid(1_000_000);
+        let days = secs.div_euclid(86_400);
+        let sod = secs.rem_euclid(86_400);
+
+        // Civil-from-days (Howard Hinnant)
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: sod / 3600,
+            minute: sod % 3600 / 60,
+            second: sod % 60,
+            micros,
+        }
+    }
+}
+
+/// Epoch microseconds -> `YYYY-MM-DDTHH:MM:SS.ffffffZ` (UTC).
+pub fn format_utc(epoch_us: i64) -> String {
+    let t = Utc::from_epoch_us(epoch_us);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        t.year, t.month, t.day, t.hour, t.minute, t.second, t.micros
+    )
+}
+
+/// Epoch microseconds -> `YYYYMMDDTHHMMSS` (UTC, whole seconds): file names
+/// and the SigV4 date.
+pub fn format_utc_compact(epoch_us: i64) -> String {
+    let t = Utc::from_epoch_us(epoch_us);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}",
+        t.year, t.month, t.day, t.hour, t.minute, t.second
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_utc_iso() {
+        assert_eq!(format_utc(0), "1970-01-01T00:00:00.000000Z");
+        assert_eq!(format_utc(1_767_323_045_123_456), "2026-01-02T03:04:05.123456Z");
+        // Ano bissexto e antes da epoch
+        assert_eq!(format_utc(951_782_400_000_000), "2000-02-29T00:00:00.000000Z");
+        assert_eq!(format_utc(-1), "1969-12-31T23:59:59.999999Z");
+    }
+
+    #[test]
+    fn format_utc_compact_drops_separators_and_micros() {
+        assert_eq!(format_utc_compact(1_767_323_045_999_999), "20260102T030405");
+        assert_eq!(format_utc_compact(0), "19700101T000000");
+    }
+}
@@ -0,0 +1,73 @@
+//! Dry-parse benchmark: roda o hot path (extração + estatísticas) sobre um
+//! dump de frames JSON capturados, sem rede.
+//!
+//! Formato do arquivo: um frame JSON bruto por linha, exatamente como recebido
+//! do WebSocket.
+//!
+//! Uso:
+//!   cargo run --release -- --bench-parse frames.jsonl [iterations]
+
+use std::time::Instant;
+
+use crate::clock::{ms_to_us, ClockRef};
+use crate::extract::extract_trade_data;
+use crate::stats::LatencyStats;
+
+pub const DEFAULT_ITERATIONS: usize = 10;
+
+/// Loads the frame dump and runs it through the hot path `iterations` times.
+pub fn run(path: &str, iterations: usize) -> std::io::Result<()> {
+    let raw = std::fs::read(path)?;
+    let frames: Vec<&[u8]> = raw
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    let iterations = iterations.max(1);
+
+    eprintln!("=== Dry-Parse Benchmark ===");
+    eprintln!("Frames:     {} ({})", frames.len(), path);
+    eprintln!("Iterations: {}", iterations);
+
+    if frames.is_empty() {
+        eprintln!("  WARNING: No frames to benchmark");
+        return Ok(());
+    }
+
+    let clock_ref = ClockRef::new();
+    let mut stats = LatencyStats::new(frames.len());
+    let mut parse_failures: u64 = 0;
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        for frame in &frames {
+            // Mesmo caminho do loop de coleta: timestamp -> parse -> stats
+            let recv_instant = Instant::now();
+            match extract_trade_data(std::hint::black_box(frame)) {
+                Some((trade_id, trade_ts_ms)) => match ms_to_us(trade_ts_ms) {
+                    Some(trade_ts_us) => {
+                        let recv_ts_us = clock_ref.to_epoch_us(recv_instant);
+                        stats.update(trade_id, recv_ts_us as i64 - trade_ts_us);
+                    }
+                    None => parse_failures += 1,
+                },
+                None => parse_failures += 1,
+            }
+        }
+    }
+    let elapsed = start.elapsed();
+
+    let total = (frames.len() * iterations) as f64;
+    let secs = elapsed.as_secs_f64();
+
+    eprintln!("\n=== Benchmark Results ===");
+    eprintln!("Messages:   {}", total as u64);
+    eprintln!("Parsed:     {}", stats.count());
+    eprintln!("Failures:   {}", parse_failures);
+    eprintln!("Elapsed:    {:.3}s", secs);
+    eprintln!("Throughput: {:.0} msg/s", total / secs);
+    eprintln!("Per msg:    {:.1}ns", secs * 1e9 / total);
+
+    Ok(())
+}
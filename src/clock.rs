@@ -0,0 +1,240 @@
+//! Timestamps de recebimento em microssegundos desde a epoch.
+//!
+//! `ClockRef` ancora um `Instant` monotônico no relógio de parede uma vez e
+//! converte os instantes seguintes sem syscall: um step de NTP no meio da
+//! coleta não altera as latências já medidas. `RecvClock` escolhe entre ele e
+//! um PHC (`CLOCK_SOURCE=ptp`); `calibrate_clock` mede o offset contra o
+//! `/api/v3/time` da Binance.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::env_parse;
+use crate::extract::extract_u64_field;
+use crate::ptp::PtpClock;
+
+pub const DEFAULT_PTP_DEVICE: &str = "/dev/ptp0";
+
+/// Wall-clock epoch microseconds. Never panics: a clock stepped to before the
+/// epoch (RTC/VM mal configurado) yields a negative value and a warning.
+pub fn wall_clock_us() -> i64 {
+    match SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        Ok(d) => d.as_micros() as i64,
+        Err(e) => {
+            eprintln!("  WARNING: system clock is before the UNIX epoch");
+            -(e.duration().as_micros() as i64)
+        }
+    }
+}
+
+/// Server timestamp (ms, as sent by Binance) in µs. None if it does not fit
+/// an i64 of microseconds: a corrupt or hostile "T"/"E" is dropped instead of
+/// wrapping (or panicking in debug builds).
+#[inline(always)]
+pub fn ms_to_us(ms: u64) -> Option<i64> {
+    i64::try_from(ms).ok()?.checked_mul(1000)
+}
+
+/// Monotonic reference to convert Instant -> epoch micros without syscall.
+pub struct ClockRef {
+    instant: Instant,
+    epoch_us: u64,
+}
+
+impl ClockRef {
+    pub fn new() -> Self {
+        // Capture both as close as possible
+        let instant = Instant::now();
+        let epoch_us = wall_clock_us().max(0) as u64;
+        Self { instant, epoch_us }
+    }
+
+    /// Converts an Instant to epoch microseconds without syscall.
+    #[inline(always)]
+    pub fn to_epoch_us(&self, now: Instant) -> u64 {
+        let elapsed = now.duration_since(self.instant).as_micros() as u64;
+        self.epoch_us + elapsed
+    }
+}
+
+impl Default for ClockRef {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Source of receive timestamps: system wall clock (via ClockRef) or a PTP
+/// hardware clock (`CLOCK_SOURCE=ptp`, Linux only).
+pub enum RecvClock {
+    System(ClockRef),
+    Ptp {
+        ptp: PtpClock,
+        utc_offset_us: i64,
+        /// Failed PHC reads, answered from the system clock instead.
+        read_failures: Cell<u64>,
+    },
+}
+
+impl RecvClock {
+    /// Reads `CLOCK_SOURCE`, `PTP_DEVICE` and `PTP_UTC_OFFSET_S`.
+    /// Falls back to the system clock if the PTP device can't be used.
+    pub fn from_env() -> Self {
+        if std::env::var("CLOCK_SOURCE").as_deref() != Ok("ptp") {
+            return RecvClock::System(ClockRef::new());
+        }
+
+        let device = std::env::var("PTP_DEVICE").unwrap_or_else(|_| DEFAULT_PTP_DEVICE.to_string());
+        match PtpClock::open(&device) {
+            Ok(ptp) => {
+                let utc_offset_us = env_parse("PTP_UTC_OFFSET_S", 0i64) * 1_000_000;
+                RecvClock::Ptp {
+                    ptp,
+                    utc_offset_us,
+                    read_failures: Cell::new(0),
+                }
+            }
+            Err(e) => {
+                eprintln!("  WARNING: PTP clock {} unavailable ({}). Using system clock", device, e);
+                RecvClock::System(ClockRef::new())
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RecvClock::System(_) => "system",
+            RecvClock::Ptp { .. } => "ptp",
+        }
+    }
+
+    /// Epoch microseconds at `recv_instant`.
+    /// PTP: lê o PHC agora e desconta o tempo decorrido desde `recv_instant`.
+    #[inline(always)]
+    pub fn to_epoch_us(&self, recv_instant: Instant) -> u64 {
+        match self {
+            RecvClock::System(clock_ref) => clock_ref.to_epoch_us(recv_instant),
+            RecvClock::Ptp { .. } => {
+                let since_recv = recv_instant.elapsed().as_micros() as i64;
+                (self.now_us() - since_recv) as u64
+            }
+        }
+    }
+
+    /// Current time in epoch microseconds (used by clock calibration).
+    /// PTP: uma leitura do PHC que falha cai no relógio do sistema (já em
+    /// UTC) em vez de virar um timestamp absurdo; a falha é contada.
+    pub fn now_us(&self) -> i64 {
+        match self {
+            RecvClock::System(_) => wall_clock_us(),
+            RecvClock::Ptp {
+                ptp,
+                utc_offset_us,
+                read_failures,
+            } => match ptp.now_us() {
+                Some(us) => us as i64 - utc_offset_us,
+                None => {
+                    read_failures.set(read_failures.get() + 1);
+                    if read_failures.get() == 1 {
+                        eprintln!(
+                            "\n  WARNING: PTP clock read failed ({}); using the system clock for those samples",
+                            std::io::Error::last_os_error()
+                        );
+                    }
+                    wall_clock_us()
+                }
+            },
+        }
+    }
+
+    /// PHC reads that fell back to the system clock (0 for `System`).
+    pub fn read_failures(&self) -> u64 {
+        match self {
+            RecvClock::System(_) => 0,
+            RecvClock::Ptp { read_failures, .. } => read_failures.get(),
+        }
+    }
+}
+
+/// Measures local clock offset vs Binance by making N requests to /api/v3/time.
+/// Returns estimated offset in microseconds (local - server), None if no
+/// request succeeded.
+///
+/// NOTE: Reduzido para 10-50 amostras para não demorar muito (1000 = ~100 segundos).
+pub async fn calibrate_clock(n: usize, clock: &RecvClock, rest_base: &str) -> Option<i64> {
+    let n = n.min(50); // Limita a 50 amostras máximo
+    eprintln!("Calibrating clock against {} ({} samples)...", rest_base, n);
+    let time_url = format!("{}/api/v3/time", rest_base);
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .expect("Error creating HTTP client");
+
+    let mut offsets = Vec::with_capacity(n);
+
+    for _ in 0..n {
+        let t1_us = clock.now_us();
+
+        let resp = client
+            .get(&time_url)
+            .send()
+            .await;
+
+        let t3_us = clock.now_us();
+
+        if let Ok(resp) = resp {
+            if let Ok(body) = resp.bytes().await {
+                // {"serverTime":1234567890123}
+                if let Some(server_us) = extract_u64_field(&body, b"\"serverTime\":").and_then(ms_to_us) {
+                    let rtt_us = t3_us - t1_us;
+                    // Estimates server timestamp is at RTT/2
+                    let local_at_server = t1_us + rtt_us / 2;
+                    let offset = local_at_server - server_us;
+                    offsets.push((offset, rtt_us));
+                }
+            }
+        }
+        // Sleep menor para acelerar calibração (mas ainda permite múltiplas amostras)
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    if offsets.is_empty() {
+        eprintln!("  WARNING: Could not calibrate. Using offset = 0");
+        return None;
+    }
+
+    // Use sample with lowest RTT (most accurate)
+    offsets.sort_by_key(|&(_, rtt)| rtt);
+    let best = offsets[0];
+    let median_idx = offsets.len() / 2;
+    let median = offsets[median_idx];
+
+    eprintln!("  Best RTT: {}µs, offset: {}µs", best.1, best.0);
+    eprintln!("  Median RTT: {}µs, offset: {}µs", median.1, median.0);
+    eprintln!(
+        "  Local clock is ~{:.2}ms {} from Binance",
+        best.0.abs() as f64 / 1000.0,
+        if best.0 > 0 { "ahead" } else { "behind" }
+    );
+
+    Some(best.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ms_to_us_converts_real_timestamps() {
+        assert_eq!(ms_to_us(0), Some(0));
+        assert_eq!(ms_to_us(1_700_000_000_123), Some(1_700_000_000_123_000));
+    }
+
+    #[test]
+    fn ms_to_us_rejects_overflow() {
+        assert_eq!(ms_to_us(u64::MAX), None);
+        assert_eq!(ms_to_us(i64::MAX as u64), None);
+        assert_eq!(ms_to_us((i64::MAX / 1000) as u64), Some(i64::MAX / 1000 * 1000));
+        assert_eq!(ms_to_us((i64::MAX / 1000) as u64 + 1), None);
+    }
+}
@@ -0,0 +1,881 @@
+//! Conexão com o feed como `Stream` de eventos (usado pelo binário e como biblioteca).
+//!
+//! `run_collector` cuida de tudo que é socket: conexão com TCP_NODELAY,
+//! SUBSCRIBE opcional, resposta aos pings, reconexão com backoff quando o
+//! stream cai, conexão standby (make-before-break) e reconexão forçada. Cada
+//! frame sai como `Event::Message` com o `Instant` de recebimento tirado logo
+//! depois do read; o que fazer com ele (parse, estatísticas, CSV) fica com
+//! quem consome o stream. O binário liga nele todos os destinos; quem só quer
+//! trades usa `Collector::trades`, um `Stream` de `TradeRecord`s.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, Stream, StreamExt};
+use tokio::net::TcpSocket;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::clock::{ms_to_us, ClockRef};
+use crate::extract::{extract_event_ts, extract_trade_data, extract_u64_field};
+use crate::TradeRecord;
+
+pub const BINANCE_WS_BASE: &str = "wss://stream.binance.com:9443/ws";
+
+const SUBSCRIBE_ID: u64 = 1;
+const SUBSCRIBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// What `run_collector` connects to and when it stops.
+#[derive(Clone, Debug)]
+pub struct CollectorConfig {
+    /// Lowercase pair, e.g. `btcusdt`.
+    pub symbol: String,
+    /// WebSocket base ending in `/ws` (another deployment or a local mock).
+    pub endpoint: String,
+    /// Full URL, used as is instead of `endpoint`/`symbol` (combined streams,
+    /// another feed).
+    pub ws_url: Option<String>,
+    /// Streams to `SUBSCRIBE` to after connecting (`/`-separated); the URL is
+    /// then the bare endpoint.
+    pub subscribe: Option<String>,
+    /// Framing limits (None = tungstenite's defaults).
+    pub ws_config: Option<WebSocketConfig>,
+    /// Reconnects when the stream ends (None = the stream ends with the connection).
+    pub reconnect: Option<Backoff>,
+    /// `trades`: ends after this many trades (None = until the connection ends).
+    pub min_trades: Option<usize>,
+    /// `trades`: subtracted from every latency (local clock minus server clock, µs).
+    pub clock_offset_us: i64,
+}
+
+impl CollectorConfig {
+    pub fn new(symbol: &str) -> Self {
+        Self {
+            symbol: symbol.to_lowercase(),
+            endpoint: BINANCE_WS_BASE.to_string(),
+            ws_url: None,
+            subscribe: None,
+            ws_config: None,
+            reconnect: None,
+            min_trades: None,
+            clock_offset_us: 0,
+        }
+    }
+
+    /// `wss://.../ws/btcusdt@trade` (or `ws_url`; the endpoint in SUBSCRIBE mode).
+    pub fn url(&self) -> String {
+        match (&self.ws_url, &self.subscribe) {
+            (Some(url), _) => url.clone(),
+            (None, Some(_)) => self.endpoint.clone(),
+            (None, None) => format!("{}/{}@trade", self.endpoint.trim_end_matches('/'), self.symbol),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Backoff de reconexão
+// ---------------------------------------------------------------------------
+
+const DEFAULT_BACKOFF_INITIAL_MS: f64 = 500.0;
+const DEFAULT_BACKOFF_MAX_MS: f64 = 30_000.0;
+
+/// Exponential backoff for AUTO_RECONNECT after the stream ends or errors:
+/// 500ms, 1s, 2s... capped at 30s (RECONNECT_BACKOFF_MS / RECONNECT_BACKOFF_MAX_MS).
+/// Volta ao início quando a conexão nova entrega a primeira mensagem.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    /// 0 = unlimited (RECONNECT_MAX_ATTEMPTS).
+    max_attempts: u32,
+    attempt: u32,
+}
+
+impl Backoff {
+    /// `max_attempts`: 0 = unlimited.
+    pub fn new(initial: Duration, max: Duration, max_attempts: u32) -> Self {
+        Self {
+            initial,
+            max: max.max(initial),
+            max_attempts,
+            attempt: 0,
+        }
+    }
+
+    /// None with AUTO_RECONNECT=0: the run ends when the stream does.
+    pub fn from_env() -> Option<Self> {
+        if std::env::var("AUTO_RECONNECT").is_ok_and(|v| v == "0") {
+            return None;
+        }
+        let ms = |name, default: f64| {
+            Duration::from_secs_f64(crate::env_parse(name, default).max(1.0) / 1000.0)
+        };
+        Some(Self::new(
+            ms("RECONNECT_BACKOFF_MS", DEFAULT_BACKOFF_INITIAL_MS),
+            ms("RECONNECT_BACKOFF_MAX_MS", DEFAULT_BACKOFF_MAX_MS),
+            crate::env_parse("RECONNECT_MAX_ATTEMPTS", 0),
+        ))
+    }
+
+    /// Delay before the next attempt; None once RECONNECT_MAX_ATTEMPTS is used up.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.max_attempts > 0 && self.attempt >= self.max_attempts {
+            return None;
+        }
+        let delay = self
+            .initial
+            .saturating_mul(1u32 << self.attempt.min(16))
+            .min(self.max);
+        self.attempt += 1;
+        Some(delay)
+    }
+
+    /// Attempts since the last reset (1 = first retry).
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Conexão (TCP_NODELAY + TLS + handshake, SUBSCRIBE)
+// ---------------------------------------------------------------------------
+
+/// TCP socket under a new connection, for kernel-level sampling (TCP_INFO).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SocketInfo {
+    #[cfg(unix)]
+    pub raw_fd: Option<std::os::unix::io::RawFd>,
+}
+
+impl SocketInfo {
+    fn of(ws: &WsStream) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+
+            let raw_fd = match ws.get_ref() {
+                MaybeTlsStream::Plain(tcp) => Some(tcp.as_raw_fd()),
+                MaybeTlsStream::NativeTls(tls) => Some(tls.get_ref().get_ref().get_ref().as_raw_fd()),
+                _ => None,
+            };
+            Self { raw_fd }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = ws;
+            Self {}
+        }
+    }
+}
+
+/// Connects with TCP_NODELAY, then TLS (wss) and the WebSocket handshake.
+pub async fn connect(url: &str, ws_config: Option<WebSocketConfig>) -> Result<WsStream, String> {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    let request = url
+        .into_client_request()
+        .map_err(|e| format!("Invalid URL: {}", e))?;
+    let domain = request.uri().host().ok_or("URL without host")?.to_string();
+    // Sem porta explícita: padrão do esquema (fstream.binance.com usa 443)
+    let default_port = if request.uri().scheme_str() == Some("wss") { 443 } else { 80 };
+    let port = request.uri().port_u16().unwrap_or(default_port);
+
+    // Resolve DNS
+    let addr = tokio::net::lookup_host(format!("{}:{}", domain, port))
+        .await
+        .map_err(|e| format!("DNS Error: {}", e))?
+        .next()
+        .ok_or("No IP address")?;
+
+    // Create socket with TCP_NODELAY
+    let socket = TcpSocket::new_v4().map_err(|e| format!("Error creating socket: {}", e))?;
+    socket
+        .set_nodelay(true)
+        .map_err(|e| format!("Error setting TCP_NODELAY: {}", e))?;
+
+    let tcp_stream = socket
+        .connect(addr)
+        .await
+        .map_err(|e| format!("Error connecting TCP: {}", e))?;
+
+    // TLS + WebSocket handshake
+    let (ws, _) = tokio_tungstenite::client_async_tls_with_config(request, tcp_stream, ws_config, None)
+        .await
+        .map_err(|e| format!("WebSocket handshake error: {}", e))?;
+
+    Ok(ws)
+}
+
+/// Sends `{"method":"SUBSCRIBE",...}` over the write half and waits for the ack
+/// (`{"result":null,"id":1}`). Frames that arrive before the ack are discarded,
+/// so trade counting only starts once the subscription is confirmed.
+async fn subscribe(
+    write: &mut SplitSink<WsStream, Message>,
+    read: &mut SplitStream<WsStream>,
+    stream: &str,
+) -> Result<(), String> {
+    // Vários streams separados por '/': um param cada
+    let params: Vec<String> = stream.split('/').map(|s| format!("\"{}\"", s)).collect();
+    let request = format!(
+        "{{\"method\":\"SUBSCRIBE\",\"params\":[{}],\"id\":{}}}",
+        params.join(","),
+        SUBSCRIBE_ID
+    );
+    write
+        .send(Message::Text(request))
+        .await
+        .map_err(|e| format!("error sending SUBSCRIBE: {}", e))?;
+
+    let deadline = tokio::time::Instant::now() + SUBSCRIBE_TIMEOUT;
+    loop {
+        let msg = tokio::time::timeout_at(deadline, read.next())
+            .await
+            .map_err(|_| format!("no confirmation within {:?}", SUBSCRIBE_TIMEOUT))?;
+
+        match msg {
+            Some(Ok(Message::Text(text))) => {
+                if extract_u64_field(text.as_bytes(), b"\"id\":") != Some(SUBSCRIBE_ID) {
+                    continue;
+                }
+                if text.contains("\"result\":null") {
+                    return Ok(());
+                }
+                return Err(format!("subscription rejected: {}", text));
+            }
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(format!("stream error: {}", e)),
+            None => return Err("connection closed before confirmation".to_string()),
+        }
+    }
+}
+
+/// Framing-layer limits from `WS_MAX_MESSAGE_KB`, `WS_MAX_FRAME_KB` (0 = no
+/// limit) and `WS_WRITE_BUFFER_KB`. Defaults are tungstenite's (64 MiB,
+/// 16 MiB, 128 KiB). The read buffer is fixed at 128 KiB in tungstenite 0.21.
+pub fn ws_config_from_env() -> WebSocketConfig {
+    let limit_kb = |name: &str, default: Option<usize>| match std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+    {
+        Some(0) => None,
+        Some(kb) => Some(kb * 1024),
+        None => default,
+    };
+    let defaults = WebSocketConfig::default();
+    let mut config = defaults;
+    config.max_message_size = limit_kb("WS_MAX_MESSAGE_KB", defaults.max_message_size);
+    config.max_frame_size = limit_kb("WS_MAX_FRAME_KB", defaults.max_frame_size);
+    config.write_buffer_size = crate::env_parse("WS_WRITE_BUFFER_KB", defaults.write_buffer_size / 1024) * 1024;
+    config
+}
+
+/// One-line summary of the effective WebSocket config (startup banner).
+pub fn describe_ws_config(config: &WebSocketConfig) -> String {
+    let limit = |v: Option<usize>| v.map_or("unlimited".to_string(), |b| format!("{}KB", b / 1024));
+    format!(
+        "max_message={} max_frame={} write_buffer={}KB",
+        limit(config.max_message_size),
+        limit(config.max_frame_size),
+        config.write_buffer_size / 1024
+    )
+}
+
+/// Fallible connect (TCP_NODELAY, `WS_*` framing limits), for the modes that
+/// drive their own connections.
+pub async fn try_connect_ws(url: &str) -> Result<WsStream, String> {
+    connect(url, Some(ws_config_from_env())).await
+}
+
+struct Link {
+    write: SplitSink<WsStream, Message>,
+    read: SplitStream<WsStream>,
+}
+
+impl Link {
+    async fn close(mut self) {
+        let _ = self.write.close().await;
+    }
+}
+
+/// Connects, splits and, in SUBSCRIBE mode, waits for the subscription ack.
+async fn open(url: &str, subscribe_to: Option<&str>, ws_config: Option<WebSocketConfig>) -> Result<(Link, SocketInfo), String> {
+    let ws = connect(url, ws_config).await?;
+    let socket = SocketInfo::of(&ws);
+    let (mut write, mut read) = ws.split();
+    if let Some(stream) = subscribe_to {
+        subscribe(&mut write, &mut read, stream)
+            .await
+            .map_err(|e| format!("Subscription failed: {}", e))?;
+    }
+    Ok((Link { write, read }, socket))
+}
+
+// ---------------------------------------------------------------------------
+// Eventos
+// ---------------------------------------------------------------------------
+
+/// Why a connection was opened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectReason {
+    /// First connection of the run.
+    Initial,
+    /// After the stream ended (`reconnect` backoff), n-th attempt.
+    Reconnect { attempt: u32 },
+    /// `Collector::reconnect`: the new connection is up and the old one closed.
+    Forced,
+    /// `Collector::open_standby`: a second connection read alongside the active one.
+    Standby,
+}
+
+/// One step of the collection, in arrival order.
+#[derive(Debug)]
+pub enum Event {
+    Connected { reason: ConnectReason, socket: SocketInfo },
+    /// A frame, stamped right after the read returned. `standby`: it came
+    /// from the standby connection. Pings are answered before this is yielded.
+    Message { msg: Message, recv_instant: Instant, standby: bool },
+    /// Read error on the active connection; the connection ends after it.
+    Error(tungstenite::Error),
+    /// Outcome of the pong sent for the previous `Message::Ping`.
+    Pong(Result<(), String>),
+    /// The active connection ended; the reconnect attempts follow.
+    Disconnected,
+    Reconnecting { attempt: u32, delay: Duration },
+    ReconnectFailed { attempt: u32, error: String },
+    /// Reconnect attempts used up; the stream ends after this.
+    GaveUp { attempts: u32 },
+    /// The first connection failed; the stream ends after this.
+    ConnectFailed(String),
+    /// `Collector::reconnect` could not connect; the old connection stays.
+    ForcedReconnectFailed(String),
+    StandbyFailed(String),
+    /// The standby connection `"closed"` or `"ended"` before taking over.
+    StandbyLost(&'static str),
+    /// The standby took over (on request, or because the active one ended).
+    Promoted(&'static str),
+}
+
+enum Command {
+    OpenStandby,
+    Promote(&'static str),
+    Reconnect,
+}
+
+/// Result of the read `select!`.
+enum Step {
+    Command(Command),
+    Active(Option<Result<Message, tungstenite::Error>>),
+    Standby(Option<Result<Message, tungstenite::Error>>),
+}
+
+/// State behind the event stream: the connections and what is pending.
+struct Driver {
+    url: String,
+    subscribe: Option<String>,
+    ws_config: Option<WebSocketConfig>,
+    backoff: Option<Backoff>,
+    active: Option<Link>,
+    standby: Option<Link>,
+    commands: mpsc::UnboundedReceiver<Command>,
+    pending: VecDeque<Event>,
+    /// Delay of the reconnect attempt announced by `Reconnecting`.
+    retry_in: Option<Duration>,
+    /// Set on disconnect: the first message afterwards resets the backoff.
+    outage: bool,
+    started: bool,
+    done: bool,
+}
+
+impl Driver {
+    async fn open(&self) -> Result<(Link, SocketInfo), String> {
+        open(&self.url, self.subscribe.as_deref(), self.ws_config).await
+    }
+
+    /// The standby becomes the active connection; the old one is closed only now.
+    async fn promote(&mut self, reason: &'static str) -> Option<Event> {
+        let standby = self.standby.take()?;
+        if let Some(old) = self.active.replace(standby) {
+            old.close().await;
+        }
+        Some(Event::Promoted(reason))
+    }
+
+    async fn next_event(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            if self.done {
+                return None;
+            }
+            if !self.started {
+                self.started = true;
+                return Some(match self.open().await {
+                    Ok((link, socket)) => {
+                        self.active = Some(link);
+                        Event::Connected { reason: ConnectReason::Initial, socket }
+                    }
+                    Err(e) => {
+                        self.done = true;
+                        Event::ConnectFailed(e)
+                    }
+                });
+            }
+            if self.active.is_none() {
+                return Some(self.reconnect_step().await);
+            }
+
+            let Self { commands, active, standby, .. } = self;
+            let active = active.as_mut()?;
+            let step = tokio::select! {
+                biased;
+                Some(command) = commands.recv() => Step::Command(command),
+                msg = active.read.next() => Step::Active(msg),
+                msg = next_standby(standby), if standby.is_some() => Step::Standby(msg),
+            };
+            // Timestamp IMMEDIATELY — before any processing
+            let recv_instant = Instant::now();
+
+            match step {
+                Step::Command(Command::OpenStandby) => {
+                    if self.standby.is_some() {
+                        continue;
+                    }
+                    return Some(match self.open().await {
+                        Ok((link, socket)) => {
+                            self.standby = Some(link);
+                            Event::Connected { reason: ConnectReason::Standby, socket }
+                        }
+                        Err(e) => Event::StandbyFailed(e),
+                    });
+                }
+                Step::Command(Command::Promote(reason)) => match self.promote(reason).await {
+                    Some(event) => return Some(event),
+                    None => continue,
+                },
+                Step::Command(Command::Reconnect) => {
+                    // Make-before-break: a conexão antiga só é fechada se a nova subir
+                    return Some(match self.open().await {
+                        Ok((link, socket)) => {
+                            if let Some(old) = self.active.replace(link) {
+                                old.close().await;
+                            }
+                            Event::Connected { reason: ConnectReason::Forced, socket }
+                        }
+                        Err(e) => Event::ForcedReconnectFailed(e),
+                    });
+                }
+                // Sobreposição: queda de uma das conexões não encerra a coleta
+                Step::Active(Some(Ok(Message::Close(_)) | Err(_))) if self.standby.is_some() => {
+                    return self.promote("old connection closed").await;
+                }
+                Step::Active(None) if self.standby.is_some() => {
+                    return self.promote("old connection ended").await;
+                }
+                Step::Active(Some(Ok(msg))) => {
+                    if self.outage {
+                        self.outage = false;
+                        // Conexão nova entregou dados: o próximo corte recomeça do backoff inicial
+                        if let Some(backoff) = self.backoff.as_mut() {
+                            backoff.reset();
+                        }
+                    }
+                    if let Message::Ping(payload) = &msg {
+                        let pong = self.active.as_mut()?.write.send(Message::Pong(payload.clone())).await;
+                        self.pending.push_back(Event::Pong(pong.map_err(|e| e.to_string())));
+                    }
+                    return Some(Event::Message { msg, recv_instant, standby: false });
+                }
+                Step::Active(Some(Err(e))) => return Some(Event::Error(e)),
+                Step::Active(None) => {
+                    self.active = None;
+                    if self.backoff.is_none() {
+                        self.done = true;
+                        return None;
+                    }
+                    self.outage = true;
+                    return Some(Event::Disconnected);
+                }
+                Step::Standby(Some(Ok(Message::Close(_)) | Err(_))) => {
+                    self.standby = None;
+                    return Some(Event::StandbyLost("closed"));
+                }
+                Step::Standby(None) => {
+                    self.standby = None;
+                    return Some(Event::StandbyLost("ended"));
+                }
+                Step::Standby(Some(Ok(msg))) => {
+                    // Pong na conexão que recebeu o ping
+                    if let Message::Ping(payload) = &msg {
+                        let pong = self.standby.as_mut()?.write.send(Message::Pong(payload.clone())).await;
+                        self.pending.push_back(Event::Pong(pong.map_err(|e| e.to_string())));
+                    }
+                    return Some(Event::Message { msg, recv_instant, standby: true });
+                }
+            }
+        }
+    }
+
+    /// One step of the reconnect loop: announce the attempt, then make it.
+    async fn reconnect_step(&mut self) -> Event {
+        let Some(backoff) = self.backoff.as_mut() else {
+            self.done = true;
+            return Event::GaveUp { attempts: 0 };
+        };
+        let Some(delay) = self.retry_in.take() else {
+            return match backoff.next_delay() {
+                Some(delay) => {
+                    self.retry_in = Some(delay);
+                    Event::Reconnecting { attempt: backoff.attempt(), delay }
+                }
+                None => {
+                    self.done = true;
+                    Event::GaveUp { attempts: backoff.attempt() }
+                }
+            };
+        };
+        let attempt = backoff.attempt();
+        tokio::time::sleep(delay).await;
+        match self.open().await {
+            Ok((link, socket)) => {
+                self.active = Some(link);
+                Event::Connected { reason: ConnectReason::Reconnect { attempt }, socket }
+            }
+            Err(error) => Event::ReconnectFailed { attempt, error },
+        }
+    }
+}
+
+/// Next frame of the standby connection; never resolves while there is none.
+async fn next_standby(standby: &mut Option<Link>) -> Option<Result<Message, tungstenite::Error>> {
+    match standby {
+        Some(link) => link.read.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Collector
+// ---------------------------------------------------------------------------
+
+/// Event stream of one collection, plus the requests that act on its
+/// connections (applied before the next frame is read).
+pub struct Collector {
+    events: Pin<Box<dyn Stream<Item = Event> + Send>>,
+    commands: mpsc::UnboundedSender<Command>,
+    min_trades: Option<usize>,
+    clock_offset_us: i64,
+    reconnects: bool,
+}
+
+/// Starts the collection: the first event is `Connected` or `ConnectFailed`.
+/// Without `reconnect` the stream ends with the connection.
+pub fn run_collector(config: CollectorConfig) -> Collector {
+    let (commands, commands_rx) = mpsc::unbounded_channel();
+    let driver = Driver {
+        url: config.url(),
+        subscribe: config.subscribe.clone(),
+        ws_config: config.ws_config,
+        backoff: config.reconnect.clone(),
+        active: None,
+        standby: None,
+        commands: commands_rx,
+        pending: VecDeque::new(),
+        retry_in: None,
+        outage: false,
+        started: false,
+        done: false,
+    };
+    let events = futures_util::stream::unfold(driver, |mut driver| async move {
+        let event = driver.next_event().await?;
+        Some((event, driver))
+    });
+    Collector {
+        events: Box::pin(events),
+        commands,
+        min_trades: config.min_trades,
+        clock_offset_us: config.clock_offset_us,
+        reconnects: config.reconnect.is_some(),
+    }
+}
+
+impl Collector {
+    /// Opens a second connection read alongside the active one
+    /// (`Connected { reason: Standby }` or `StandbyFailed`).
+    pub fn open_standby(&self) {
+        let _ = self.commands.send(Command::OpenStandby);
+    }
+
+    /// The standby takes over and the old connection is closed (`Promoted`).
+    pub fn promote(&self, reason: &'static str) {
+        let _ = self.commands.send(Command::Promote(reason));
+    }
+
+    /// Replaces the active connection, closing the old one only once the new
+    /// one is up (`Connected { reason: Forced }` or `ForcedReconnectFailed`).
+    pub fn reconnect(&self) {
+        let _ = self.commands.send(Command::Reconnect);
+    }
+
+    /// Trade frames as `TradeRecord`s. Ends after `min_trades` trades, or
+    /// with a final `Err` when the connection fails or closes (with
+    /// `reconnect`, closes and errors are yielded as `Err` and collection goes on).
+    pub fn trades(self) -> impl Stream<Item = Result<TradeRecord, String>> {
+        let clock = ClockRef::new();
+        let (min_trades, clock_offset_us, reconnects) = (self.min_trades, self.clock_offset_us, self.reconnects);
+        futures_util::stream::unfold((self, clock, 0usize, false), move |(mut events, clock, mut received, ended)| async move {
+            if ended || min_trades.is_some_and(|n| received >= n) {
+                return None;
+            }
+            loop {
+                let (item, end) = match events.next().await {
+                    Some(Event::Message { msg: Message::Text(text), recv_instant, .. }) => {
+                        let data = text.as_bytes();
+                        let Some((trade_id, trade_ts_us)) =
+                            extract_trade_data(data).and_then(|(id, ts_ms)| Some((id, ms_to_us(ts_ms)?)))
+                        else {
+                            continue;
+                        };
+                        let recv_ts_us = clock.to_epoch_us(recv_instant);
+                        received += 1;
+                        let record = TradeRecord {
+                            trade_id,
+                            trade_ts_us: trade_ts_us as u64,
+                            recv_ts_us,
+                            latency_us: recv_ts_us as i64 - trade_ts_us - clock_offset_us,
+                            event_latency_us: extract_event_ts(data)
+                                .and_then(ms_to_us)
+                                .map(|event_ts_us| recv_ts_us as i64 - event_ts_us - clock_offset_us),
+                        };
+                        (Ok(record), false)
+                    }
+                    Some(Event::Message { msg: Message::Close(frame), .. }) => {
+                        (Err(format!("server closed connection ({:?})", frame)), !reconnects)
+                    }
+                    Some(Event::Error(e)) => (Err(format!("WebSocket error: {}", e)), !reconnects),
+                    Some(Event::ConnectFailed(e)) => (Err(e), true),
+                    Some(Event::GaveUp { attempts }) => {
+                        (Err(format!("gave up after {} reconnect attempts", attempts)), true)
+                    }
+                    Some(_) => continue,
+                    None => (Err("stream ended".to_string()), true),
+                };
+                return Some((item, (events, clock, received, end)));
+            }
+        })
+    }
+}
+
+impl Stream for Collector {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        self.events.as_mut().poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T0_MS: u64 = 1_700_000_000_000;
+
+    fn trade_frame(trade_id: u64) -> Message {
+        Message::Text(format!(
+            r#"{{"e":"trade","E":{},"s":"BTCUSDT","t":{},"p":"1.0","q":"2.0","T":{},"m":true,"M":true}}"#,
+            T0_MS + trade_id + 1,
+            trade_id,
+            T0_MS + trade_id
+        ))
+    }
+
+    /// Local server: one accepted connection per script, served concurrently.
+    /// Each sends its frames (a `Close` ends it from the server side) and then
+    /// reads until the client closes. Returns the URL and the pongs received.
+    async fn serve(scripts: Vec<Vec<Message>>) -> (String, tokio::task::JoinHandle<Vec<Vec<u8>>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/ws/btcusdt@trade", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut connections = Vec::new();
+            for frames in scripts {
+                let (tcp, _) = listener.accept().await.unwrap();
+                connections.push(tokio::spawn(async move {
+                    let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+                    let mut pongs = Vec::new();
+                    for frame in frames {
+                        let ping = matches!(frame, Message::Ping(_));
+                        ws.send(frame).await.unwrap();
+                        // Espera o pong antes de seguir: o coletor responde sozinho
+                        if ping {
+                            loop {
+                                match ws.next().await {
+                                    Some(Ok(Message::Pong(payload))) => {
+                                        pongs.push(payload);
+                                        break;
+                                    }
+                                    Some(Ok(_)) => continue,
+                                    other => panic!("no pong: {:?}", other),
+                                }
+                            }
+                        }
+                    }
+                    // Drena até o cliente fechar (handshake de close)
+                    while let Some(Ok(_)) = ws.next().await {}
+                    pongs
+                }));
+            }
+            let mut pongs = Vec::new();
+            for connection in connections {
+                pongs.extend(connection.await.unwrap());
+            }
+            pongs
+        });
+        (url, server)
+    }
+
+    fn config(url: String) -> CollectorConfig {
+        let mut config = CollectorConfig::new("btcusdt");
+        config.ws_url = Some(url);
+        config
+    }
+
+    #[tokio::test]
+    async fn trades_from_a_canned_stream() {
+        let frames = vec![
+            trade_frame(1),
+            Message::Ping(b"hb".to_vec()),
+            Message::Text(r#"{"result":null,"id":7}"#.to_string()),
+            trade_frame(2),
+            trade_frame(3),
+            Message::Close(None),
+        ];
+        let (url, server) = serve(vec![frames]).await;
+        let trades: Vec<_> = run_collector(config(url)).trades().collect().await;
+
+        assert_eq!(trades.len(), 4, "{:?}", trades);
+        for (i, trade) in trades[..3].iter().enumerate() {
+            let trade = trade.as_ref().unwrap();
+            let trade_id = i as u64 + 1;
+            assert_eq!(trade.trade_id, trade_id);
+            assert_eq!(trade.trade_ts_us, (T0_MS + trade_id) * 1000);
+            assert_eq!(trade.latency_us, trade.recv_ts_us as i64 - trade.trade_ts_us as i64);
+            assert_eq!(trade.event_latency_us, Some(trade.latency_us - 1000));
+        }
+        assert!(trades[3].as_ref().unwrap_err().contains("closed"));
+        assert_eq!(server.await.unwrap(), vec![b"hb".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn min_trades_ends_the_stream() {
+        let (url, _server) = serve(vec![(1..=5).map(trade_frame).chain([Message::Close(None)]).collect()]).await;
+        let mut config = config(url);
+        config.min_trades = Some(2);
+        let ids: Vec<u64> = run_collector(config)
+            .trades()
+            .map(|t| t.unwrap().trade_id)
+            .collect()
+            .await;
+        assert_eq!(ids, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn reconnects_with_backoff_until_it_gives_up() {
+        let (url, server) = serve(vec![
+            vec![trade_frame(1), Message::Close(None)],
+            vec![trade_frame(2), trade_frame(3), Message::Close(None)],
+        ]).await;
+        let mut config = config(url);
+        config.reconnect = Some(Backoff::new(Duration::from_millis(1), Duration::from_millis(1), 2));
+        let mut collector = run_collector(config);
+        let mut trace = Vec::new();
+        while let Some(event) = collector.next().await {
+            trace.push(match event {
+                Event::Connected { reason, .. } => format!("connected {:?}", reason),
+                Event::Message { msg: Message::Text(text), .. } => {
+                    format!("trade {}", extract_trade_data(text.as_bytes()).unwrap().0)
+                }
+                Event::Message { msg, .. } => format!("message {:?}", msg),
+                Event::Reconnecting { attempt, .. } => format!("reconnecting {}", attempt),
+                Event::ReconnectFailed { attempt, .. } => format!("failed {}", attempt),
+                other => format!("{:?}", other),
+            });
+            // Fim do script: sem servidor, as próximas tentativas falham
+            if trace.last().is_some_and(|t| t == "trade 3") {
+                server.abort();
+            }
+        }
+        assert_eq!(
+            trace,
+            [
+                "connected Initial",
+                "trade 1",
+                "message Close(None)",
+                "Disconnected",
+                "reconnecting 1",
+                "connected Reconnect { attempt: 1 }",
+                "trade 2",
+                "trade 3",
+                "message Close(None)",
+                "Disconnected",
+                // Backoff zerado pela conexão que entregou dados
+                "reconnecting 1",
+                "failed 1",
+                "reconnecting 2",
+                "failed 2",
+                "GaveUp { attempts: 2 }",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn standby_takes_over_on_request() {
+        let (url, _server) = serve(vec![
+            // A ativa só fecha quando o coletor a troca pelo standby
+            vec![trade_frame(1), Message::Ping(b"a".to_vec())],
+            vec![trade_frame(1), trade_frame(2), Message::Close(None)],
+        ])
+        .await;
+        let mut collector = run_collector(config(url));
+        assert!(matches!(
+            collector.next().await,
+            Some(Event::Connected { reason: ConnectReason::Initial, .. })
+        ));
+        assert!(matches!(collector.next().await, Some(Event::Message { standby: false, .. })));
+        collector.open_standby();
+        // O ping da ativa pode chegar antes do standby abrir
+        let mut standby_ids = Vec::new();
+        let mut promoted = false;
+        while let Some(event) = collector.next().await {
+            match event {
+                Event::Connected { reason, .. } => assert_eq!(reason, ConnectReason::Standby),
+                Event::Message { msg: Message::Text(text), standby: true, .. } => {
+                    standby_ids.push(extract_trade_data(text.as_bytes()).unwrap().0);
+                    if standby_ids.len() == 2 {
+                        collector.promote("test");
+                    }
+                }
+                Event::Promoted(reason) => {
+                    assert_eq!(reason, "test");
+                    promoted = true;
+                }
+                // Depois de assumir, o standby é a ativa: o close dela encerra o stream
+                Event::Message { msg: Message::Close(_), standby: false, .. } if promoted => {}
+                Event::Message { .. } | Event::Pong(_) => {}
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert_eq!(standby_ids, vec![1, 2]);
+        assert!(promoted);
+    }
+}
@@ -0,0 +1,154 @@
+//! Comparação estatística entre duas capturas (CSV gerados por este programa).
+//!
+//! Two-sample Kolmogorov–Smirnov: responde "as distribuições de latência
+//! diferem?" em vez de comparar percentis a olho.
+//!
+//! Uso:
+//!   cargo run --release -- --compare antes.csv depois.csv
+
+use std::io::BufRead;
+
+use crate::stats::{percentile, PercentileMethod};
+
+/// Significance level used for the yes/no verdict.
+pub const ALPHA: f64 = 0.05;
+
+/// Loads the `trade_latency_us` column from a capture CSV (`latency_us` in
+/// captures from older versions).
+pub fn load_latencies(path: &str) -> std::io::Result<Vec<i64>> {
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut lines = file.lines();
+
+    let header = lines.next().transpose()?.unwrap_or_default();
+    let col = header
+        .split(',')
+        .position(|c| c.trim() == "trade_latency_us")
+        .or_else(|| header.split(',').position(|c| c.trim() == "latency_us"))
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: no trade_latency_us column in header", path),
+            )
+        })?;
+
+    let mut latencies = Vec::new();
+    for line in lines {
+        let line = line?;
+        // Caminho rápido sem aspas; label/machine_id com vírgula vêm entre aspas
+        let value = if line.contains('"') {
+            crate::csv::split_line(&line).get(col).and_then(|v| v.trim().parse().ok())
+        } else {
+            line.split(',').nth(col).and_then(|v| v.trim().parse().ok())
+        };
+        if let Some(v) = value {
+            latencies.push(v);
+        }
+    }
+    Ok(latencies)
+}
+
+/// Two-sample KS statistic D = sup |F1(x) - F2(x)|. Both slices must be sorted.
+pub fn ks_statistic(a: &[i64], b: &[i64]) -> f64 {
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let (mut i, mut j) = (0, 0);
+    let mut d: f64 = 0.0;
+
+    while i < a.len() && j < b.len() {
+        // Avança ambos sobre valores empatados antes de medir a distância
+        let x = a[i].min(b[j]);
+        while i < a.len() && a[i] == x {
+            i += 1;
+        }
+        while j < b.len() && b[j] == x {
+            j += 1;
+        }
+        d = d.max((i as f64 / n1 - j as f64 / n2).abs());
+    }
+    d
+}
+
+/// Asymptotic p-value for D (Kolmogorov distribution, Stephens' correction).
+pub fn ks_p_value(d: f64, n1: usize, n2: usize) -> f64 {
+    let en = ((n1 * n2) as f64 / (n1 + n2) as f64).sqrt();
+    let lambda = (en + 0.12 + 0.11 / en) * d;
+
+    let mut sum = 0.0;
+    for j in 1..=100 {
+        let sign = if j % 2 == 1 { 1.0 } else { -1.0 };
+        let term = sign * (-2.0 * (j * j) as f64 * lambda * lambda).exp();
+        sum += term;
+        if term.abs() < 1e-12 {
+            break;
+        }
+    }
+    (2.0 * sum).clamp(0.0, 1.0)
+}
+
+/// Loads both captures, prints percentiles side by side and the KS result.
+pub fn run(path_a: &str, path_b: &str) -> std::io::Result<()> {
+    let mut a = load_latencies(path_a)?;
+    let mut b = load_latencies(path_b)?;
+
+    eprintln!("=== Latency Distribution Comparison (KS test) ===");
+    eprintln!("A: {} ({} samples)", path_a, a.len());
+    eprintln!("B: {} ({} samples)", path_b, b.len());
+
+    if a.is_empty() || b.is_empty() {
+        eprintln!("  WARNING: Both captures need at least one sample");
+        return Ok(());
+    }
+
+    a.sort_unstable();
+    b.sort_unstable();
+
+    // Mesma definição do relatório da coleta (PERCENTILE_METHOD)
+    let method = PercentileMethod::from_env();
+    eprintln!("\n{:<8} {:>12} {:>12} {:>12}", "", "A (µs)", "B (µs)", "B - A (µs)");
+    for (name, q) in [("P50", 0.50), ("P95", 0.95), ("P99", 0.99)] {
+        let (pa, pb) = (percentile(&a, q, method), percentile(&b, q, method));
+        eprintln!("{:<8} {:>12} {:>12} {:>+12}", name, pa, pb, pb - pa);
+    }
+
+    let d = ks_statistic(&a, &b);
+    let p = ks_p_value(d, a.len(), b.len());
+
+    eprintln!("\nKS statistic (D): {:.6}", d);
+    eprintln!("p-value (approx): {:.6}", p);
+    if p < ALPHA {
+        eprintln!("Result: distributions DIFFER (p < {})", ALPHA);
+    } else {
+        eprintln!("Result: no significant difference (p >= {})", ALPHA);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, contents: &str) -> std::io::Result<Vec<i64>> {
+        let path = std::env::temp_dir().join(format!("compare_{}_{}.csv", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        let result = load_latencies(path.to_str().unwrap());
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    #[test]
+    fn reads_trade_latency_column() {
+        let csv = format!(
+            "{}\nrun,m1,1,1000,2100,1100,0,1050\n\"a,b\",m1,2,1000,2300,1300,0,\n",
+            binance_trades::output::CSV_HEADER
+        );
+        assert_eq!(load("current", &csv).unwrap(), vec![1100, 1300]);
+    }
+
+    #[test]
+    fn falls_back_to_legacy_latency_column() {
+        let csv = "label,machine_id,trade_id,trade_ts_us,recv_ts_us,latency_us,clock_offset_us\n\
+                   run,m1,1,1000,1900,900,0\n";
+        assert_eq!(load("legacy", csv).unwrap(), vec![900]);
+        let err = load("no_column", "label,trade_id\nrun,1\n").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
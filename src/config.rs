@@ -0,0 +1,296 @@
+//! Configuração de uma coleta a partir da linha de comando e do ambiente.
+//!
+//! O binário só faz o parse dos argumentos (`CliArgs`); aqui ficam as
+//! variáveis de ambiente, as validações e o que deriva delas: URL do stream,
+//! arquivo de saída e formato. Erros voltam como texto para o binário
+//! mostrar e sair com código 2, antes de qualquer conexão.
+
+use crate::clock::wall_clock_us;
+use crate::collector::BINANCE_WS_BASE;
+use crate::extract::{FieldMap, StreamKind};
+use crate::output::csv_path_for_symbol;
+use crate::{display, env_flag, env_parse, gzip};
+
+pub const DEFAULT_SYMBOL: &str = "btcusdt";
+pub const DEFAULT_COUNT: usize = 100_000;
+
+/// Binance deployment for the collector (`--exchange` / `BINANCE_EXCHANGE`).
+///
+/// Os três usam os mesmos nomes de stream e o mesmo JSON (`trade`, `aggTrade`,
+/// `depth`, `!ticker@arr`) e o mesmo `/api/v3/time`; o que muda:
+/// - `us`: Binance.US, lista de símbolos própria (pares em USD como `btcusd`,
+///   muitos `*usdt` não existem) e volume bem menor
+/// - `testnet`: spot testnet, poucos trades (gaps de tempo longos) e os dados
+///   são zerados periodicamente: `trade_id` recomeça (ver ID_RESET_THRESHOLD)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Exchange {
+    Com,
+    Us,
+    Testnet,
+}
+
+impl Exchange {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "com" | "binance" => Some(Exchange::Com),
+            "us" => Some(Exchange::Us),
+            "testnet" => Some(Exchange::Testnet),
+            _ => None,
+        }
+    }
+
+    pub fn ws_base(self) -> &'static str {
+        match self {
+            Exchange::Com => BINANCE_WS_BASE,
+            Exchange::Us => "wss://stream.binance.us:9443/ws",
+            Exchange::Testnet => "wss://stream.testnet.binance.vision/ws",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Exchange::Com => "binance.com",
+            Exchange::Us => "binance.us",
+            Exchange::Testnet => "spot testnet",
+        }
+    }
+
+    /// REST base for the clock calibration (`/api/v3/time`).
+    pub fn rest_base(self) -> &'static str {
+        match self {
+            Exchange::Com => "https://api.binance.com",
+            Exchange::Us => "https://api.binance.us",
+            Exchange::Testnet => "https://testnet.binance.vision",
+        }
+    }
+}
+
+/// What the command line chose for a collection run.
+pub struct CliArgs {
+    /// Positional or `--symbol`: `btcusdt`, `btcusdt,ethusdt`, `btcusdt@aggTrade`.
+    pub symbol: String,
+    /// `--stream-type` (wins over the `@` suffix and `STREAM_TYPE`).
+    pub stream_type: Option<String>,
+    pub count: usize,
+    pub label: String,
+    /// `--endpoint` / `BINANCE_ENDPOINT`: `wss://host:port[/ws]`.
+    pub endpoint: Option<String>,
+    pub exchange: Exchange,
+}
+
+/// Everything a collection run needs to know before connecting.
+pub struct RunConfig {
+    pub exchange: Exchange,
+    /// As given, without the `@stream` suffix (comma-separated for several).
+    pub symbol: String,
+    /// Lowercased, one per stream.
+    pub symbols: Vec<String>,
+    pub multi_symbol: bool,
+    pub stream_type: String,
+    pub stream_kind: StreamKind,
+    /// `btcusdt@trade`, `btcusdt@trade/ethusdt@trade` or `!ticker@arr`.
+    pub stream_name: String,
+    /// Name in the display and report (`SYMBOL_ALIASES`, or the stream for `!...`).
+    pub display_name: String,
+    pub url: String,
+    /// SUBSCRIBE=1: stream to subscribe to after connecting to `url`.
+    pub subscribe: Option<String>,
+    pub count: usize,
+    pub label: String,
+    pub machine_id: String,
+    /// CSV, Parquet or (SUMMARY_ONLY) summary JSON; `.gz` already appended.
+    pub output_file: String,
+    pub parquet_output: bool,
+    pub summary_only: bool,
+    pub csv_gzip: bool,
+    pub csv_fsync: bool,
+}
+
+impl RunConfig {
+    pub fn from_env(cli: CliArgs) -> Result<Self, String> {
+        // "btcusdt@aggTrade": o sufixo vale como STREAM_TYPE
+        let (symbol, stream_suffix) = match cli.symbol.split_once('@') {
+            Some((symbol, stream_type)) => (symbol.to_string(), Some(stream_type.to_string())),
+            None => (cli.symbol.clone(), None),
+        };
+        // STREAM_TYPE=trade|aggTrade|depth@100ms|depth5@100ms...
+        let stream_type = cli
+            .stream_type
+            .or(stream_suffix)
+            .or_else(|| std::env::var("STREAM_TYPE").ok())
+            .unwrap_or_else(|| "trade".to_string());
+        // O símbolo vai direto no path da URL: só letras e dígitos (minúsculas no path)
+        if std::env::var("WS_URL").is_err() && !stream_type.starts_with('!') {
+            let parts: Vec<&str> = symbol.split(',').map(str::trim).filter(|s| !s.is_empty()).collect();
+            if parts.is_empty() {
+                return Err("No symbol given".to_string());
+            }
+            if let Some(bad) = parts.iter().find(|s| !s.bytes().all(|b| b.is_ascii_alphanumeric())) {
+                return Err(format!(
+                    "Invalid symbol {:?}: expected letters and digits only (e.g. btcusdt)",
+                    bad
+                ));
+            }
+        }
+
+        // Machine ID via variável de ambiente (essencial para múltiplas instâncias)
+        let machine_id = std::env::var("MACHINE_ID")
+            .or_else(|_| std::env::var("AWS_REGION"))
+            .unwrap_or_else(|_| "unknown".to_string());
+
+        let parquet_output = parquet_output_from_env()?;
+        let output_ext = if parquet_output { "parquet" } else { "csv" };
+
+        // Arquivo de saída único por instância (evita conflitos)
+        // SUMMARY_ONLY=1: sem CSV por trade, só um JSON de resumo (SUMMARY_FILE)
+        let summary_only = env_flag("SUMMARY_ONLY");
+        let output_file = if summary_only {
+            std::env::var("SUMMARY_FILE").unwrap_or_else(|_| {
+                format!("summary_{}_{}.json", machine_id, wall_clock_us().max(0) / 1_000_000)
+            })
+        } else if env_flag("CSV_SPLIT_BY_SYMBOL") {
+            // Um arquivo por símbolo: latency_<symbol>.csv (ou CSV_FILE com o símbolo no nome)
+            let base = std::env::var("CSV_FILE").unwrap_or_else(|_| format!("latency.{}", output_ext));
+            csv_path_for_symbol(&base, &symbol.to_lowercase())
+        } else {
+            std::env::var("CSV_FILE").unwrap_or_else(|_| {
+                format!("trades_{}_{}.{}", machine_id, wall_clock_us().max(0) / 1_000_000, output_ext)
+            })
+        };
+        // CSV_GZIP=1: mesmo nome com `.gz` (o resumo JSON não é comprimido)
+        let csv_gzip = !summary_only && gzip::enabled();
+        let output_file = if csv_gzip { gzip::path_for(&output_file) } else { output_file };
+
+        let stream_kind = StreamKind::from_stream_type(&stream_type);
+        // "btcusdt,ethusdt": vários símbolos numa conexão combinada, stats por símbolo
+        let symbols: Vec<String> = symbol
+            .split(',')
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+        let multi_symbol = symbols.len() > 1;
+        if multi_symbol && stream_kind != StreamKind::Trade {
+            return Err("Multiple symbols are only supported with STREAM_TYPE=trade".to_string());
+        }
+        // Streams "!..." são do mercado inteiro: sem símbolo no nome
+        let stream_name = if stream_type.starts_with('!') {
+            stream_type.clone()
+        } else {
+            symbols
+                .iter()
+                .map(|s| format!("{}@{}", s, stream_type))
+                .collect::<Vec<_>>()
+                .join("/")
+        };
+        // SYMBOL_ALIASES: nome amigável no display/relatório (CSV mantém o símbolo)
+        let display_name = if stream_kind == StreamKind::TickerArray {
+            stream_type.clone()
+        } else {
+            display::symbol_display_name(&symbol)
+        };
+
+        // SUBSCRIBE=1: conecta no endpoint base /ws e assina o stream via mensagem,
+        // em vez de codificar o stream na URL.
+        let subscribe_mode = env_flag("SUBSCRIBE");
+        // --endpoint wss://host:port[/ws]: outro servidor com o layout da Binance
+        let ws_base = match cli.endpoint {
+            Some(endpoint) => {
+                let endpoint = endpoint.trim_end_matches('/');
+                if endpoint.ends_with("/ws") {
+                    endpoint.to_string()
+                } else {
+                    format!("{}/ws", endpoint)
+                }
+            }
+            None => cli.exchange.ws_base().to_string(),
+        };
+        // WS_URL: outro feed (URL completa, sem stream no path); FIELD_*: nomes dos campos
+        let url = match std::env::var("WS_URL") {
+            Ok(url) => url,
+            Err(_) if subscribe_mode => ws_base,
+            Err(_) if multi_symbol => format!(
+                "{}/stream?streams={}",
+                ws_base.trim_end_matches("/ws"),
+                stream_name
+            ),
+            Err(_) => format!("{}/{}", ws_base, stream_name),
+        };
+        let subscribe = subscribe_mode.then(|| stream_name.clone());
+
+        Ok(Self {
+            exchange: cli.exchange,
+            symbol,
+            symbols,
+            multi_symbol,
+            stream_type,
+            stream_kind,
+            stream_name,
+            display_name,
+            url,
+            subscribe,
+            count: cli.count,
+            label: cli.label,
+            machine_id,
+            output_file,
+            parquet_output,
+            summary_only,
+            csv_gzip,
+            csv_fsync: env_flag("CSV_FSYNC"),
+        })
+    }
+
+    /// FIELD_*: custom field names, only for a single-symbol `trade` stream
+    /// (warns and ignores them otherwise).
+    pub fn field_map(&self) -> Option<FieldMap> {
+        match FieldMap::from_env() {
+            Some(_) if self.stream_kind != StreamKind::Trade => {
+                eprintln!("  WARNING: FIELD_* only apply to STREAM_TYPE=trade; ignored");
+                None
+            }
+            Some(_) if self.multi_symbol => {
+                eprintln!("  WARNING: FIELD_* are not supported with multiple symbols; ignored");
+                None
+            }
+            Some(map) => {
+                eprintln!("Fields:     {}", map.describe());
+                Some(map)
+            }
+            None => None,
+        }
+    }
+}
+
+/// OUTPUT_FORMAT=parquet: um arquivo escrito no fim (precisa da feature
+/// `parquet`), que não combina com os modos de streaming do CSV.
+fn parquet_output_from_env() -> Result<bool, String> {
+    let parquet = match std::env::var("OUTPUT_FORMAT").as_deref().map(str::to_ascii_lowercase).as_deref() {
+        Err(_) | Ok("csv") => false,
+        Ok("parquet") if cfg!(feature = "parquet") => true,
+        Ok("parquet") => {
+            return Err(
+                "OUTPUT_FORMAT=parquet is not in this build; rebuild with `cargo build --release --features parquet`"
+                    .to_string(),
+            )
+        }
+        Ok(other) => return Err(format!("Unknown OUTPUT_FORMAT {:?} (use csv or parquet)", other)),
+    };
+    if parquet {
+        // Parquet não tem escrita linha a linha: os modos de streaming do CSV não se aplicam
+        let streaming: Vec<&str> = ["CSV_GZIP", "CSV_FLUSH_EVERY", "CSV_FLUSH_ON_RECONNECT"]
+            .into_iter()
+            .filter(|var| env_flag(var))
+            .chain(
+                ["CSV_ROTATE_MB", "CSV_ROTATE_SECS", "CSV_MAX_MB"]
+                    .into_iter()
+                    .filter(|var| env_parse(var, 0.0) > 0.0),
+            )
+            .collect();
+        if !streaming.is_empty() {
+            return Err(format!(
+                "OUTPUT_FORMAT=parquet is written once at the end of the run; unset {}",
+                streaming.join(", ")
+            ));
+        }
+    }
+    Ok(parquet)
+}
@@ -0,0 +1,196 @@
+//! Control socket: reconfiguração em tempo de execução sem reiniciar o coletor.
+//!
+//! Protocolo texto, um comando por linha, uma resposta por comando:
+//!   snapshot            -> estatísticas atuais
+//!   reset               -> zera as estatísticas
+//!   set-alert <ms>      -> muda o limiar de alerta de latência (0 = desliga)
+//!   set-samples <n>     -> muda o tamanho da janela de percentis
+//!   help
+//!
+//! SEM AUTENTICAÇÃO: endereços TCP fora do loopback são recusados, a menos
+//! que `CONTROL_ALLOW_REMOTE=1` (aí qualquer um que alcance a porta manda).
+//!
+//! Os comandos não tocam o estado diretamente: são enviados por canal ao loop
+//! de coleta, que os aplica entre mensagens (nada de lock no hot path).
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::stats::LatencyStats;
+
+const HELP: &str = "commands: snapshot | reset | set-alert <ms> | set-samples <n> | help";
+
+pub enum Command {
+    Snapshot,
+    Reset,
+    SetAlert(f64),
+    SetSamples(usize),
+}
+
+/// A parsed command plus the channel the collection loop answers on.
+pub struct Request {
+    pub command: Command,
+    pub reply: oneshot::Sender<String>,
+}
+
+fn parse(line: &str) -> Result<Command, String> {
+    let mut parts = line.split_whitespace();
+    let cmd = parts.next().unwrap_or("");
+    let arg = parts.next();
+
+    match cmd {
+        "snapshot" | "stats" => Ok(Command::Snapshot),
+        "reset" => Ok(Command::Reset),
+        "set-alert" => arg
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| *v >= 0.0)
+            .map(Command::SetAlert)
+            .ok_or_else(|| "usage: set-alert <ms>".to_string()),
+        "set-samples" => arg
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .map(Command::SetSamples)
+            .ok_or_else(|| "usage: set-samples <n>".to_string()),
+        "help" | "" => Err(HELP.to_string()),
+        other => Err(format!("unknown command '{}'. {}", other, HELP)),
+    }
+}
+
+/// Checks that a TCP `addr` only resolves to loopback addresses (UNIX
+/// sockets always pass). With `allow_remote` anything goes, loudly.
+pub fn check_addr(addr: &str, allow_remote: bool) -> Result<(), String> {
+    use std::net::ToSocketAddrs;
+
+    if addr.starts_with("unix:") {
+        return Ok(());
+    }
+    let resolved: Vec<_> = addr
+        .to_socket_addrs()
+        .map_err(|e| format!("invalid CONTROL_ADDR {:?}: {}", addr, e))?
+        .collect();
+    let remote = resolved.is_empty() || resolved.iter().any(|a| !a.ip().is_loopback());
+    match (remote, allow_remote) {
+        (false, _) => Ok(()),
+        (true, true) => {
+            eprintln!(
+                "  WARNING: control socket on {} is reachable from the network and has NO authentication \
+                 (CONTROL_ALLOW_REMOTE=1): anyone who can connect can reset the stats",
+                addr
+            );
+            Ok(())
+        }
+        (true, false) => Err(format!(
+            "CONTROL_ADDR {:?} is not a loopback address; the control socket is unauthenticated. \
+             Use 127.0.0.1/[::1] or unix:/path, or set CONTROL_ALLOW_REMOTE=1 to expose it anyway",
+            addr
+        )),
+    }
+}
+
+/// CONTROL_ADDR (`unix:/tmp/x.sock` or `127.0.0.1:9901`): starts the server
+/// and returns the queue of requests for the collection loop (None if unset).
+/// Must be called from inside the tokio runtime.
+pub fn spawn_from_env() -> Result<Option<mpsc::Receiver<Request>>, String> {
+    let Ok(addr) = std::env::var("CONTROL_ADDR") else {
+        return Ok(None);
+    };
+    check_addr(&addr, crate::env_flag("CONTROL_ALLOW_REMOTE"))?;
+    let (tx, rx) = mpsc::channel(16);
+    tokio::spawn(serve(addr, tx));
+    Ok(Some(rx))
+}
+
+/// Listens on `addr` (`unix:/path.sock` or `host:port`) and forwards commands.
+pub async fn serve(addr: String, tx: mpsc::Sender<Request>) {
+    #[cfg(unix)]
+    if let Some(path) = addr.strip_prefix("unix:") {
+        let _ = std::fs::remove_file(path);
+        let listener = match tokio::net::UnixListener::bind(path) {
+            Ok(l) => l,
+            Err(e) => {
+                eprintln!("  WARNING: control socket {} unavailable: {}", addr, e);
+                return;
+            }
+        };
+        eprintln!("Control socket listening on {}", addr);
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(handle_conn(stream, tx.clone()));
+        }
+        return;
+    }
+
+    let listener = match tokio::net::TcpListener::bind(&addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("  WARNING: control socket {} unavailable: {}", addr, e);
+            return;
+        }
+    };
+    eprintln!("Control socket listening on {}", addr);
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(handle_conn(stream, tx.clone()));
+    }
+}
+
+async fn handle_conn<S: AsyncRead + AsyncWrite + Unpin>(stream: S, tx: mpsc::Sender<Request>) {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let response = match parse(line.trim()) {
+            Ok(command) => {
+                let (reply, rx) = oneshot::channel();
+                if tx.send(Request { command, reply }).await.is_err() {
+                    break; // coletor terminou
+                }
+                rx.await.unwrap_or_else(|_| "collector stopped".to_string())
+            }
+            Err(msg) => msg,
+        };
+        if writer.write_all(response.as_bytes()).await.is_err()
+            || writer.write_all(b"\n").await.is_err()
+        {
+            break;
+        }
+    }
+}
+
+/// Text answer for the control socket `snapshot` command.
+pub fn snapshot_text(stats: &LatencyStats, alert_threshold_us: i64, alerts: u64) -> String {
+    let Some((count, avg, min, max, p50, p95, p99)) = stats.get() else {
+        return "trades=0".to_string();
+    };
+    let (gaps, out_of_order, _) = stats.integrity();
+    format!(
+        "trades={} avg_us={:.0} min_us={} max_us={} p50_us={} p95_us={} p99_us={} \
+         gaps={} out_of_order={} alert_ms={:.3} alerts={}",
+        count,
+        avg,
+        min,
+        max,
+        p50,
+        p95,
+        p99,
+        gaps,
+        out_of_order,
+        alert_threshold_us as f64 / 1000.0,
+        alerts
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_loopback_without_opt_in() {
+        assert!(check_addr("127.0.0.1:9901", false).is_ok());
+        assert!(check_addr("[::1]:9901", false).is_ok());
+        assert!(check_addr("unix:/tmp/coletor.sock", false).is_ok());
+        let err = check_addr("0.0.0.0:9901", false).unwrap_err();
+        assert!(err.contains("CONTROL_ALLOW_REMOTE"), "{}", err);
+        assert!(check_addr("192.0.2.10:9901", false).is_err());
+        assert!(check_addr("0.0.0.0:9901", true).is_ok());
+        assert!(check_addr("not an address", true).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+//! Topologia de CPU e layout de threads.
+//!
+//! O coletor roda num runtime tokio `current_thread`: o loop de coleta, o
+//! display e os servidores opcionais (métricas, control socket) dividem a
+//! mesma thread. O CSV é escrito ao final da coleta ou, linha a linha
+//! (CSV_FLUSH_EVERY / CSV_ROTATE_*), por uma thread própria. A thread do
+//! coletor é fixada em COLLECTION_CORE, por padrão o menor core permitido
+//! (threads criadas depois, como o pool blocking do tokio, herdam a máscara;
+//! a thread do CSV volta para os outros cores permitidos).
+//!
+//! Linux fixa de fato (`sched_setaffinity`); no macOS a afinidade é só uma
+//! dica ao scheduler (`THREAD_AFFINITY_POLICY`) e a prioridade vira uma
+//! classe de QoS; no Windows usa `SetThreadAffinityMask`/`SetThreadPriority`.
+
+/// Number of cores available to this process (respects cgroup/affinity limits).
+pub fn get_num_cores() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// One-line description of where each piece of work runs.
+pub fn thread_layout(pinned: Option<usize>) -> String {
+    let placement = match pinned {
+        Some(core) => format!("pinned to cpu {}", core),
+        None => "unpinned".to_string(),
+    };
+    format!(
+        "collector + display + servers on main thread (current_thread runtime, {}); \
+         CSV written after collection, or by the csv-writer thread when streamed",
+        placement
+    )
+}
+
+/// Pins the calling thread to `core`. False if the core does not exist or is
+/// outside this process's allowed set (taskset/cgroup), if macOS refuses the
+/// hint, or on other systems.
+pub fn set_cpu_affinity(core: usize) -> bool {
+    set_cpu_set(&[core])
+}
+
+/// Restricts the calling thread to `cpus`. False if none of them can be used,
+/// or on systems without affinity support.
+#[cfg(target_os = "linux")]
+pub fn set_cpu_set(cpus: &[usize]) -> bool {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let mut any = false;
+    for &cpu in cpus.iter().filter(|&&cpu| cpu < libc::CPU_SETSIZE as usize) {
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+        any = true;
+    }
+    any && unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0 }
+}
+
+/// macOS has no hard affinity: THREAD_AFFINITY_POLICY only tags the thread,
+/// and threads with different tags are kept on different L2 caches when
+/// possible. The tag is the first cpu + 1 (0 is THREAD_AFFINITY_TAG_NULL).
+/// Apple Silicon rejects the policy (KERN_NOT_SUPPORTED): false there.
+#[cfg(target_os = "macos")]
+pub fn set_cpu_set(cpus: &[usize]) -> bool {
+    let Some(tag) = cpus.first().and_then(|&cpu| libc::integer_t::try_from(cpu).ok()?.checked_add(1)) else {
+        return false;
+    };
+    let mut policy = libc::thread_affinity_policy { affinity_tag: tag };
+    let rc = unsafe {
+        libc::thread_policy_set(
+            libc::pthread_mach_thread_np(libc::pthread_self()),
+            libc::THREAD_AFFINITY_POLICY as libc::thread_policy_flavor_t,
+            &mut policy as *mut libc::thread_affinity_policy as libc::thread_policy_t,
+            libc::THREAD_AFFINITY_POLICY_COUNT,
+        )
+    };
+    rc == libc::KERN_SUCCESS
+}
+
+/// Windows: `SetThreadAffinityMask` over the cpus of processor group 0 (the
+/// first 64 logical processors).
+#[cfg(target_os = "windows")]
+pub fn set_cpu_set(cpus: &[usize]) -> bool {
+    let mask = cpus
+        .iter()
+        .filter(|&&cpu| cpu < usize::BITS as usize)
+        .fold(0usize, |mask, &cpu| mask | 1 << cpu);
+    // Retorna a máscara anterior; 0 = recusada (cpu fora da máscara do processo)
+    mask != 0 && unsafe { win::SetThreadAffinityMask(win::GetCurrentThread(), mask) != 0 }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn set_cpu_set(_cpus: &[usize]) -> bool {
+    false
+}
+
+/// Sets the calling thread's priority from a nice value (-20 = highest, 0 =
+/// default, 19 = lowest). False if refused (raising it needs CAP_SYS_NICE or
+/// root) or unsupported.
+#[cfg(target_os = "linux")]
+pub fn set_thread_priority(nice: i32) -> bool {
+    // Linux: setpriority com o tid vale só para esta thread
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+    unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice.clamp(-20, 19)) == 0 }
+}
+
+/// macOS: the nice value picks a QoS class for the thread (no root needed).
+#[cfg(target_os = "macos")]
+pub fn set_thread_priority(nice: i32) -> bool {
+    use libc::qos_class_t::*;
+    let class = match nice {
+        ..=-10 => QOS_CLASS_USER_INTERACTIVE,
+        -9..=-1 => QOS_CLASS_USER_INITIATED,
+        0 => QOS_CLASS_DEFAULT,
+        1..=9 => QOS_CLASS_UTILITY,
+        _ => QOS_CLASS_BACKGROUND,
+    };
+    unsafe { libc::pthread_set_qos_class_self_np(class, 0) == 0 }
+}
+
+/// Windows: the nice value maps to one of the `THREAD_PRIORITY_*` levels.
+#[cfg(target_os = "windows")]
+pub fn set_thread_priority(nice: i32) -> bool {
+    unsafe { win::SetThreadPriority(win::GetCurrentThread(), windows_priority(nice)) != 0 }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn set_thread_priority(_nice: i32) -> bool {
+    false
+}
+
+/// Nice value -> Windows `THREAD_PRIORITY_*` (time critical .. idle).
+#[cfg(any(target_os = "windows", test))]
+fn windows_priority(nice: i32) -> i32 {
+    match nice {
+        ..=-15 => 15, // THREAD_PRIORITY_TIME_CRITICAL
+        -14..=-8 => 2, // THREAD_PRIORITY_HIGHEST
+        -7..=-1 => 1,  // THREAD_PRIORITY_ABOVE_NORMAL
+        0 => 0,        // THREAD_PRIORITY_NORMAL
+        1..=7 => -1,   // THREAD_PRIORITY_BELOW_NORMAL
+        8..=14 => -2,  // THREAD_PRIORITY_LOWEST
+        _ => -15,      // THREAD_PRIORITY_IDLE
+    }
+}
+
+/// kernel32, declared here instead of pulling a Windows bindings crate.
+#[cfg(target_os = "windows")]
+mod win {
+    use std::ffi::c_void;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        /// Pseudo-handle of the calling thread (no CloseHandle needed).
+        pub fn GetCurrentThread() -> *mut c_void;
+        pub fn SetThreadAffinityMask(thread: *mut c_void, mask: usize) -> usize;
+        pub fn SetThreadPriority(thread: *mut c_void, priority: i32) -> i32;
+    }
+}
+
+/// CPUs of each NUMA node (index = node id), from `/sys/devices/system/node`.
+/// Empty when the kernel does not expose NUMA info (non-Linux, some VMs).
+pub fn get_numa_topology() -> Vec<Vec<usize>> {
+    let Ok(entries) = std::fs::read_dir("/sys/devices/system/node") else {
+        return Vec::new();
+    };
+    let mut nodes: Vec<(usize, Vec<usize>)> = entries
+        .filter_map(|e| {
+            let e = e.ok()?;
+            let id = e.file_name().to_str()?.strip_prefix("node")?.parse().ok()?;
+            let list = std::fs::read_to_string(e.path().join("cpulist")).ok()?;
+            Some((id, parse_cpu_list(&list)))
+        })
+        .collect();
+    nodes.sort_unstable_by_key(|(id, _)| *id);
+    // Ids podem ter buraco (node0, node2): o índice continua sendo o id
+    let mut topology = vec![Vec::new(); nodes.last().map_or(0, |(id, _)| id + 1)];
+    for (id, cpus) in nodes {
+        topology[id] = cpus;
+    }
+    topology
+}
+
+/// `0-3,8,10-11` -> [0, 1, 2, 3, 8, 10, 11] (kernel cpulist format).
+fn parse_cpu_list(list: &str) -> Vec<usize> {
+    let mut cpus = Vec::new();
+    for part in list.trim().split(',').filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((a, b)) => {
+                if let (Ok(a), Ok(b)) = (a.parse::<usize>(), b.parse::<usize>()) {
+                    cpus.extend(a..=b);
+                }
+            }
+            None => cpus.extend(part.parse::<usize>().ok()),
+        }
+    }
+    cpus
+}
+
+/// Inverse of `parse_cpu_list` for sorted input.
+pub fn format_cpu_list(cpus: &[usize]) -> String {
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i < cpus.len() {
+        let start = cpus[i];
+        while i + 1 < cpus.len() && cpus[i + 1] == cpus[i] + 1 {
+            i += 1;
+        }
+        parts.push(if cpus[i] == start {
+            start.to_string()
+        } else {
+            format!("{}-{}", start, cpus[i])
+        });
+        i += 1;
+    }
+    parts.join(",")
+}
+
+/// CPU the calling thread is running on right now.
+#[cfg(target_os = "linux")]
+fn current_cpu() -> Option<usize> {
+    let cpu = unsafe { libc::sched_getcpu() };
+    (cpu >= 0).then_some(cpu as usize)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn current_cpu() -> Option<usize> {
+    None
+}
+
+/// CPUs this process may run on (`taskset`/`numactl`/cgroup cpuset).
+#[cfg(target_os = "linux")]
+pub fn allowed_cpus() -> Option<Vec<usize>> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let rc = unsafe { libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) };
+    if rc != 0 {
+        return None;
+    }
+    Some(
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+            .collect(),
+    )
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn allowed_cpus() -> Option<Vec<usize>> {
+    None
+}
+
+/// NUMA layout for the startup banner, e.g. `2 nodes (0: 0-15, 1: 16-31);
+/// collector on node 0 (cpu 3)`, plus whether the allowed CPUs span more
+/// than one node (the scheduler may then migrate the collector across
+/// nodes). None when NUMA info is unavailable.
+pub fn numa_layout() -> Option<(String, bool)> {
+    let topology = get_numa_topology();
+    if topology.iter().all(Vec::is_empty) {
+        return None;
+    }
+    let node_of = |cpu: usize| topology.iter().position(|cpus| cpus.contains(&cpu));
+    let nodes: Vec<String> = topology
+        .iter()
+        .enumerate()
+        .filter(|(_, cpus)| !cpus.is_empty())
+        .map(|(id, cpus)| format!("{}: {}", id, format_cpu_list(cpus)))
+        .collect();
+    let mut line = format!(
+        "{} node{} ({})",
+        nodes.len(),
+        if nodes.len() == 1 { "" } else { "s" },
+        nodes.join(", ")
+    );
+    if let Some((cpu, node)) = current_cpu().and_then(|cpu| Some((cpu, node_of(cpu)?))) {
+        line.push_str(&format!("; collector on node {} (cpu {})", node, cpu));
+    }
+    let mut spanned: Vec<usize> = allowed_cpus()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(node_of)
+        .collect();
+    spanned.sort_unstable();
+    spanned.dedup();
+    Some((line, spanned.len() > 1))
+}
+
+/// COLLECTION_CORE / COLLECTION_NICE for the calling (collector) thread,
+/// before calibrating and connecting, with the banner lines. Returns the
+/// CPUs left for the csv-writer thread (None: collector unpinned, or no
+/// other allowed CPU).
+pub fn pin_collector_from_env() -> Option<Vec<usize>> {
+    // Máscara antes de fixar: a thread do CSV roda nos outros cores dela
+    let allowed = allowed_cpus();
+    // Padrão: o menor core permitido (0 sem taskset); COLLECTION_CORE=none deixa sem fixar
+    let collection_core = match std::env::var("COLLECTION_CORE") {
+        Ok(v) if matches!(v.trim().to_ascii_lowercase().as_str(), "none" | "off") => None,
+        Ok(v) => match v.trim().parse::<usize>() {
+            Ok(core) if set_cpu_affinity(core) => Some(core),
+            _ => {
+                eprintln!("  WARNING: COLLECTION_CORE={}: cannot pin to this core (missing, outside the allowed set, or unsupported on this OS); running unpinned", v);
+                None
+            }
+        },
+        // Sem suporte no SO (ou dica recusada): segue sem fixar, sem aviso
+        Err(_) => {
+            let core = allowed.as_ref().and_then(|cpus| cpus.first().copied()).unwrap_or(0);
+            set_cpu_affinity(core).then_some(core)
+        }
+    };
+    // COLLECTION_NICE: prioridade da thread do coletor (nice; no macOS vira classe de QoS)
+    if let Ok(v) = std::env::var("COLLECTION_NICE") {
+        match v.trim().parse::<i32>() {
+            Ok(nice) if set_thread_priority(nice) => eprintln!("Priority:   nice {}", nice),
+            _ => eprintln!("  WARNING: COLLECTION_NICE={}: cannot set the collector priority (negative values need CAP_SYS_NICE/root, or unsupported on this OS); default priority", v),
+        }
+    }
+    eprintln!("Threads:    {}", thread_layout(collection_core));
+    if let Some((numa, spans_nodes)) = numa_layout() {
+        eprintln!("NUMA:       {}", numa);
+        if spans_nodes {
+            // Thread não fixada: o scheduler pode migrar entre nós (memória remota)
+            eprintln!("  WARNING: allowed CPUs span several NUMA nodes; pin the run to one node (numactl --cpunodebind=N --membind=N)");
+        }
+    }
+    // Coletor fixado: a thread do CSV fica com os outros cores permitidos
+    collection_core.and_then(|core| {
+        let cpus: Vec<usize> = allowed?.into_iter().filter(|&cpu| cpu != core).collect();
+        (!cpus.is_empty()).then_some(cpus)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_list_round_trip() {
+        let cpus = parse_cpu_list("0-3,8,10-11\n");
+        assert_eq!(cpus, [0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(format_cpu_list(&cpus), "0-3,8,10-11");
+        assert!(parse_cpu_list("").is_empty());
+    }
+
+    #[test]
+    fn out_of_range_core_is_refused() {
+        assert!(!set_cpu_affinity(usize::MAX));
+        assert!(!set_cpu_set(&[]));
+    }
+
+    #[test]
+    fn nice_maps_to_windows_priority_levels() {
+        let levels: Vec<i32> = [-20, -15, -10, -1, 0, 1, 10, 19].into_iter().map(windows_priority).collect();
+        assert_eq!(levels, [15, 15, 2, 1, 0, -1, -2, -15]);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    #[test]
+    fn lowering_priority_is_allowed() {
+        // Roda na thread do próprio teste: baixar a prioridade não precisa de privilégio
+        assert!(set_thread_priority(10));
+    }
+}
@@ -0,0 +1,89 @@
+//! Estimativa de latência independente de relógio (sanity check do recv − T).
+//!
+//! Com relógios não sincronizados, `recv − T = latência + offset` (offset
+//! constante e desconhecido). Duas medidas não dependem do offset:
+//!
+//! - latência relativa ao piso da execução: `(recv − T) − min(recv − T)`,
+//!   o atraso acima do melhor caso observado;
+//! - padrão de chegada: entre trades consecutivos, `Δrecv` deve acompanhar
+//!   `ΔT` (correlação alta, diferença média pequena) se a latência é estável.
+//!
+//! Se o recv − T direto fica negativo, ou o piso direto diverge muito de um
+//! RTT plausível, o offset de relógio (e não a rede) domina o número direto.
+
+use crate::stats::{percentile, PercentileMethod};
+
+/// One received trade: (trade_ts_us, recv_ts_us, latency_us).
+pub type Sample = (u64, u64, i64);
+
+/// Pearson correlation of two equally long series (None if degenerate).
+fn correlation(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    let n = xs.len() as f64;
+    if xs.len() < 2 {
+        return None;
+    }
+    let (mx, my) = (xs.iter().sum::<f64>() / n, ys.iter().sum::<f64>() / n);
+    let (mut sxy, mut sxx, mut syy) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys) {
+        sxy += (x - mx) * (y - my);
+        sxx += (x - mx) * (x - mx);
+        syy += (y - my) * (y - my);
+    }
+    (sxx > 0.0 && syy > 0.0).then(|| sxy / (sxx * syy).sqrt())
+}
+
+/// Prints the clock-independent estimate next to the direct recv − T figures.
+pub fn report(samples: &[Sample]) {
+    if samples.len() < 2 {
+        return;
+    }
+
+    let mut direct: Vec<i64> = samples.iter().map(|s| s.2).collect();
+    direct.sort_unstable();
+    let floor = direct[0];
+    let q = |sorted: &[i64], p: f64| percentile(sorted, p, PercentileMethod::Nearest);
+
+    // Inter-arrival: só pares com ΔT > 0 (trades no mesmo ms não informam nada)
+    let (mut d_trade, mut d_recv) = (Vec::new(), Vec::new());
+    for pair in samples.windows(2) {
+        let dt = pair[1].0 as f64 - pair[0].0 as f64;
+        if dt > 0.0 {
+            d_trade.push(dt);
+            d_recv.push(pair[1].1 as f64 - pair[0].1 as f64);
+        }
+    }
+    let mean_abs_diff = d_trade
+        .iter()
+        .zip(&d_recv)
+        .map(|(t, r)| (r - t).abs())
+        .sum::<f64>()
+        / d_trade.len().max(1) as f64;
+
+    eprintln!("\n=== Clock-Independent Cross-Check ===");
+    eprintln!(
+        "Direct (recv−T):        min {}µs | p50 {}µs | p99 {}µs",
+        floor,
+        q(&direct, 0.50),
+        q(&direct, 0.99)
+    );
+    eprintln!(
+        "Above run floor:        p50 {}µs | p99 {}µs (clock-offset independent)",
+        q(&direct, 0.50) - floor,
+        q(&direct, 0.99) - floor
+    );
+    match correlation(&d_trade, &d_recv) {
+        Some(r) => eprintln!(
+            "Inter-arrival ΔT↔Δrecv: r={:.3} | mean |Δrecv−ΔT| {:.0}µs ({} pairs)",
+            r,
+            mean_abs_diff,
+            d_trade.len()
+        ),
+        None => eprintln!("Inter-arrival ΔT↔Δrecv: not enough distinct timestamps"),
+    }
+
+    if floor < 0 {
+        eprintln!(
+            "⚠️  Direct latency below zero: clock offset dominates recv−T; trust only the relative figures"
+        );
+    }
+}
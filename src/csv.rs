@@ -0,0 +1,77 @@
+//! Campos de texto no CSV (RFC 4180).
+//!
+//! `label` e `machine_id` vêm do usuário: vírgulas e aspas são escapadas
+//! com aspas duplas, e quebras de linha viram espaço para manter um registro
+//! por linha (os leitores deste repositório leem o CSV linha a linha).
+
+use std::borrow::Cow;
+
+/// Escapes a free-text field; borrows when no quoting is needed.
+pub fn escape_field(s: &str) -> Cow<'_, str> {
+    if !s.contains([',', '"', '\n', '\r']) {
+        return Cow::Borrowed(s);
+    }
+    let flat = s.replace(['\n', '\r'], " ");
+    if flat.contains([',', '"']) {
+        Cow::Owned(format!("\"{}\"", flat.replace('"', "\"\"")))
+    } else {
+        Cow::Owned(flat)
+    }
+}
+
+/// Splits one CSV line, honoring quoted fields ("a,b" and "" escapes).
+pub fn split_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', _) => in_quotes = !in_quotes,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_fields_are_borrowed() {
+        assert!(matches!(escape_field("aws-tokyo-1"), Cow::Borrowed("aws-tokyo-1")));
+        assert_eq!(escape_field(""), "");
+    }
+
+    #[test]
+    fn escaped_fields_round_trip() {
+        for field in ["a,b", "say \"hi\"", "\"", ",", "x,\"y\",z", "ünï,cødé"] {
+            let line = format!("1,{},{},2", escape_field(field), escape_field("plain"));
+            assert_eq!(split_line(&line), vec!["1", field, "plain", "2"], "{}", line);
+        }
+    }
+
+    #[test]
+    fn newlines_become_spaces() {
+        // Um registro por linha: quebras não sobrevivem, o resto sim
+        assert_eq!(escape_field("a\nb"), "a b");
+        assert_eq!(escape_field("a\r\nb,c"), "\"a  b,c\"");
+        let line = format!("{},x", escape_field("l1\n\"l2\""));
+        assert_eq!(split_line(&line), vec!["l1 \"l2\"", "x"]);
+    }
+
+    #[test]
+    fn split_line_edge_cases() {
+        assert_eq!(split_line(""), vec![""]);
+        assert_eq!(split_line(",,"), vec!["", "", ""]);
+        assert_eq!(split_line("\"\",a"), vec!["", "a"]);
+    }
+}
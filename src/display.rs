@@ -0,0 +1,453 @@
+//! Realtime display renderer (stderr).
+//!
+//! Desenha um bloco fixo de N linhas e o redesenha no lugar usando ANSI
+//! cursor-up, para que relatórios com várias linhas (por símbolo, por janela)
+//! não rolem o terminal. Em saída que não é TTY (arquivo, pipe, journald),
+//! degrada para linhas simples anexadas.
+
+use std::collections::VecDeque;
+use std::io::{IsTerminal, Write};
+use std::time::Duration;
+
+use crate::stats::{LatencyStats, SampleMode, SecondRow, SecondSeries};
+
+/// How the block is drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DisplayMode {
+    /// Redraw in place with ANSI escapes.
+    Tty,
+    /// Append plain lines (no escapes).
+    Plain,
+}
+
+impl DisplayMode {
+    /// `DISPLAY_MODE=tty|plain|auto` (auto = TTY detection on stderr).
+    pub fn from_env() -> Self {
+        match std::env::var("DISPLAY_MODE").as_deref() {
+            Ok("tty") => DisplayMode::Tty,
+            Ok("plain") => DisplayMode::Plain,
+            _ => {
+                if std::io::stderr().is_terminal() {
+                    DisplayMode::Tty
+                } else {
+                    DisplayMode::Plain
+                }
+            }
+        }
+    }
+}
+
+pub struct Renderer {
+    mode: DisplayMode,
+    /// Lines drawn by the previous frame (TTY mode only).
+    drawn: usize,
+}
+
+impl Renderer {
+    pub fn new(mode: DisplayMode) -> Self {
+        Self { mode, drawn: 0 }
+    }
+
+    /// Draws one frame. In TTY mode the previous frame is overwritten, even if
+    /// it had a different number of lines.
+    pub fn draw(&mut self, lines: &[String]) {
+        let stderr = std::io::stderr();
+        let mut out = stderr.lock();
+
+        match self.mode {
+            DisplayMode::Plain => {
+                for line in lines {
+                    let _ = writeln!(out, "{}", line);
+                }
+            }
+            DisplayMode::Tty => {
+                if self.drawn > 0 {
+                    let _ = write!(out, "\x1b[{}A", self.drawn);
+                }
+                for line in lines {
+                    let _ = write!(out, "\r\x1b[2K{}\n", line);
+                }
+                // Bloco anterior era maior: limpa as linhas que sobraram
+                let leftover = self.drawn.saturating_sub(lines.len());
+                for _ in 0..leftover {
+                    let _ = writeln!(out, "\r\x1b[2K");
+                }
+                if leftover > 0 {
+                    let _ = write!(out, "\x1b[{}A", leftover);
+                }
+                self.drawn = lines.len();
+            }
+        }
+        let _ = out.flush();
+    }
+
+    /// Forgets the current block so the next output starts below it.
+    pub fn finish(&mut self) {
+        self.drawn = 0;
+    }
+}
+
+/// Display name for `symbol`: its alias from `SYMBOL_ALIASES`
+/// (`btcusdt=BTC,ethusdt=ETH`) or the uppercased symbol. Only used in the
+/// display and reports; the CSV keeps the canonical symbol.
+pub fn symbol_display_name(symbol: &str) -> String {
+    let aliases = std::env::var("SYMBOL_ALIASES").unwrap_or_default();
+    aliases
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(sym, _)| sym.trim().eq_ignore_ascii_case(symbol))
+        .map(|(_, alias)| alias.trim().to_string())
+        .filter(|alias| !alias.is_empty())
+        .unwrap_or_else(|| symbol.to_uppercase())
+}
+
+/// One display tick of `RealtimeWindow` (run-elapsed bounds).
+struct WindowTick {
+    start: Duration,
+    end: Duration,
+    count: u64,
+    total_us: f64,
+    /// i64::MAX / i64::MIN when the tick had no trades.
+    min: i64,
+    max: i64,
+}
+
+/// REALTIME_WINDOW_SECS: o display mostra os últimos N segundos em vez da
+/// execução inteira (o relatório final continua acumulado).
+///
+/// Nada no hot path além do min/max do tick em `LatencyStats`: contagem e
+/// soma de cada tick saem da diferença dos acumulados, como no STATS_CSV.
+/// Cada tick guarda o tempo real que cobriu: ticks atrasados (reconexão com
+/// backoff) não distorcem a taxa.
+pub struct RealtimeWindow {
+    ticks: VecDeque<WindowTick>,
+    span: Duration,
+    last_count: u64,
+    last_total_us: f64,
+    /// None until the first tick, which fires right at the start and only
+    /// opens the window.
+    last_elapsed: Option<Duration>,
+}
+
+impl RealtimeWindow {
+    pub fn new(span: Duration) -> Self {
+        Self {
+            ticks: VecDeque::new(),
+            span,
+            last_count: 0,
+            last_total_us: 0.0,
+            last_elapsed: None,
+        }
+    }
+
+    /// `last 60s`
+    pub fn label(&self) -> String {
+        format!("last {}s", self.span.as_secs_f64())
+    }
+
+    /// Closes the tick ending at `elapsed` (call once per display interval).
+    pub fn tick(&mut self, stats: &mut LatencyStats, elapsed: Duration) {
+        let (count, total_us) = stats
+            .get_basic()
+            .map(|(count, avg, _, _)| (count, avg * count as f64))
+            .unwrap_or_default();
+        // reset pelo control socket: a janela recomeça
+        if count < self.last_count {
+            self.ticks.clear();
+            self.last_count = 0;
+            self.last_total_us = 0.0;
+        }
+        let (min, max) = stats.take_tick_extremes().unwrap_or((i64::MAX, i64::MIN));
+        if let Some(start) = self.last_elapsed.replace(elapsed) {
+            self.ticks.push_back(WindowTick {
+                start,
+                end: elapsed,
+                count: count - self.last_count,
+                total_us: total_us - self.last_total_us,
+                min,
+                max,
+            });
+        }
+        self.last_count = count;
+        self.last_total_us = total_us;
+        while self.ticks.front().is_some_and(|t| t.end + self.span <= elapsed) {
+            self.ticks.pop_front();
+        }
+    }
+
+    /// (trades, seconds covered, avg_us, min_us, max_us); None without trades.
+    fn view(&self) -> Option<(u64, f64, f64, i64, i64)> {
+        let count: u64 = self.ticks.iter().map(|t| t.count).sum();
+        let (first, last) = (self.ticks.front()?, self.ticks.back()?);
+        if count == 0 {
+            return None;
+        }
+        let total: f64 = self.ticks.iter().map(|t| t.total_us).sum();
+        let min = self.ticks.iter().map(|t| t.min).min().unwrap_or(i64::MAX);
+        let max = self.ticks.iter().map(|t| t.max).max().unwrap_or(i64::MIN);
+        let secs = last.end.saturating_sub(first.start).as_secs_f64();
+        Some((count, secs, total / count as f64, min, max))
+    }
+}
+
+/// Builds the realtime block for the current stats (O(1), no sorting).
+/// `notional` is the accumulated price × quantity when TRACK_NOTIONAL=1.
+/// With `window`, rate and latency cover only its ticks (trade count stays
+/// the run total).
+pub fn stats_lines(
+    name: &str,
+    stats: &LatencyStats,
+    elapsed: Duration,
+    notional: Option<f64>,
+    window: Option<&RealtimeWindow>,
+) -> Vec<String> {
+    let secs = elapsed.as_secs_f64().max(0.001);
+    // Antes do primeiro tick fechado a janela está vazia: linha acumulada
+    if let Some(window) = window.filter(|w| !w.ticks.is_empty()) {
+        let mut lines = window_lines(name, stats, window, elapsed);
+        if let (Some(notional), Some(line)) = (notional, lines.first_mut()) {
+            line.push_str(&format!(" | {}/s", format_notional(notional / secs)));
+        }
+        return lines;
+    }
+    match stats.get_basic() {
+        Some((count, avg, min, max)) => {
+            let mut line = format!(
+                "{} | Trades: {:>9} | {:>7.0} tps | avg {:>8.2}ms",
+                name,
+                count,
+                count as f64 / secs,
+                avg / 1000.0,
+            );
+            // min > max: nenhum trade desde o último reset_extremes()
+            if min <= max {
+                line.push_str(&format!(
+                    " | min {:>8.2}ms | max {:>8.2}ms",
+                    min as f64 / 1000.0,
+                    max as f64 / 1000.0
+                ));
+            } else {
+                line.push_str(" | min        - | max        -");
+            }
+            if let Some(notional) = notional {
+                line.push_str(&format!(" | {}/s", format_notional(notional / secs)));
+            }
+            vec![line]
+        }
+        None => vec![format!("{} | Waiting for trades... ({:.0}s)", name, secs)],
+    }
+}
+
+fn window_lines(name: &str, stats: &LatencyStats, window: &RealtimeWindow, elapsed: Duration) -> Vec<String> {
+    let total = stats.get_basic().map_or(0, |(count, _, _, _)| count);
+    let label = window.label();
+    match window.view() {
+        Some((count, secs, avg, min, max)) => vec![format!(
+            "{} | Trades: {:>9} | {:>7.0} tps | avg {:>8.2}ms | min {:>8.2}ms | max {:>8.2}ms ({})",
+            name,
+            total,
+            count as f64 / secs.max(0.001),
+            avg / 1000.0,
+            min as f64 / 1000.0,
+            max as f64 / 1000.0,
+            label
+        )],
+        None if total > 0 => vec![format!("{} | Trades: {:>9} | no trades in the {}", name, total, label)],
+        None => vec![format!(
+            "{} | Waiting for trades... ({:.0}s)",
+            name,
+            elapsed.as_secs_f64()
+        )],
+    }
+}
+
+/// Summary line printed every N trades (STATS_EVERY_TRADES). Keyed by trade
+/// count only, so logs from machines with different throughput line up.
+pub fn checkpoint_line(trades: usize, stats: &LatencyStats) -> String {
+    let (gaps, out_of_order, _) = stats.integrity();
+    match stats.get() {
+        Some((_, avg, min, max, p50, p95, p99)) => format!(
+            "[{:>9} trades] avg {:.2}ms | min {:.2}ms | p50 {:.2}ms | p95 {:.2}ms | p99 {:.2}ms | max {:.2}ms | gaps {} | ooo {}",
+            trades,
+            avg / 1000.0,
+            min as f64 / 1000.0,
+            p50 as f64 / 1000.0,
+            p95 as f64 / 1000.0,
+            p99 as f64 / 1000.0,
+            max as f64 / 1000.0,
+            gaps,
+            out_of_order,
+        ),
+        None => format!("[{:>9} trades] no samples", trades),
+    }
+}
+
+/// Human-readable quote-currency amount ($1.23M, $456.7K).
+pub fn format_notional(value: f64) -> String {
+    if value >= 1e9 {
+        format!("${:.2}B", value / 1e9)
+    } else if value >= 1e6 {
+        format!("${:.2}M", value / 1e6)
+    } else if value >= 1e3 {
+        format!("${:.1}K", value / 1e3)
+    } else {
+        format!("${:.2}", value)
+    }
+}
+
+/// Per-second series (TIMESERIES_FILE): one row per wall-clock second of
+/// receive time, independent of the display interval.
+///
+/// avg_1s/p99_1s em µs, vazios num segundo sem trades; tps divide pela parte
+/// do segundo que a coleta cobriu (primeiro e último são parciais).
+pub struct TimeseriesCsv {
+    file: std::io::BufWriter<std::fs::File>,
+    series: SecondSeries,
+    rows: Vec<SecondRow>,
+}
+
+impl TimeseriesCsv {
+    pub const HEADER: &'static str = "unix_sec,count_delta,avg_1s,p99_1s,tps";
+
+    /// `stats`: the run's stats (percentile method, negative-latency policy);
+    /// `start_us`: start of the collection (epoch µs).
+    pub fn create(path: &str, stats: &LatencyStats, start_us: i64) -> std::io::Result<Self> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "{}", Self::HEADER)?;
+        Ok(Self {
+            file,
+            series: SecondSeries::new(stats, start_us),
+            rows: Vec::new(),
+        })
+    }
+
+    /// Adds one trade (same latency given to `stats`) by its receive time.
+    #[inline(always)]
+    pub fn record(&mut self, recv_ts_us: i64, latency_us: i64) {
+        self.series.record(recv_ts_us, latency_us, &mut self.rows);
+    }
+
+    /// Closes the seconds that ended by `now_us` (also without trades) and
+    /// writes them out.
+    pub fn tick(&mut self, now_us: i64) -> std::io::Result<()> {
+        self.series.advance(now_us, &mut self.rows);
+        self.write_rows()?;
+        self.file.flush()
+    }
+
+    /// End of the run: writes the remaining seconds, the last one partial.
+    pub fn finish(mut self, end_us: i64) -> std::io::Result<()> {
+        self.series.finish(end_us, &mut self.rows);
+        self.write_rows()?;
+        self.file.flush()
+    }
+
+    fn write_rows(&mut self) -> std::io::Result<()> {
+        for row in self.rows.drain(..) {
+            writeln!(
+                self.file,
+                "{},{},{},{},{:.2}",
+                row.unix_sec,
+                row.count,
+                row.avg_us.map(|avg| format!("{:.1}", avg)).unwrap_or_default(),
+                row.p99_us.map(|p| p.to_string()).unwrap_or_default(),
+                row.tps
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Time series of the display interval (STATS_CSV): one row per tick.
+///
+/// Intervalo sem trades também gera linha (trades=0, avg vazio): um buraco na
+/// série significa coletor parado, não mercado parado. A p99 é só do
+/// intervalo: as latências dele vão para um histograma próprio (memória fixa),
+/// zerado a cada linha.
+pub struct StatsCsv {
+    file: std::fs::File,
+    last_count: u64,
+    last_total_us: f64,
+    /// Latencies of the current interval only.
+    interval: LatencyStats,
+    /// None until the first tick, which fires right at the start and only
+    /// opens the first interval.
+    last_elapsed: Option<Duration>,
+}
+
+impl StatsCsv {
+    pub const HEADER: &'static str = "ts_us,elapsed_s,trades_total,trades,tps,avg_us,p99_us,csv_dropped";
+
+    /// `stats`: the run's stats, whose percentile method and negative-latency
+    /// policy the per-interval p99 follows.
+    pub fn create(path: &str, stats: &LatencyStats) -> std::io::Result<Self> {
+        let mut file = std::fs::File::create(path)?;
+        writeln!(file, "{}", Self::HEADER)?;
+        let mut interval = stats.empty_like();
+        interval.set_sample_mode(SampleMode::Histogram);
+        Ok(Self {
+            file,
+            last_count: 0,
+            last_total_us: 0.0,
+            interval,
+            last_elapsed: None,
+        })
+    }
+
+    /// Adds one latency to the current interval (same value given to `stats`).
+    #[inline(always)]
+    pub fn record(&mut self, latency_us: i64) {
+        self.interval.record(latency_us);
+    }
+
+    /// Appends the row for the interval since the previous call.
+    /// `csv_dropped`: trades CSV rows dropped so far (full writer queue).
+    pub fn append(
+        &mut self,
+        ts_us: i64,
+        elapsed: Duration,
+        stats: &LatencyStats,
+        csv_dropped: u64,
+    ) -> std::io::Result<()> {
+        let p99 = self
+            .interval
+            .percentiles(&[0.99])
+            .first()
+            .map(|p| p.to_string())
+            .unwrap_or_default();
+        self.interval.reset();
+        let Some(last_elapsed) = self.last_elapsed.replace(elapsed) else {
+            return Ok(());
+        };
+        let (count, total_us) = stats
+            .get_basic()
+            .map(|(count, avg, _, _)| (count, avg * count as f64))
+            .unwrap_or_default();
+        // reset pelo control socket: a contagem recomeça do zero
+        if count < self.last_count {
+            self.last_count = 0;
+            self.last_total_us = 0.0;
+        }
+        let trades = count - self.last_count;
+        let avg = if trades > 0 {
+            format!("{:.1}", (total_us - self.last_total_us) / trades as f64)
+        } else {
+            String::new()
+        };
+        let interval = elapsed.saturating_sub(last_elapsed);
+        self.last_count = count;
+        self.last_total_us = total_us;
+        writeln!(
+            self.file,
+            "{},{:.3},{},{},{:.2},{},{},{}",
+            ts_us,
+            elapsed.as_secs_f64(),
+            count,
+            trades,
+            trades as f64 / interval.as_secs_f64().max(0.001),
+            avg,
+            p99,
+            csv_dropped
+        )
+    }
+}
@@ -0,0 +1,626 @@
+//! Manual JSON parse (zero-alloc) for Binance stream frames.
+//!
+//! Busca direta em bytes: nenhum frame é desserializado por completo, apenas
+//! os campos numéricos necessários para medir latência são extraídos.
+
+/// What the frames of `STREAM_TYPE` carry and how their ids are sequenced.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StreamKind {
+    /// `trade`: one id per message ("t").
+    Trade,
+    /// `aggTrade`: aggregate id "a" covering trade ids f..=l.
+    AggTrade,
+    /// `depth` / `depth@100ms`: diff updates U..=u, strictly sequenced.
+    DepthDiff,
+    /// `depth5`, `depth10@100ms`...: book snapshots, no sequence guarantee.
+    DepthPartial,
+    /// `!ticker@arr` / `!miniTicker@arr`: all-market array, one "E" per entry.
+    TickerArray,
+    /// `bookTicker`: best bid/ask per order book update "u". Ids skip the
+    /// updates that don't touch the top of book; spot sends no timestamp.
+    BookTicker,
+}
+
+impl StreamKind {
+    /// `trade`, `aggTrade`, `depth@100ms`, `depth5`, `!ticker@arr`, `bookTicker`...
+    /// Unknown names are read as `trade`.
+    pub fn from_stream_type(stream_type: &str) -> Self {
+        let base = stream_type.split('@').next().unwrap_or("");
+        match base {
+            "aggTrade" => StreamKind::AggTrade,
+            "bookTicker" => StreamKind::BookTicker,
+            "!ticker" | "!miniTicker" => StreamKind::TickerArray,
+            "depth" => StreamKind::DepthDiff,
+            _ if base.starts_with("depth") => StreamKind::DepthPartial,
+            _ => StreamKind::Trade,
+        }
+    }
+
+    /// Whether message ids form a sequence that can be checked for gaps.
+    pub fn is_sequenced(self) -> bool {
+        !matches!(self, StreamKind::DepthPartial | StreamKind::TickerArray)
+    }
+}
+
+/// One frame of any `StreamKind`, reduced to what the latency loop uses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame<'a> {
+    /// "t", aggregate "a", depth "u", bookTicker "u" or "lastUpdateId".
+    /// 0 for ticker arrays, which have no id (the caller numbers them).
+    pub id: u64,
+    /// Server timestamp in ms: "T", or "E" for depth and ticker arrays.
+    /// 0 when the frame carries none (spot bookTicker, spot depth snapshots).
+    pub ts_ms: u64,
+    /// Ids covered by the frame: aggTrade f..=l, depth diff U..=u.
+    pub id_range: Option<(u64, u64)>,
+    pub detail: FrameDetail<'a>,
+}
+
+/// Stream-specific fields of a `Frame`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameDetail<'a> {
+    None,
+    /// Combined-stream trade: "s" as sent (uppercase).
+    Symbol(&'a [u8]),
+    /// bookTicker: best bid / ask.
+    Quote { bid: f64, ask: f64 },
+    /// Ticker array: oldest "E" (ms) and number of entries.
+    Ticker { oldest_ms: u64, entries: u64 },
+}
+
+/// Parses `json` as a frame of `kind`. None if the fields that kind needs
+/// are missing (error frames, acks, another schema).
+#[inline(always)]
+pub fn extract_frame(kind: StreamKind, json: &[u8]) -> Option<Frame<'_>> {
+    let frame = |id, ts_ms, id_range, detail| Frame {
+        id,
+        ts_ms,
+        id_range,
+        detail,
+    };
+    match kind {
+        StreamKind::Trade => {
+            extract_trade_data(json).map(|(trade_id, ts)| frame(trade_id, ts, None, FrameDetail::None))
+        }
+        StreamKind::AggTrade => extract_agg_trade(json)
+            .map(|(agg_id, first_id, last_id, ts)| frame(agg_id, ts, Some((first_id, last_id)), FrameDetail::None)),
+        StreamKind::DepthDiff => extract_depth_update(json)
+            .map(|(first_id, last_id, ts)| frame(last_id, ts, Some((first_id, last_id)), FrameDetail::None)),
+        // Spot não envia "E" no depth parcial (futures envia)
+        StreamKind::DepthPartial => extract_u64_field(json, b"\"lastUpdateId\":")
+            .map(|id| frame(id, extract_event_ts(json).unwrap_or(0), None, FrameDetail::None)),
+        StreamKind::TickerArray => extract_ticker_array(json).map(|(newest, oldest_ms, entries)| {
+            frame(0, newest, None, FrameDetail::Ticker { oldest_ms, entries })
+        }),
+        StreamKind::BookTicker => extract_book_ticker(json).map(|book| {
+            let quote = FrameDetail::Quote {
+                bid: book.bid,
+                ask: book.ask,
+            };
+            frame(book.update_id, book.ts_ms.unwrap_or(0), None, quote)
+        }),
+    }
+}
+
+/// Combined-stream trade (`extract_trade_with_symbol`) as a `Frame`.
+#[inline(always)]
+pub fn extract_trade_frame_with_symbol(json: &[u8]) -> Option<Frame<'_>> {
+    extract_trade_with_symbol(json).map(|(symbol, trade_id, ts_ms)| Frame {
+        id: trade_id,
+        ts_ms,
+        id_range: None,
+        detail: FrameDetail::Symbol(symbol),
+    })
+}
+
+/// Extracts "t" (trade_id) and "T" (trade_ts_ms) from Binance JSON.
+/// Note: Binance sends "T" in milliseconds; we convert to microseconds later for CSV/storage.
+/// Manual parse without allocation — searches directly for numeric fields.
+#[inline(always)]
+pub fn extract_trade_data(json: &[u8]) -> Option<(u64, u64)> {
+    let trade_id = extract_u64_field(json, b"\"t\":")?;
+    let trade_ts = extract_u64_field(json, b"\"T\":")?;
+    Some((trade_id, trade_ts))
+}
+
+/// Extracts "E" (event_ts_ms): when Binance sent the frame, after matching.
+/// recv − "E" is the network leg alone; "E" − "T" is exchange-side delay.
+#[inline(always)]
+pub fn extract_event_ts(json: &[u8]) -> Option<u64> {
+    extract_u64_field(json, b"\"E\":")
+}
+
+/// Extracts ("s" symbol, "t" trade_id, "T" trade_ts_ms), unwrapping the
+/// combined-stream envelope (`{"stream":"btcusdt@trade","data":{...}}`)
+/// when present. The symbol is returned as sent (uppercase).
+#[inline(always)]
+pub fn extract_trade_with_symbol(json: &[u8]) -> Option<(&[u8], u64, u64)> {
+    let trade = match find_key(json, b"\"data\":") {
+        Some(pos) => &json[pos + b"\"data\":".len()..],
+        None => json,
+    };
+    let symbol = extract_str_field(trade, b"\"s\":\"")?;
+    let (trade_id, trade_ts) = extract_trade_data(trade)?;
+    Some((symbol, trade_id, trade_ts))
+}
+
+/// A `trade` frame with the fields needed beyond latency.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TradeFull {
+    pub trade_id: u64,
+    pub trade_ts_ms: u64,
+    pub price: f64,
+    pub qty: f64,
+    /// "m": buyer is the maker (the aggressor sold).
+    pub is_buyer_maker: bool,
+}
+
+/// Extracts "t", "T", "p", "q" and "m" from a trade frame. None if any field
+/// is missing or price/quantity are not valid numbers.
+#[inline(always)]
+pub fn extract_trade_full(json: &[u8]) -> Option<TradeFull> {
+    let (trade_id, trade_ts_ms) = extract_trade_data(json)?;
+    Some(TradeFull {
+        trade_id,
+        trade_ts_ms,
+        price: extract_quoted_f64(json, b"\"p\":\"")?,
+        qty: extract_quoted_f64(json, b"\"q\":\"")?,
+        is_buyer_maker: extract_bool_field(json, b"\"m\":")?,
+    })
+}
+
+/// A `bookTicker` frame: best bid/ask after order book update "u".
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BookTicker {
+    pub update_id: u64,
+    pub bid: f64,
+    pub ask: f64,
+    /// "T" (futures transaction time), else "E". Spot frames carry neither.
+    pub ts_ms: Option<u64>,
+}
+
+/// Extracts "u", "b" and "a" from a bookTicker frame, plus "T"/"E" when the
+/// stream sends them. None if the id or a price is missing.
+#[inline(always)]
+pub fn extract_book_ticker(json: &[u8]) -> Option<BookTicker> {
+    Some(BookTicker {
+        update_id: extract_u64_field(json, b"\"u\":")?,
+        bid: extract_quoted_f64(json, b"\"b\":\"")?,
+        ask: extract_quoted_f64(json, b"\"a\":\"")?,
+        ts_ms: extract_u64_field(json, b"\"T\":").or_else(|| extract_event_ts(json)),
+    })
+}
+
+/// Extracts an aggTrade frame: (agg_id "a", first_trade_id "f", last_trade_id "l", trade_ts_ms "T").
+#[inline(always)]
+pub fn extract_agg_trade(json: &[u8]) -> Option<(u64, u64, u64, u64)> {
+    let agg_id = extract_u64_field(json, b"\"a\":")?;
+    let first_id = extract_u64_field(json, b"\"f\":")?;
+    let last_id = extract_u64_field(json, b"\"l\":")?;
+    let trade_ts = extract_u64_field(json, b"\"T\":")?;
+    Some((agg_id, first_id, last_id, trade_ts))
+}
+
+/// Extracts a diff depth frame: (first_update_id "U", final_update_id "u", event_ts_ms "E").
+#[inline(always)]
+pub fn extract_depth_update(json: &[u8]) -> Option<(u64, u64, u64)> {
+    let event_ts = extract_u64_field(json, b"\"E\":")?;
+    let first_id = extract_u64_field(json, b"\"U\":")?;
+    let last_id = extract_u64_field(json, b"\"u\":")?;
+    Some((first_id, last_id, event_ts))
+}
+
+/// Extracts an all-market array frame (`!ticker@arr`, `!miniTicker@arr`):
+/// (newest "E", oldest "E", entries). Each entry carries its own event time.
+#[inline(always)]
+pub fn extract_ticker_array(json: &[u8]) -> Option<(u64, u64, u64)> {
+    let (mut newest, mut oldest, mut entries) = (0u64, u64::MAX, 0u64);
+    let mut rest = json;
+    while let Some(pos) = find_key(rest, b"\"E\":") {
+        rest = &rest[pos..];
+        if let Some(event_ts) = extract_u64_field(rest, b"\"E\":") {
+            newest = newest.max(event_ts);
+            oldest = oldest.min(event_ts);
+            entries += 1;
+        }
+        rest = &rest[4..];
+    }
+    (entries > 0).then_some((newest, oldest, entries))
+}
+
+/// Field names for feeds that mirror Binance's trade stream under other keys
+/// (FIELD_ID, FIELD_TS, FIELD_PRICE, FIELD_QTY).
+///
+/// Mesma busca por bytes dos campos fixos: a primeira ocorrência de `"nome":`
+/// no frame, em qualquer nível. Valores podem vir com ou sem aspas.
+pub struct FieldMap {
+    id: Vec<u8>,
+    ts: Vec<u8>,
+    price: Option<Vec<u8>>,
+    qty: Option<Vec<u8>>,
+    names: String,
+}
+
+impl FieldMap {
+    /// None unless FIELD_ID or FIELD_TS is set (the other defaults to "t"/"T").
+    pub fn from_env() -> Option<Self> {
+        let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+        let (id, ts) = (var("FIELD_ID"), var("FIELD_TS"));
+        if id.is_none() && ts.is_none() {
+            return None;
+        }
+        let id = id.unwrap_or_else(|| "t".to_string());
+        let ts = ts.unwrap_or_else(|| "T".to_string());
+        let (price, qty) = (var("FIELD_PRICE"), var("FIELD_QTY"));
+        let mut names = format!("id={:?} ts={:?} (ms)", id, ts);
+        if let (Some(p), Some(q)) = (&price, &qty) {
+            names.push_str(&format!(" price={:?} qty={:?}", p, q));
+        }
+        let pattern = |name: &str| format!("\"{}\":", name).into_bytes();
+        Some(Self {
+            id: pattern(&id),
+            ts: pattern(&ts),
+            price: price.as_deref().map(pattern),
+            qty: qty.as_deref().map(pattern),
+            names,
+        })
+    }
+
+    /// Field names, for the startup banner.
+    pub fn describe(&self) -> &str {
+        &self.names
+    }
+
+    /// (id, ts_ms) like `extract_trade_data`.
+    #[inline(always)]
+    pub fn extract(&self, json: &[u8]) -> Option<(u64, u64)> {
+        let id = extract_u64_field(numeric_value(json, &self.id)?, b"")?;
+        let ts = extract_u64_field(numeric_value(json, &self.ts)?, b"")?;
+        Some((id, ts))
+    }
+
+    /// (price, qty) when both fields are mapped and present.
+    #[inline(always)]
+    pub fn price_qty(&self, json: &[u8]) -> Option<(f64, f64)> {
+        let price = parse_f64(numeric_value(json, self.price.as_deref()?)?)?;
+        let qty = parse_f64(numeric_value(json, self.qty.as_deref()?)?)?;
+        Some((price, qty))
+    }
+}
+
+/// Bytes right after `pattern`, past optional spaces and an opening quote.
+#[inline(always)]
+fn numeric_value<'a>(json: &'a [u8], pattern: &[u8]) -> Option<&'a [u8]> {
+    let mut i = find_key(json, pattern)? + pattern.len();
+    while i < json.len() && json[i] == b' ' {
+        i += 1;
+    }
+    if json.get(i) == Some(&b'"') {
+        i += 1;
+    }
+    Some(&json[i..])
+}
+
+/// Parses the leading decimal number of `bytes` (`88120.26`, `1e-3`...).
+#[inline(always)]
+fn parse_f64(bytes: &[u8]) -> Option<f64> {
+    let len = bytes
+        .iter()
+        .position(|b| !matches!(b, b'0'..=b'9' | b'.' | b'-' | b'+' | b'e' | b'E'))
+        .unwrap_or(bytes.len());
+    std::str::from_utf8(&bytes[..len]).ok()?.parse().ok()
+}
+
+/// Searches for a numeric field in JSON by pattern `"key":`.
+/// Assumes value is an integer without quotes (true for "t" and "T" from Binance).
+/// Returns the number as-is (no unit conversion here).
+/// None if the digits don't fit in a u64 (a wrapped id would fake gaps).
+#[inline(always)]
+pub fn extract_u64_field(json: &[u8], pattern: &[u8]) -> Option<u64> {
+    let pos = find_key(json, pattern)?;
+    let start = pos + pattern.len();
+
+    // Skip optional spaces
+    let mut i = start;
+    while i < json.len() && json[i] == b' ' {
+        i += 1;
+    }
+
+    // Parse number
+    let mut val: u64 = 0;
+    while i < json.len() {
+        let b = json[i];
+        if b.is_ascii_digit() {
+            val = val.checked_mul(10)?.checked_add((b - b'0') as u64)?;
+            i += 1;
+        } else {
+            break;
+        }
+    }
+
+    if i > start {
+        Some(val)
+    } else {
+        None
+    }
+}
+
+/// Like `find_pattern`, but a `"key":` pattern only matches as a JSON key:
+/// the previous non-space byte must be `{` or `,` (or the slice start), so a
+/// value that merely contains `"t":` is skipped. Other patterns match anywhere.
+#[inline(always)]
+fn find_key(haystack: &[u8], pattern: &[u8]) -> Option<usize> {
+    if pattern.first() != Some(&b'"') {
+        return find_pattern(haystack, pattern);
+    }
+    let mut from = 0;
+    loop {
+        let pos = from + find_pattern(&haystack[from..], pattern)?;
+        let prev = haystack[..pos].iter().rev().find(|b| !b.is_ascii_whitespace());
+        if matches!(prev, None | Some(b'{') | Some(b',')) {
+            return Some(pos);
+        }
+        from = pos + 1;
+    }
+}
+
+/// Searches for a byte pattern inside a slice.
+#[inline(always)]
+fn find_pattern(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.len() > haystack.len() {
+        return None;
+    }
+    let limit = haystack.len() - needle.len();
+    for i in 0..=limit {
+        if &haystack[i..i + needle.len()] == needle {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// Searches for a string field by pattern `"key":"` and returns the raw bytes
+/// up to the closing quote (no unescaping — only for simple ids/enums).
+#[inline(always)]
+pub fn extract_str_field<'a>(json: &'a [u8], pattern: &[u8]) -> Option<&'a [u8]> {
+    let start = find_key(json, pattern)? + pattern.len();
+    let len = json[start..].iter().position(|&b| b == b'"')?;
+    Some(&json[start..start + len])
+}
+
+/// Parses a quoted decimal field (`"p":"88120.26"`) as f64.
+#[inline(always)]
+pub fn extract_quoted_f64(json: &[u8], pattern: &[u8]) -> Option<f64> {
+    let raw = extract_str_field(json, pattern)?;
+    std::str::from_utf8(raw).ok()?.parse().ok()
+}
+
+/// Searches for a boolean field by pattern `"key":` (`true` / `false`).
+#[inline(always)]
+pub fn extract_bool_field(json: &[u8], pattern: &[u8]) -> Option<bool> {
+    let rest = &json[find_key(json, pattern)? + pattern.len()..];
+    let start = rest.iter().position(|&b| b != b' ')?;
+    let rest = &rest[start..];
+    if rest.starts_with(b"true") {
+        Some(true)
+    } else if rest.starts_with(b"false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Detects a Binance error frame (`{"error":{"code":-1121,"msg":"Invalid symbol."}}`)
+/// and returns (code, msg). The code is 0 if it can't be parsed.
+pub fn extract_error_frame(json: &[u8]) -> Option<(i64, &[u8])> {
+    find_key(json, b"\"error\":")?;
+    let code = extract_i64_field(json, b"\"code\":").unwrap_or(0);
+    let msg = extract_str_field(json, b"\"msg\":\"").unwrap_or(b"");
+    Some((code, msg))
+}
+
+/// Like `extract_u64_field`, accepting an optional leading `-`.
+pub fn extract_i64_field(json: &[u8], pattern: &[u8]) -> Option<i64> {
+    let pos = find_key(json, pattern)? + pattern.len();
+    let mut i = pos;
+    while i < json.len() && json[i] == b' ' {
+        i += 1;
+    }
+    if json.get(i) == Some(&b'-') {
+        let val = extract_u64_field(&json[i + 1..], b"")?;
+        Some(-i64::try_from(val).ok()?)
+    } else {
+        i64::try_from(extract_u64_field(&json[i..], b"")?).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Frames reais da documentação da Binance (spot e USDⓈ-M futures)
+    const TRADE: &[u8] = br#"{"e":"trade","E":1672515782136,"s":"BNBBTC","t":12345,"p":"0.001","q":"100","T":1672515782134,"m":true,"M":true}"#;
+    const AGG_TRADE: &[u8] = br#"{"e":"aggTrade","E":1672515782136,"s":"BNBBTC","a":12345,"p":"0.001","q":"100","f":100,"l":105,"T":1672515782134,"m":true,"M":true}"#;
+    const DEPTH_DIFF: &[u8] = br#"{"e":"depthUpdate","E":1672515782136,"s":"BNBBTC","U":157,"u":160,"b":[["0.0024","10"]],"a":[["0.0026","100"]]}"#;
+    const DEPTH_PARTIAL: &[u8] = br#"{"lastUpdateId":160,"bids":[["0.0024","10"]],"asks":[["0.0026","100"]]}"#;
+    const BOOK_TICKER_SPOT: &[u8] = br#"{"u":400900217,"s":"BNBUSDT","b":"25.35190000","B":"31.21000000","a":"25.36520000","A":"40.66000000"}"#;
+    const BOOK_TICKER_FUTURES: &[u8] = br#"{"e":"bookTicker","u":400900217,"E":1568014460893,"T":1568014460891,"s":"BNBUSDT","b":"25.35190000","B":"31.21000000","a":"25.36520000","A":"40.66000000"}"#;
+    const TICKER_ARRAY: &[u8] = br#"[{"e":"24hrMiniTicker","E":1672515782136,"s":"BTCUSDT","c":"16500.1"},{"e":"24hrMiniTicker","E":1672515781900,"s":"ETHUSDT","c":"1200.5"}]"#;
+    const COMBINED: &[u8] = br#"{"stream":"btcusdt@trade","data":{"e":"trade","E":1672515782136,"s":"BTCUSDT","t":99,"p":"16500.1","q":"0.5","T":1672515782134,"m":false,"M":true}}"#;
+
+    #[test]
+    fn trade_reads_t_and_capital_t() {
+        assert_eq!(extract_trade_data(TRADE), Some((12345, 1672515782134)));
+        assert_eq!(extract_event_ts(TRADE), Some(1672515782136));
+    }
+
+    #[test]
+    fn agg_trade_is_not_read_as_trade() {
+        // aggTrade não tem "t": o parser de trade não pode aceitar o frame
+        assert_eq!(extract_trade_data(AGG_TRADE), None);
+        assert_eq!(extract_agg_trade(AGG_TRADE), Some((12345, 100, 105, 1672515782134)));
+    }
+
+    #[test]
+    fn find_key_only_matches_key_positions() {
+        let json = br#"{"m":"x\"t\":5","t":7,"T":9}"#;
+        assert_eq!(extract_trade_data(json), Some((7, 9)));
+        assert_eq!(find_key(br#"{ "t":1}"#, b"\"t\":"), Some(2));
+        assert_eq!(find_key(br#"{"a":1, "t":2}"#, b"\"t\":"), Some(8));
+        assert_eq!(find_key(br#"{"a":"t","b":1}"#, b"\"t\":"), None);
+        // Padrão sem aspas casa em qualquer posição
+        assert_eq!(find_key(b"12345", b"34"), Some(2));
+    }
+
+    #[test]
+    fn u64_overflow_returns_none() {
+        let json = br#"{"t":123456789012345678901234567890,"T":1}"#;
+        assert_eq!(extract_u64_field(json, b"\"t\":"), None);
+        assert_eq!(extract_trade_data(json), None);
+        assert_eq!(extract_u64_field(br#"{"t":18446744073709551615}"#, b"\"t\":"), Some(u64::MAX));
+        // checked_add: só o último dígito passa do limite
+        assert_eq!(extract_u64_field(br#"{"t":18446744073709551616}"#, b"\"t\":"), None);
+        // checked_mul: um dígito a mais
+        assert_eq!(extract_u64_field(br#"{"t":184467440737095516150}"#, b"\"t\":"), None);
+    }
+
+    #[test]
+    fn numeric_field_edge_cases() {
+        assert_eq!(extract_u64_field(br#"{"t": 42}"#, b"\"t\":"), Some(42));
+        assert_eq!(extract_u64_field(br#"{"t":"42"}"#, b"\"t\":"), None);
+        assert_eq!(extract_u64_field(br#"{"t":}"#, b"\"t\":"), None);
+        assert_eq!(extract_i64_field(br#"{"code":-1121}"#, b"\"code\":"), Some(-1121));
+        assert_eq!(extract_i64_field(br#"{"code":9223372036854775808}"#, b"\"code\":"), None);
+    }
+
+    #[test]
+    fn trade_full_reads_price_qty_and_maker() {
+        assert_eq!(
+            extract_trade_full(TRADE),
+            Some(TradeFull {
+                trade_id: 12345,
+                trade_ts_ms: 1672515782134,
+                price: 0.001,
+                qty: 100.0,
+                is_buyer_maker: true,
+            })
+        );
+        let no_maker = br#"{"t":1,"T":2,"p":"1.5","q":"2"}"#;
+        assert_eq!(extract_trade_full(no_maker), None);
+        let bad_price = br#"{"t":1,"T":2,"p":"abc","q":"2","m":false}"#;
+        assert_eq!(extract_trade_full(bad_price), None);
+    }
+
+    #[test]
+    fn book_ticker_timestamps() {
+        let spot = extract_book_ticker(BOOK_TICKER_SPOT).unwrap();
+        assert_eq!(spot.update_id, 400900217);
+        assert_eq!((spot.bid, spot.ask), (25.3519, 25.3652));
+        assert_eq!(spot.ts_ms, None);
+        // Futures: "T" (transação) tem prioridade sobre "E"
+        assert_eq!(extract_book_ticker(BOOK_TICKER_FUTURES).unwrap().ts_ms, Some(1568014460891));
+        let event_only = br#"{"u":1,"E":5,"b":"1.0","a":"2.0"}"#;
+        assert_eq!(extract_book_ticker(event_only).unwrap().ts_ms, Some(5));
+    }
+
+    #[test]
+    fn event_ts_is_a_key_not_a_value() {
+        assert_eq!(extract_event_ts(BOOK_TICKER_SPOT), None);
+        assert_eq!(extract_event_ts(br#"{"x":"\"E\":1","t":1}"#), None);
+        assert_eq!(extract_event_ts(DEPTH_DIFF), Some(1672515782136));
+    }
+
+    #[test]
+    fn combined_stream_envelope() {
+        assert_eq!(
+            extract_trade_with_symbol(COMBINED),
+            Some((&b"BTCUSDT"[..], 99, 1672515782134))
+        );
+        // Sem envelope: o objeto do trade está no topo
+        assert_eq!(extract_trade_with_symbol(TRADE), Some((&b"BNBBTC"[..], 12345, 1672515782134)));
+    }
+
+    #[test]
+    fn ticker_array_newest_oldest() {
+        assert_eq!(extract_ticker_array(TICKER_ARRAY), Some((1672515782136, 1672515781900, 2)));
+        assert_eq!(extract_ticker_array(b"[]"), None);
+    }
+
+    #[test]
+    fn error_frame() {
+        let json = br#"{"error":{"code":-1121,"msg":"Invalid symbol."},"id":1}"#;
+        assert_eq!(extract_error_frame(json), Some((-1121, &b"Invalid symbol."[..])));
+        assert_eq!(extract_error_frame(TRADE), None);
+    }
+
+    #[test]
+    fn stream_kind_from_stream_type() {
+        assert_eq!(StreamKind::from_stream_type("trade"), StreamKind::Trade);
+        assert_eq!(StreamKind::from_stream_type("aggTrade"), StreamKind::AggTrade);
+        assert_eq!(StreamKind::from_stream_type("depth@100ms"), StreamKind::DepthDiff);
+        assert_eq!(StreamKind::from_stream_type("depth"), StreamKind::DepthDiff);
+        assert_eq!(StreamKind::from_stream_type("depth20@100ms"), StreamKind::DepthPartial);
+        assert_eq!(StreamKind::from_stream_type("!miniTicker@arr"), StreamKind::TickerArray);
+        assert_eq!(StreamKind::from_stream_type("bookTicker"), StreamKind::BookTicker);
+        assert_eq!(StreamKind::from_stream_type("kline_1m"), StreamKind::Trade);
+        assert!(!StreamKind::TickerArray.is_sequenced());
+        assert!(StreamKind::BookTicker.is_sequenced());
+    }
+
+    #[test]
+    fn extract_frame_per_kind() {
+        let frame = |id, ts_ms, id_range| Frame {
+            id,
+            ts_ms,
+            id_range,
+            detail: FrameDetail::None,
+        };
+        assert_eq!(extract_frame(StreamKind::Trade, TRADE), Some(frame(12345, 1672515782134, None)));
+        assert_eq!(extract_frame(StreamKind::Trade, AGG_TRADE), None);
+        assert_eq!(
+            extract_frame(StreamKind::AggTrade, AGG_TRADE),
+            Some(frame(12345, 1672515782134, Some((100, 105))))
+        );
+        assert_eq!(
+            extract_frame(StreamKind::DepthDiff, DEPTH_DIFF),
+            Some(frame(160, 1672515782136, Some((157, 160))))
+        );
+        assert_eq!(extract_frame(StreamKind::DepthPartial, DEPTH_PARTIAL), Some(frame(160, 0, None)));
+        assert_eq!(
+            extract_frame(StreamKind::BookTicker, BOOK_TICKER_SPOT),
+            Some(Frame {
+                id: 400900217,
+                ts_ms: 0,
+                id_range: None,
+                detail: FrameDetail::Quote { bid: 25.3519, ask: 25.3652 },
+            })
+        );
+        assert_eq!(
+            extract_frame(StreamKind::TickerArray, TICKER_ARRAY),
+            Some(Frame {
+                id: 0,
+                ts_ms: 1672515782136,
+                id_range: None,
+                detail: FrameDetail::Ticker { oldest_ms: 1672515781900, entries: 2 },
+            })
+        );
+        assert_eq!(
+            extract_trade_frame_with_symbol(COMBINED).map(|f| f.detail),
+            Some(FrameDetail::Symbol(b"BTCUSDT"))
+        );
+    }
+
+    #[test]
+    fn field_map_accepts_quoted_values() {
+        let map = FieldMap {
+            id: b"\"id\":".to_vec(),
+            ts: b"\"time\":".to_vec(),
+            price: Some(b"\"px\":".to_vec()),
+            qty: Some(b"\"sz\":".to_vec()),
+            names: String::new(),
+        };
+        let json = br#"{"id":"77","time": 1700000000000,"px":"101.5","sz":0.25}"#;
+        assert_eq!(map.extract(json), Some((77, 1700000000000)));
+        assert_eq!(map.price_qty(json), Some((101.5, 0.25)));
+    }
+}
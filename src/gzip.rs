@@ -0,0 +1,194 @@
+//! Saída do CSV comprimida (CSV_GZIP=1).
+//!
+//! O CSV passa por um `flate2::write::GzEncoder` sobre o arquivo. O `finish`
+//! fecha o encoder, que grava o trailer (CRC e tamanho). Abrir em modo
+//! append cria um novo membro gzip no fim do arquivo, e `gzip -d`/`zcat`/
+//! pandas leem os membros concatenados como um arquivo só.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+/// Compression level (`CSV_GZIP_LEVEL`, 1..=9).
+pub const DEFAULT_LEVEL: u32 = 6;
+
+/// `CSV_GZIP=1`: CSV files are written through a gzip encoder.
+pub fn enabled() -> bool {
+    crate::env_flag("CSV_GZIP")
+}
+
+/// `trades.csv` -> `trades.csv.gz` (unchanged if it already ends in `.gz`).
+pub fn path_for(path: &str) -> String {
+    if path.ends_with(".gz") {
+        path.to_string()
+    } else {
+        format!("{}.gz", path)
+    }
+}
+
+/// One gzip member written into `file`.
+pub struct GzipFile {
+    /// None once `finish` wrote the trailer.
+    encoder: Option<GzEncoder<BufWriter<File>>>,
+    /// Same file the encoder writes to, kept for `sync_data` after `finish`.
+    file: File,
+}
+
+impl GzipFile {
+    pub fn create(path: &str) -> io::Result<Self> {
+        Self::new(File::create(path)?)
+    }
+
+    /// Appends a new gzip member to `path`.
+    pub fn append(path: &str) -> io::Result<Self> {
+        Self::new(OpenOptions::new().append(true).create(true).open(path)?)
+    }
+
+    fn new(file: File) -> io::Result<Self> {
+        let level = crate::env_parse("CSV_GZIP_LEVEL", DEFAULT_LEVEL).clamp(1, 9);
+        let encoder = GzEncoder::new(BufWriter::new(file.try_clone()?), Compression::new(level));
+        Ok(Self {
+            encoder: Some(encoder),
+            file,
+        })
+    }
+
+    /// Writes the trailer and flushes it to the file; `fsync` then syncs
+    /// the file. Later writes fail.
+    pub fn finish(&mut self, fsync: bool) -> io::Result<()> {
+        if let Some(encoder) = self.encoder.take() {
+            encoder.finish()?.flush()?;
+        }
+        if fsync {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for GzipFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.encoder.as_mut() {
+            Some(encoder) => encoder.write(buf),
+            None => Err(io::Error::other("gzip member already finished")),
+        }
+    }
+
+    /// Sync flush: rows compressed so far reach the file (readable by
+    /// `zcat` up to here), at some cost in compression ratio.
+    fn flush(&mut self) -> io::Result<()> {
+        match self.encoder.as_mut() {
+            Some(encoder) => encoder.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// CSV output: plain buffered file or a gzip member.
+pub enum CsvOut {
+    Plain(BufWriter<File>),
+    Gzip(GzipFile),
+}
+
+impl CsvOut {
+    /// Creates `path` (already with `.gz` when `gzip`).
+    pub fn create(path: &str, gzip: bool) -> io::Result<Self> {
+        Ok(if gzip {
+            CsvOut::Gzip(GzipFile::create(path)?)
+        } else {
+            CsvOut::Plain(BufWriter::new(File::create(path)?))
+        })
+    }
+
+    /// Opens `path` for appending (a new gzip member when `gzip`).
+    pub fn append(path: &str, gzip: bool) -> io::Result<Self> {
+        Ok(if gzip {
+            CsvOut::Gzip(GzipFile::append(path)?)
+        } else {
+            CsvOut::Plain(BufWriter::new(OpenOptions::new().append(true).open(path)?))
+        })
+    }
+
+    /// Flushes everything to the file (gzip: writes the trailer and ends the
+    /// member).
+    pub fn finish(&mut self, fsync: bool) -> io::Result<()> {
+        match self {
+            CsvOut::Plain(file) => {
+                file.flush()?;
+                if fsync {
+                    file.get_ref().sync_data()?;
+                }
+                Ok(())
+            }
+            CsvOut::Gzip(gz) => gz.finish(fsync),
+        }
+    }
+
+    /// fsync of what already reached the file (gzip: compressed so far).
+    pub fn sync_data(&self) -> io::Result<()> {
+        match self {
+            CsvOut::Plain(file) => file.get_ref().sync_data(),
+            CsvOut::Gzip(gz) => gz.file.sync_data(),
+        }
+    }
+}
+
+impl Write for CsvOut {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CsvOut::Plain(file) => file.write(buf),
+            CsvOut::Gzip(gz) => gz.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CsvOut::Plain(file) => file.flush(),
+            CsvOut::Gzip(gz) => gz.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn gunzip(path: &str) -> String {
+        let mut out = String::new();
+        flate2::read::MultiGzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn path_for_adds_gz_once() {
+        assert_eq!(path_for("trades.csv"), "trades.csv.gz");
+        assert_eq!(path_for("trades.csv.gz"), "trades.csv.gz");
+    }
+
+    #[test]
+    fn round_trip_with_appended_member() {
+        let path = std::env::temp_dir().join(format!("gzip_round_trip_{}.csv.gz", std::process::id()));
+        let path = path.to_str().unwrap();
+        let rows = "label,trade_id,latency_us\nrun,1,1500\n\"a,b\",2,-40\n";
+
+        let mut out = CsvOut::create(path, true).unwrap();
+        out.write_all(rows.as_bytes()).unwrap();
+        // Flush no meio (CSV_FLUSH_EVERY) não quebra o membro
+        out.flush().unwrap();
+        writeln!(out, "run,3,900").unwrap();
+        out.finish(false).unwrap();
+        assert!(out.write_all(b"late").is_err());
+        // Checkpoint de reconexão: segundo membro no mesmo arquivo
+        let mut out = CsvOut::append(path, true).unwrap();
+        writeln!(out, "run,4,1000").unwrap();
+        out.finish(true).unwrap();
+
+        assert_eq!(gunzip(path), format!("{}run,3,900\nrun,4,1000\n", rows));
+        std::fs::remove_file(path).unwrap();
+    }
+}
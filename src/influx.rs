@@ -0,0 +1,286 @@
+//! Envio opcional de pontos de latência para o InfluxDB (INFLUX_URL).
+//!
+//! Um ponto por tick do display, em line protocol:
+//!   binance_latency,machine_id=tokyo-1,symbol=btcusdt trades=950i,tps=950.00,avg_us=1421.3,p99_us=3100i <ts_us>
+//! enviado por POST em `/api/v2/write` (InfluxDB 2.x, `Authorization: Token`).
+//!
+//! O tick só faz `try_send` num canal limitado; uma task separada junta os
+//! pontos pendentes num lote e faz o POST. Falha de envio mantém o lote para
+//! a próxima tentativa (até MAX_PENDING pontos, os mais antigos são
+//! descartados e contados como falha); nada disso interrompe a coleta.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+
+use crate::stats::{LatencyStats, SampleMode};
+
+const MEASUREMENT: &str = "binance_latency";
+const CHANNEL_CAPACITY: usize = 1024;
+/// Points kept for retry while InfluxDB is unreachable (~1h at 1 point/s).
+const MAX_PENDING: usize = 3600;
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Line protocol tag values: `,` ` ` and `=` are backslash-escaped.
+fn escape_tag(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, ',' | ' ' | '=' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// One interval of the run.
+pub struct Point {
+    pub ts_us: i64,
+    pub trades: u64,
+    pub tps: f64,
+    /// None when the interval had no trades.
+    pub avg_us: Option<f64>,
+    pub p99_us: Option<i64>,
+}
+
+/// Formats `point` as one line-protocol line (µs precision). `tags` is the
+/// `,machine_id=...,symbol=...` suffix of the measurement.
+pub fn line(tags: &str, point: &Point) -> String {
+    let mut line = format!(
+        "{}{} trades={}i,tps={:.2}",
+        MEASUREMENT, tags, point.trades, point.tps
+    );
+    if let Some(avg) = point.avg_us {
+        line.push_str(&format!(",avg_us={:.1}", avg));
+    }
+    if let Some(p99) = point.p99_us {
+        line.push_str(&format!(",p99_us={}i", p99));
+    }
+    line.push_str(&format!(" {}", point.ts_us));
+    line
+}
+
+/// Handle used by the collection loop: one `record` per trade, one `emit`
+/// per display tick.
+pub struct InfluxSink {
+    tx: mpsc::Sender<String>,
+    sent: Arc<AtomicU64>,
+    failed: Arc<AtomicU64>,
+    task: tokio::task::JoinHandle<()>,
+    url: String,
+    tags: String,
+    last_count: u64,
+    last_total_us: f64,
+    /// Latencies of the current interval only (for the p99 field).
+    interval: LatencyStats,
+    /// None until the first tick, which only opens the first interval.
+    last_elapsed: Option<Duration>,
+}
+
+impl InfluxSink {
+    /// None when INFLUX_URL is unset. INFLUX_BUCKET is required;
+    /// INFLUX_TOKEN and INFLUX_ORG are optional.
+    pub fn from_env(machine_id: &str, symbol: &str, stats: &LatencyStats) -> Option<Self> {
+        let base = std::env::var("INFLUX_URL").ok()?;
+        let Ok(bucket) = std::env::var("INFLUX_BUCKET") else {
+            eprintln!("  WARNING: INFLUX_URL set without INFLUX_BUCKET; InfluxDB disabled");
+            return None;
+        };
+        let mut url = format!(
+            "{}/api/v2/write?bucket={}&precision=us",
+            base.trim_end_matches('/'),
+            bucket
+        );
+        if let Ok(org) = std::env::var("INFLUX_ORG") {
+            url.push_str(&format!("&org={}", org));
+        }
+        let client = match reqwest::Client::builder().timeout(HTTP_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("  WARNING: InfluxDB client: {}; InfluxDB disabled", e);
+                return None;
+            }
+        };
+        let token = std::env::var("INFLUX_TOKEN").ok();
+        eprintln!("InfluxDB:   {} ({})", url, MEASUREMENT);
+
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let sent = Arc::new(AtomicU64::new(0));
+        let failed = Arc::new(AtomicU64::new(0));
+        let task = tokio::spawn(writer(client, url.clone(), token, rx, sent.clone(), failed.clone()));
+        let mut interval = stats.empty_like();
+        interval.set_sample_mode(SampleMode::Histogram);
+        Some(Self {
+            tx,
+            sent,
+            failed,
+            task,
+            url,
+            tags: format!(",machine_id={},symbol={}", escape_tag(machine_id), escape_tag(symbol)),
+            last_count: 0,
+            last_total_us: 0.0,
+            interval,
+            last_elapsed: None,
+        })
+    }
+
+    /// Adds one latency to the current interval (same value given to `stats`).
+    #[inline(always)]
+    pub fn record(&mut self, latency_us: i64) {
+        self.interval.record(latency_us);
+    }
+
+    /// Queues the point for the interval since the previous call.
+    pub fn emit(&mut self, ts_us: i64, elapsed: Duration, stats: &LatencyStats) {
+        let p99_us = self.interval.percentiles(&[0.99]).first().copied();
+        self.interval.reset();
+        let Some(last_elapsed) = self.last_elapsed.replace(elapsed) else {
+            return;
+        };
+        let (count, total_us) = stats
+            .get_basic()
+            .map(|(count, avg, _, _)| (count, avg * count as f64))
+            .unwrap_or_default();
+        // reset pelo control socket: a contagem recomeça do zero
+        if count < self.last_count {
+            self.last_count = 0;
+            self.last_total_us = 0.0;
+        }
+        let trades = count - self.last_count;
+        let point = Point {
+            ts_us,
+            trades,
+            tps: trades as f64 / elapsed.saturating_sub(last_elapsed).as_secs_f64().max(0.001),
+            avg_us: (trades > 0).then(|| (total_us - self.last_total_us) / trades as f64),
+            p99_us,
+        };
+        self.last_count = count;
+        self.last_total_us = total_us;
+        if self.tx.try_send(line(&self.tags, &point)).is_err() {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Closes the queue and waits (bounded) for the last batch.
+    /// Returns a one-line summary for the final report.
+    pub async fn finish(self) -> String {
+        drop(self.tx);
+        if tokio::time::timeout(FLUSH_TIMEOUT, self.task).await.is_err() {
+            eprintln!("  WARNING: InfluxDB flush timed out");
+        }
+        format!(
+            "{}: {} points written, {} failed",
+            self.url,
+            self.sent.load(Ordering::Relaxed),
+            self.failed.load(Ordering::Relaxed)
+        )
+    }
+}
+
+async fn post(client: &reqwest::Client, url: &str, token: Option<&str>, body: String) -> Result<(), String> {
+    let mut request = client.post(url).header("Content-Type", "text/plain; charset=utf-8");
+    if let Some(token) = token {
+        request = request.header("Authorization", format!("Token {}", token));
+    }
+    let response = request.body(body).send().await.map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        return Ok(());
+    }
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    Err(format!("HTTP {}: {}", status, text.trim()))
+}
+
+async fn writer(
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+    mut rx: mpsc::Receiver<String>,
+    sent: Arc<AtomicU64>,
+    failed: Arc<AtomicU64>,
+) {
+    let mut pending: Vec<String> = Vec::new();
+    let mut closed = false;
+    // Só a primeira falha de uma sequência é avisada
+    let mut failing = false;
+
+    loop {
+        if pending.is_empty() {
+            match rx.recv().await {
+                Some(point) => pending.push(point),
+                None => break,
+            }
+        }
+        loop {
+            match rx.try_recv() {
+                Ok(point) => pending.push(point),
+                Err(mpsc::error::TryRecvError::Empty) => break,
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    closed = true;
+                    break;
+                }
+            }
+        }
+        if pending.len() > MAX_PENDING {
+            let dropped = pending.len() - MAX_PENDING;
+            pending.drain(..dropped);
+            failed.fetch_add(dropped as u64, Ordering::Relaxed);
+        }
+
+        match post(&client, &url, token.as_deref(), pending.join("\n")).await {
+            Ok(()) => {
+                sent.fetch_add(pending.len() as u64, Ordering::Relaxed);
+                pending.clear();
+                failing = false;
+            }
+            Err(e) => {
+                if !failing {
+                    eprintln!("\n  WARNING: InfluxDB write failed ({}); retrying every {:?}", e, RETRY_DELAY);
+                    failing = true;
+                }
+                // Fim da coleta: não há próxima tentativa
+                if closed {
+                    failed.fetch_add(pending.len() as u64, Ordering::Relaxed);
+                    break;
+                }
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+        }
+        if closed && pending.is_empty() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_escaped() {
+        assert_eq!(escape_tag("tokyo-1"), "tokyo-1");
+        assert_eq!(escape_tag("a b,c=d\\e"), "a\\ b\\,c\\=d\\\\e");
+    }
+
+    #[test]
+    fn line_protocol_fields() {
+        let point = Point {
+            ts_us: 1_700_000_000_000_000,
+            trades: 950,
+            tps: 950.0,
+            avg_us: Some(1421.34),
+            p99_us: Some(3100),
+        };
+        assert_eq!(
+            line(",machine_id=tokyo-1,symbol=btcusdt", &point),
+            "binance_latency,machine_id=tokyo-1,symbol=btcusdt trades=950i,tps=950.00,avg_us=1421.3,p99_us=3100i 1700000000000000"
+        );
+        // Intervalo vazio: sem avg/p99, o ponto ainda sai
+        let idle = Point { ts_us: 5, trades: 0, tps: 0.0, avg_us: None, p99_us: None };
+        assert_eq!(line("", &idle), "binance_latency trades=0i,tps=0.00 5");
+    }
+}
@@ -0,0 +1,85 @@
+//! Coletor de latência dos trades da Binance como biblioteca.
+//!
+//! O binário `binance-trades` usa estes mesmos módulos; aqui ficam as peças
+//! reaproveitáveis para quem quer embutir a coleta na própria aplicação:
+//! parse dos frames (`extract`), estatísticas (`stats`), timestamps de
+//! recebimento (`clock`) e `run_collector`, a conexão (reconexão, standby,
+//! pings) como `Stream` de eventos; `trades()` reduz isso a `TradeRecord`s
+//! para ligar em qualquer destino (banco, fila, CSV próprio). A coleta
+//! completa do binário também fica aqui: `config` (ambiente e validações),
+//! `session` (loop, destinos e relatório final) e `output` (CSV/Parquet).
+//!
+//! ```no_run
+//! use binance_trades::{run_collector, stats::LatencyStats, CollectorConfig};
+//! use futures_util::StreamExt;
+//!
+//! # async fn example() {
+//! let mut config = CollectorConfig::new("btcusdt");
+//! config.min_trades = Some(1000);
+//! let mut stats = LatencyStats::new(1000);
+//! let trades = run_collector(config).trades();
+//! futures_util::pin_mut!(trades);
+//! while let Some(trade) = trades.next().await {
+//!     match trade {
+//!         Ok(t) => stats.update(t.trade_id, t.latency_us),
+//!         Err(e) => eprintln!("collector stopped: {}", e),
+//!     }
+//! }
+//! # }
+//! ```
+
+pub mod anomaly;
+pub mod audit;
+pub mod clock;
+pub mod collector;
+pub mod config;
+pub mod control;
+pub mod cpu_affinity;
+pub mod cross_check;
+pub mod csv;
+pub mod display;
+pub mod extract;
+pub mod gzip;
+pub mod influx;
+pub mod metrics;
+pub mod output;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod ptp;
+pub mod publish;
+pub mod reconnect;
+pub mod report;
+pub mod s3;
+pub mod session;
+pub mod signing;
+pub mod stats;
+pub mod statsd;
+pub mod summary;
+pub mod tcp_info;
+
+pub use collector::{run_collector, CollectorConfig};
+pub use extract::extract_trade_data;
+pub use stats::LatencyStats;
+
+/// Boolean env flag: "1" or "true" enables.
+pub fn env_flag(name: &str) -> bool {
+    matches!(std::env::var(name).as_deref(), Ok("1") | Ok("true"))
+}
+
+/// Parses an env var, falling back to `default` when unset or invalid.
+pub fn env_parse<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// One received trade (one CSV row).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TradeRecord {
+    pub trade_id: u64,
+    pub trade_ts_us: u64,    // trade timestamp (Binance, microseconds)
+    pub recv_ts_us: u64,     // receive timestamp (local, microseconds)
+    pub latency_us: i64,     // difference in microseconds (can be negative if clock drift)
+    pub event_latency_us: Option<i64>, // recv − "E" (network only); None if the frame has no "E"
+}
@@ -0,0 +1,125 @@
+//! Auto-calibração via loopback: mede o overhead do próprio instrumento.
+//!
+//! Sobe um servidor WebSocket local (127.0.0.1) que envia frames de trade no
+//! formato da Binance, e os recebe pelo mesmo caminho do coletor real
+//! (try_connect_ws com TCP_NODELAY -> extract_trade_data -> RecvClock). Como não há
+//! rede, a "latência" medida é o piso do instrumento: pilha TCP local,
+//! framing WebSocket, parse e leitura de clock.
+//!
+//! Cada frame leva também o instante de envio em µs (campo "S"), para medir o
+//! piso com precisão; o cálculo de produção (recv - T) usa "T" em ms e por
+//! isso inclui o erro de truncamento (~0.5ms em média).
+//!
+//! Uso:
+//!   cargo run --release -- --calibrate [count]
+
+use std::time::{Duration, Instant};
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::clock::{ms_to_us, RecvClock};
+use crate::collector::try_connect_ws;
+use crate::extract::{extract_trade_data, extract_u64_field};
+use crate::stats::LatencyStats;
+
+pub const DEFAULT_COUNT: usize = 5000;
+const SEND_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Serves one connection, sending `count` trade frames stamped with `clock`.
+async fn serve(listener: TcpListener, count: usize, clock: &RecvClock) {
+    let Ok((stream, _)) = listener.accept().await else {
+        return;
+    };
+    let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+
+    let mut interval = tokio::time::interval(SEND_INTERVAL);
+    for i in 0..count {
+        interval.tick().await;
+        let now_us = clock.to_epoch_us(Instant::now());
+        let frame = format!(
+            "{{\"e\":\"trade\",\"E\":{},\"s\":\"BTCUSDT\",\"t\":{},\"p\":\"0.0\",\"q\":\"0.0\",\"T\":{},\"S\":{},\"m\":true,\"M\":true}}",
+            now_us / 1000,
+            i + 1,
+            now_us / 1000,
+            now_us
+        );
+        if ws.send(Message::Text(frame)).await.is_err() {
+            return;
+        }
+    }
+    let _ = ws.close(None).await;
+}
+
+fn report(name: &str, stats: &LatencyStats) {
+    let Some((count, avg, min, max, p50, p95, p99)) = stats.get() else {
+        eprintln!("{:<22} no samples", name);
+        return;
+    };
+    eprintln!(
+        "{:<22} n={} min {}µs | avg {:.0}µs | p50 {}µs | p95 {}µs | p99 {}µs | max {}µs",
+        name, count, min, avg, p50, p95, p99, max
+    );
+}
+
+/// Runs the loopback calibration and prints the instrument's latency floor.
+pub async fn run(count: usize) -> Result<(), String> {
+    let clock = RecvClock::from_env();
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| format!("Error binding loopback server: {}", e))?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| format!("No local address: {}", e))?;
+
+    eprintln!("=== Loopback Self-Calibration ===");
+    eprintln!("Server:  ws://{}", addr);
+    eprintln!("Frames:  {} (1 per {:?})", count, SEND_INTERVAL);
+    eprintln!("Clock:   {}", clock.name());
+
+    let client = async {
+        let ws = try_connect_ws(&format!("ws://{}/ws", addr)).await?;
+        let (_write, mut read) = ws.split();
+
+        let mut floor = LatencyStats::new(count);
+        let mut pipeline = LatencyStats::new(count);
+
+        while let Some(msg) = read.next().await {
+            // Mesmo caminho do loop de coleta
+            let recv_instant = Instant::now();
+            let Ok(Message::Text(text)) = msg else {
+                continue;
+            };
+            let data = text.as_bytes();
+            let Some((trade_id, trade_ts_us)) =
+                extract_trade_data(data).and_then(|(id, ts_ms)| Some((id, ms_to_us(ts_ms)?)))
+            else {
+                continue;
+            };
+            let recv_ts_us = clock.to_epoch_us(recv_instant);
+            pipeline.update(trade_id, recv_ts_us as i64 - trade_ts_us);
+
+            if let Some(sent_us) = extract_u64_field(data, b"\"S\":") {
+                floor.record(recv_ts_us as i64 - sent_us as i64);
+            }
+        }
+        Ok::<_, String>((floor, pipeline))
+    };
+    let server = async {
+        serve(listener, count, &clock).await;
+        Ok::<_, String>(())
+    };
+
+    // try_join: se o cliente falhar ao conectar, o servidor (ainda no accept) é descartado
+    let (_, (floor, pipeline)) = tokio::try_join!(server, client)?;
+
+    eprintln!("\n=== Instrument Latency Floor ===");
+    report("Loopback (send→recv)", &floor);
+    report("Pipeline (recv − T)", &pipeline);
+    eprintln!("\nLoopback = overhead do próprio instrumento (subtraia das medições reais).");
+    eprintln!("Pipeline inclui o truncamento de T em ms, como nas medições contra a Binance.");
+    Ok(())
+}
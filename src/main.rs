@@ -12,378 +12,329 @@
 //! - Tudo em memória durante coleta (zero I/O no hot path)
 //! - Single-thread (current_thread runtime)
 //!
+//! O binário só lê a linha de comando e dirige o loop: configuração
+//! (`config`), coleta e relatório (`session`) e saídas (`output`) ficam na
+//! biblioteca. Os modos à parte (`--compare`, `--sweep`...) continuam aqui.
+//!
 //! Uso:
 //!   MACHINE_ID=m8a.xlarge cargo run --release
 //!   MACHINE_ID=m8a.xlarge cargo run --release -- btcusdt 100000
 //!   CSV_FILE=latency.csv MACHINE_ID=m8a.xlarge cargo run --release -- btcusdt 100000
-
-use std::io::Write;
-use std::time::{Duration, Instant, SystemTime};
+//!   cargo run --release -- --bench-parse frames.jsonl [iterations]
+//!   cargo run --release -- --compare a.csv b.csv
+//!   cargo run --release -- --calibrate [count]
+//!   BINANCE_API_KEY=.. BINANCE_API_SECRET=.. ORDER_PRICE=.. ORDER_QTY=.. cargo run --release -- --order-latency
+
+mod bench;
+mod compare;
+mod loopback;
+mod multiplex;
+mod order_latency;
+mod spot_futures;
+mod sweep;
 
 use futures_util::StreamExt;
-use tokio::net::TcpSocket;
-use tokio_tungstenite::tungstenite::Message;
-use tokio_tungstenite::WebSocketStream;
-
-// ---------------------------------------------------------------------------
-// Defaults
-// ---------------------------------------------------------------------------
 
-const DEFAULT_SYMBOL: &str = "btcusdt";
-const DEFAULT_COUNT: usize = 100_000;
-
-// ---------------------------------------------------------------------------
-// High Precision Timestamp
-// ---------------------------------------------------------------------------
-
-/// Monotonic reference to convert Instant -> epoch micros without syscall.
-struct ClockRef {
-    instant: Instant,
-    epoch_us: u64,
+// Os modos do binário usam os módulos da biblioteca como `crate::...`
+use binance_trades::{
+    clock, collector, config, control, csv, display, env_parse, extract, reconnect, session, signing, stats, summary,
+    tcp_info,
+};
+use collector::run_collector;
+use config::{CliArgs, Exchange, RunConfig, DEFAULT_COUNT, DEFAULT_SYMBOL};
+use session::{Session, DISPLAY_INTERVAL};
+
+const DEFAULT_BASELINE_TOLERANCE_PCT: f64 = 10.0;
+
+/// Removes `name <value>` from the CLI args and returns the value.
+fn take_flag(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let pos = args.iter().position(|a| a == name)?;
+    args.remove(pos);
+    (pos < args.len()).then(|| args.remove(pos))
 }
 
-impl ClockRef {
-    fn new() -> Self {
-        // Capture both as close as possible
-        let instant = Instant::now();
-        let epoch_us = SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as u64;
-        Self { instant, epoch_us }
-    }
-
-    /// Converts an Instant to epoch microseconds without syscall.
-    #[inline(always)]
-    fn to_epoch_us(&self, now: Instant) -> u64 {
-        let elapsed = now.duration_since(self.instant).as_micros() as u64;
-        self.epoch_us + elapsed
+/// Splits the positionals `[symbol] [count] [label]` into the symbol and the
+/// rest. With `--symbol` the positional symbol is optional
+/// (`--symbol ethusdt 5000 label`); if given, it must name the same symbol.
+fn split_symbol_arg(args: &[String], symbol_flag: Option<String>) -> Result<(String, &[String]), String> {
+    let Some(flag) = symbol_flag else {
+        let symbol = args.first().cloned().unwrap_or_else(|| DEFAULT_SYMBOL.to_string());
+        return Ok((symbol, args.get(1..).unwrap_or_default()));
+    };
+    // Um número na primeira posição é o count: não há símbolo posicional
+    match args.first().filter(|a| a.parse::<usize>().is_err()) {
+        None => Ok((flag, args)),
+        Some(positional) if positional.eq_ignore_ascii_case(&flag) => Ok((flag, &args[1..])),
+        Some(positional) => Err(format!(
+            "Conflicting symbols: --symbol {} and positional {} (give only one)",
+            flag, positional
+        )),
     }
 }
 
-// ---------------------------------------------------------------------------
-// Manual JSON Parse (zero-alloc)
-// ---------------------------------------------------------------------------
-
-/// Extracts "t" (trade_id) and "T" (trade_ts_ms) from Binance JSON.
-/// Note: Binance sends "T" in milliseconds; we convert to microseconds later for CSV/storage.
-/// Manual parse without allocation — searches directly for numeric fields.
-#[inline(always)]
-fn parse_trade_fast(json: &[u8]) -> Option<(u64, u64)> {
-    let trade_id = extract_u64_field(json, b"\"t\":")?;
-    let trade_ts = extract_u64_field(json, b"\"T\":")?;
-    Some((trade_id, trade_ts))
-}
-
-/// Searches for a numeric field in JSON by pattern `"key":`.
-/// Assumes value is an integer without quotes (true for "t" and "T" from Binance).
-/// Returns the number as-is (no unit conversion here).
-#[inline(always)]
-fn extract_u64_field(json: &[u8], pattern: &[u8]) -> Option<u64> {
-    let pos = find_pattern(json, pattern)?;
-    let start = pos + pattern.len();
-
-    // Skip optional spaces
-    let mut i = start;
-    while i < json.len() && json[i] == b' ' {
-        i += 1;
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let mut args: Vec<String> = std::env::args().collect();
+
+    // --- Gate de regressão: --baseline summary.json [--tolerance 10%] ---
+    let baseline = take_flag(&mut args, "--baseline").map(|path| {
+        summary::Baseline::load(&path).unwrap_or_else(|e| {
+            eprintln!("❌ Invalid baseline: {}", e);
+            std::process::exit(2);
+        })
+    });
+    let tolerance_pct: f64 = match take_flag(&mut args, "--tolerance") {
+        Some(t) => t.trim_end_matches('%').parse().unwrap_or_else(|_| {
+            eprintln!("Usage: --tolerance <percent>, e.g. --tolerance 10%");
+            std::process::exit(2);
+        }),
+        None => DEFAULT_BASELINE_TOLERANCE_PCT,
+    };
+
+    // --- Varredura de símbolos: --sweep symbols.txt [--per-symbol-trades N] ---
+    let per_symbol_trades = take_flag(&mut args, "--per-symbol-trades").map(|n| {
+        n.parse().unwrap_or_else(|_| {
+            eprintln!("Usage: --per-symbol-trades <count>");
+            std::process::exit(2);
+        })
+    });
+
+    // --- Coletor: --symbol, --stream-type, --endpoint (sobrepõem posicional/env) ---
+    let symbol_flag = take_flag(&mut args, "--symbol");
+    let stream_type_flag = take_flag(&mut args, "--stream-type");
+    let endpoint_flag = take_flag(&mut args, "--endpoint").or_else(|| std::env::var("BINANCE_ENDPOINT").ok());
+    let exchange_name = take_flag(&mut args, "--exchange").or_else(|| std::env::var("BINANCE_EXCHANGE").ok());
+    let exchange = match exchange_name.as_deref().map(|name| (name, Exchange::parse(name))) {
+        None => Exchange::Com,
+        Some((_, Some(exchange))) => exchange,
+        Some((name, None)) => {
+            eprintln!("❌ Unknown exchange {:?} (use com, us or testnet)", name);
+            std::process::exit(2);
+        }
+    };
+
+    // --- Modos offline (sem rede) ---
+    match args.get(1).map(|s| s.as_str()) {
+        Some("--bench-parse") => {
+            let Some(path) = args.get(2) else {
+                eprintln!("Usage: --bench-parse <frames.jsonl> [iterations]");
+                std::process::exit(2);
+            };
+            let iterations = match args.get(3).map(|s| s.parse()) {
+                None => bench::DEFAULT_ITERATIONS,
+                Some(Ok(n)) => n,
+                Some(Err(_)) => {
+                    eprintln!("Usage: --bench-parse <frames.jsonl> [iterations]");
+                    std::process::exit(2);
+                }
+            };
+            if let Err(e) = bench::run(path, iterations) {
+                eprintln!("❌ Error reading frame dump: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("--compare") => {
+            let (Some(a), Some(b)) = (args.get(2), args.get(3)) else {
+                eprintln!("Usage: --compare <a.csv> <b.csv>");
+                std::process::exit(2);
+            };
+            if let Err(e) = compare::run(a, b) {
+                eprintln!("❌ Error reading CSV: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("--calibrate") => {
+            let count = args
+                .get(2)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(loopback::DEFAULT_COUNT);
+            if let Err(e) = loopback::run(count).await {
+                eprintln!("❌ Loopback calibration failed: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("--spot-vs-futures") => {
+            let symbol = args.get(2).map(|s| s.as_str()).unwrap_or(DEFAULT_SYMBOL);
+            let count = args
+                .get(3)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(spot_futures::DEFAULT_COUNT);
+            if let Err(e) = spot_futures::run(symbol, count).await {
+                eprintln!("❌ Spot vs futures mode failed: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("--multiplex-vs-dedicated") => {
+            let symbols: Vec<String> = args
+                .get(2)
+                .map(|s| s.as_str())
+                .unwrap_or(multiplex::DEFAULT_SYMBOLS)
+                .split(',')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect();
+            let count = args
+                .get(3)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(multiplex::DEFAULT_COUNT);
+            if let Err(e) = multiplex::run(&symbols, count).await {
+                eprintln!("❌ Multiplex comparison failed: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("--sweep") => {
+            let Some(path) = args.get(2) else {
+                eprintln!("Usage: --sweep <symbols.txt> [--per-symbol-trades N]");
+                std::process::exit(2);
+            };
+            let per_symbol = per_symbol_trades.unwrap_or(sweep::DEFAULT_PER_SYMBOL_TRADES);
+            if let Err(e) = sweep::run(path, per_symbol).await {
+                eprintln!("❌ Sweep failed: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("--order-latency") => {
+            let result = match order_latency::ApiCredentials::from_env() {
+                Ok(creds) => order_latency::run(creds).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                eprintln!("❌ Order latency mode failed: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        _ => {}
     }
 
-    // Parse number
-    let mut val: u64 = 0;
-    while i < json.len() {
-        let b = json[i];
-        if b >= b'0' && b <= b'9' {
-            val = val * 10 + (b - b'0') as u64;
-            i += 1;
-        } else {
-            break;
+    let (symbol, positional) = match split_symbol_arg(args.get(1..).unwrap_or_default(), symbol_flag) {
+        Ok(split) => split,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(2);
+        }
+    };
+    let count: usize = positional.first().and_then(|s| s.parse().ok()).unwrap_or(DEFAULT_COUNT);
+    // Optional label passed via CLI: <symbol> <count> [label]
+    let label: String = positional.get(1).cloned().unwrap_or_else(|| "unknown".to_string());
+
+    let config = match RunConfig::from_env(CliArgs {
+        symbol,
+        stream_type: stream_type_flag,
+        count,
+        label,
+        endpoint: endpoint_flag,
+        exchange,
+    }) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(2);
         }
+    };
+    // --- Control socket opcional (CONTROL_ADDR=unix:/tmp/x.sock ou 127.0.0.1:9901) ---
+    let mut control = match control::spawn_from_env() {
+        Ok(control) => control,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let mut session = Session::start(config).await;
+    // Toda a parte de socket (pings, reconexão, standby) fica no collector
+    let mut collector = run_collector(session.collector_config());
+    if let Err(e) = session.connect(&mut collector).await {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
     }
 
-    if i > start {
-        Some(val)
-    } else {
-        None
-    }
-}
+    let mut display_tick = tokio::time::interval(DISPLAY_INTERVAL);
+    display_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut tcp_tick = tokio::time::interval(tcp_info::interval_from_env());
+    tcp_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut health_tick = tokio::time::interval(reconnect::HEALTH_CHECK_INTERVAL);
+    health_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    // Ctrl+C: sai do loop e segue o mesmo caminho do fim normal (relatório + CSV)
+    let interrupted = tokio::signal::ctrl_c();
+    tokio::pin!(interrupted);
 
-/// Searches for a byte pattern inside a slice.
-#[inline(always)]
-fn find_pattern(haystack: &[u8], needle: &[u8]) -> Option<usize> {
-    if needle.len() > haystack.len() {
-        return None;
-    }
-    let limit = haystack.len() - needle.len();
-    for i in 0..=limit {
-        if &haystack[i..i + needle.len()] == needle {
-            return Some(i);
+    // --- Collection Loop ---
+    loop {
+        // biased: mensagens sempre têm prioridade sobre o display
+        let flow = tokio::select! {
+            biased;
+            event = collector.next() => session.on_event(event, &collector),
+            () = session.standby_due(), if session.wants_standby() => session.open_standby(&collector),
+            _ = display_tick.tick(), if session.wants_display_tick() => session.on_display_tick(),
+            _ = tcp_tick.tick(), if session.wants_tcp_tick() => session.on_tcp_tick(),
+            _ = health_tick.tick(), if session.wants_health_tick() => session.on_health_tick(&collector),
+            _ = &mut interrupted => session.interrupt(),
+            Some(req) = async { control.as_mut()?.recv().await }, if control.is_some() => session.on_control(req),
+        };
+        if flow.is_break() {
+            break;
         }
     }
-    None
-}
-
-// ---------------------------------------------------------------------------
-// Trade Data
-// ---------------------------------------------------------------------------
-
-struct Trade {
-    trade_id: u64,
-    trade_ts_us: u64,    // trade timestamp (Binance, microseconds)
-    recv_ts_us: u64,     // receive timestamp (local, microseconds)
-    latency_us: i64,     // difference in microseconds (can be negative if clock drift)
-}
-
-// ---------------------------------------------------------------------------
-// Clock Calibration via Binance REST API
-// ---------------------------------------------------------------------------
-
-/// Measures local clock offset vs Binance by making N requests to /api/v3/time.
-/// Returns estimated offset in microseconds (local - server).
-/// 
-/// NOTE: Reduzido para 10-50 amostras para não demorar muito (1000 = ~100 segundos).
-async fn calibrate_clock(n: usize) -> i64 {
-    let n = n.min(50); // Limita a 50 amostras máximo
-    eprintln!("Calibrating clock against Binance ({} samples)...", n);
 
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(5))
-        .build()
-        .expect("Error creating HTTP client");
+    let outcome = session.finish().await;
+    let regressed = baseline.as_ref().is_some_and(|b| b.check(&outcome.stats, tolerance_pct));
 
-    let mut offsets = Vec::with_capacity(n);
-
-    for _ in 0..n {
-        let t1_us = SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as i64;
-
-        let resp = client
-            .get("https://api.binance.com/api/v3/time")
-            .send()
-            .await;
-
-        let t3_us = SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as i64;
+    eprintln!("\n💡 Próximo passo: Faça JOIN dos CSVs por trade_id para análise comparativa");
 
-        if let Ok(resp) = resp {
-            if let Ok(body) = resp.bytes().await {
-                // {"serverTime":1234567890123}
-                if let Some(server_ms) = extract_u64_field(&body, b"\"serverTime\":") {
-                    let server_us = server_ms as i64 * 1000;
-                    let rtt_us = t3_us - t1_us;
-                    // Estimates server timestamp is at RTT/2
-                    let local_at_server = t1_us + rtt_us / 2;
-                    let offset = local_at_server - server_us;
-                    offsets.push((offset, rtt_us));
-                }
-            }
-        }
-        // Sleep menor para acelerar calibração (mas ainda permite múltiplas amostras)
-        tokio::time::sleep(Duration::from_millis(50)).await;
+    if outcome.stream_error {
+        std::process::exit(1);
     }
-
-    if offsets.is_empty() {
-        eprintln!("  WARNING: Could not calibrate. Using offset = 0");
-        return 0;
+    if regressed {
+        std::process::exit(summary::REGRESSION_EXIT_CODE);
     }
-
-    // Use sample with lowest RTT (most accurate)
-    offsets.sort_by_key(|&(_, rtt)| rtt);
-    let best = offsets[0];
-    let median_idx = offsets.len() / 2;
-    let median = offsets[median_idx];
-
-    eprintln!("  Best RTT: {}µs, offset: {}µs", best.1, best.0);
-    eprintln!("  Median RTT: {}µs, offset: {}µs", median.1, median.0);
-    eprintln!(
-        "  Local clock is ~{:.2}ms {} from Binance",
-        best.0.abs() as f64 / 1000.0,
-        if best.0 > 0 { "ahead" } else { "behind" }
-    );
-
-    best.0
 }
 
-// ---------------------------------------------------------------------------
-// WebSocket Connection with TCP_NODELAY
-// ---------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-async fn connect_ws(
-    url: &str,
-) -> WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>> {
-    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
-
-    let request = url.into_client_request().expect("Invalid URL");
-    let domain = request.uri().host().unwrap().to_string();
-    let port = request.uri().port_u16().unwrap_or(9443);
-
-    // Resolve DNS
-    let addr = tokio::net::lookup_host(format!("{}:{}", domain, port))
-        .await
-        .expect("DNS Error")
-        .next()
-        .expect("No IP address");
-
-    // Create socket with TCP_NODELAY
-    let socket = TcpSocket::new_v4().expect("Error creating socket");
-    socket.set_nodelay(true).expect("Error setting TCP_NODELAY");
-
-    let tcp_stream = socket.connect(addr).await.expect("Error connecting TCP");
-
-    // TLS + WebSocket handshake
-    let (ws, _) = tokio_tungstenite::client_async_tls(request, tcp_stream)
-        .await
-        .expect("WebSocket handshake error");
-
-    ws
-}
-
-// ---------------------------------------------------------------------------
-// Save CSV
-// ---------------------------------------------------------------------------
-
-fn save_csv(path: &str, trades: &[Trade], label: &str, machine_id: &str, clock_offset_us: i64) -> std::io::Result<()> {
-    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
-    writeln!(
-        file,
-        "label,machine_id,trade_id,trade_ts_us,recv_ts_us,latency_us,clock_offset_us"
-    )?;
-    for t in trades {
-        writeln!(
-            file,
-            "{},{},{},{},{},{},{}",
-            label,
-            machine_id,
-            t.trade_id,
-            t.trade_ts_us,
-            t.recv_ts_us,
-            t.latency_us,
-            clock_offset_us,
-        )?;
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
     }
-    file.flush()?;
-    Ok(())
-}
-
-// ---------------------------------------------------------------------------
-// Main
-// ---------------------------------------------------------------------------
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() {
-    let args: Vec<String> = std::env::args().collect();
-
-    let symbol = args.get(1).map(|s| s.as_str()).unwrap_or(DEFAULT_SYMBOL);
-    let count: usize = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(DEFAULT_COUNT);
-    // Optional label passed via CLI: <symbol> <count> [label]
-    let label: String = args.get(3).cloned().unwrap_or_else(|| "unknown".to_string());
-    
-    // Machine ID via variável de ambiente (essencial para múltiplas instâncias)
-    let machine_id = std::env::var("MACHINE_ID")
-        .or_else(|_| std::env::var("AWS_REGION"))
-        .unwrap_or_else(|_| "unknown".to_string());
-    
-    // Arquivo de saída único por instância (evita conflitos)
-    let output_file = std::env::var("CSV_FILE")
-        .unwrap_or_else(|_| format!("trades_{}_{}.csv", machine_id, 
-            SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()));
-
-    eprintln!("=== Binance Latency Benchmark ===");
-    eprintln!("Label:      {}", label);
-    eprintln!("Machine ID: {}", machine_id);
-    eprintln!("Symbol:     {}", symbol.to_uppercase());
-    eprintln!("Trades:     {}", count);
-    eprintln!("Output:     {}", output_file);
-
-    // --- Clock Calibration ---
-    // Reduzido para 20 amostras (suficiente e rápido: ~1 segundo)
-    let clock_offset_us = calibrate_clock(20).await;
-
-    // --- Clock reference (monotonic -> epoch without syscall) ---
-    let clock_ref = ClockRef::new();
-
-    // --- Pre-allocate buffer ---
-    let mut trades: Vec<Trade> = Vec::with_capacity(count);
-
-    // --- Connect to WebSocket with TCP_NODELAY ---
-    let url = format!(
-        "wss://stream.binance.com:9443/ws/{}@trade",
-        symbol.to_lowercase()
-    );
-    eprintln!("Connecting to {}...", url);
-
-    let ws = connect_ws(&url).await;
-    let (_write, mut read) = ws.split();
-
-    eprintln!("Connected! Collecting {} trades...", count);
-
-    // --- Collection Loop ---
-    while let Some(msg) = read.next().await {
-        // Timestamp IMMEDIATELY — before any processing
-        let recv_instant = Instant::now();
-
-        let data = match &msg {
-            Ok(Message::Text(text)) => text.as_bytes(),
-            Ok(Message::Binary(bin)) => bin.as_slice(),
-            _ => continue,
-        };
-
-        // Zero-alloc parse
-        if let Some((trade_id, trade_ts_ms)) = parse_trade_fast(data) {
-            // Validação básica: ignora trades inválidos
-            if trade_id == 0 || trade_ts_ms == 0 {
-                continue;
-            }
-            
-            let recv_ts_us = clock_ref.to_epoch_us(recv_instant);
-            let trade_ts_us: u64 = trade_ts_ms * 1000;
-            let latency_us = recv_ts_us as i64 - trade_ts_us as i64 - clock_offset_us;
-
-            trades.push(Trade {
-                trade_id,
-                trade_ts_us,
-                recv_ts_us,
-                latency_us,
-            });
-
-            // Para quando buffer estiver cheio
-            if trades.len() >= count {
-                break;
-            }
-        }
+    #[test]
+    fn positional_symbol_count_label() {
+        let args = strings(&["ethusdt", "5000", "lab"]);
+        let (symbol, rest) = split_symbol_arg(&args, None).unwrap();
+        assert_eq!(symbol, "ethusdt");
+        assert_eq!(rest, &args[1..]);
+        let (symbol, rest) = split_symbol_arg(&[], None).unwrap();
+        assert_eq!(symbol, DEFAULT_SYMBOL);
+        assert!(rest.is_empty());
     }
 
-    eprintln!("Collection finished: {} trades", trades.len());
-    
-    // --- Estatísticas de Latência ---
-    if !trades.is_empty() {
-        let latencies: Vec<i64> = trades.iter().map(|t| t.latency_us).collect();
-        let mut sorted = latencies.clone();
-        sorted.sort();
-        
-        let min = sorted[0];
-        let max = sorted[sorted.len() - 1];
-        let median = sorted[sorted.len() / 2];
-        let p95 = sorted[(sorted.len() as f64 * 0.95) as usize];
-        let p99 = sorted[(sorted.len() as f64 * 0.99) as usize];
-        
-        eprintln!("\n=== Latency Statistics ===");
-        eprintln!("Min:    {}µs", min);
-        eprintln!("Max:    {}µs", max);
-        eprintln!("Median: {}µs", median);
-        eprintln!("P95:    {}µs", p95);
-        eprintln!("P99:    {}µs", p99);
+    #[test]
+    fn symbol_flag_keeps_count_and_label_in_place() {
+        // `--symbol ethusdt 5000 lab`
+        let args = strings(&["5000", "lab"]);
+        let (symbol, rest) = split_symbol_arg(&args, Some("ethusdt".into())).unwrap();
+        assert_eq!(symbol, "ethusdt");
+        assert_eq!(rest, &args[..]);
+        // `ethusdt 5000 --symbol ETHUSDT`: mesmo símbolo, posicionais intactos
+        let args = strings(&["ethusdt", "5000"]);
+        let (symbol, rest) = split_symbol_arg(&args, Some("ETHUSDT".into())).unwrap();
+        assert_eq!(symbol, "ETHUSDT");
+        assert_eq!(rest, &args[1..]);
+        let (_, rest) = split_symbol_arg(&[], Some("ethusdt".into())).unwrap();
+        assert!(rest.is_empty());
     }
 
-    // --- Save CSV ---
-    match save_csv(&output_file, &trades, &label, &machine_id, clock_offset_us) {
-        Ok(()) => eprintln!("\n✅ Data saved to: {}", output_file),
-        Err(e) => eprintln!("\n❌ Error saving CSV: {}", e),
+    #[test]
+    fn conflicting_symbol_is_rejected() {
+        let args = strings(&["btcusdt", "5000"]);
+        let err = split_symbol_arg(&args, Some("ethusdt".into())).unwrap_err();
+        assert!(err.contains("Conflicting"), "{}", err);
     }
-    
-    eprintln!("\n💡 Próximo passo: Faça JOIN dos CSVs por trade_id para análise comparativa");
 }
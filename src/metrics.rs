@@ -0,0 +1,313 @@
+//! Servidor HTTP mínimo de métricas (METRICS_PORT).
+//!
+//! Rotas:
+//!   GET /            -> dashboard HTML embutido (faz polling de /stats.json)
+//!   GET /stats.json  -> último snapshot das estatísticas
+//!   GET /metrics     -> mesmo snapshot no formato texto do Prometheus
+//!
+//! O loop de coleta publica um snapshot por segundo num `watch` channel; o
+//! servidor só lê o último valor publicado, nunca o estado do hot path.
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+
+use crate::reconnect::ReconnectStats;
+use crate::stats::LatencyStats;
+use crate::summary::round_to;
+
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+/// Point-in-time view of the stats, published once per tick.
+#[derive(Clone, Default)]
+pub struct StatsSnapshot {
+    pub trades: u64,
+    pub tps: f64,
+    pub avg_us: f64,
+    pub min_us: i64,
+    pub max_us: i64,
+    pub p50_us: i64,
+    pub p95_us: i64,
+    pub p99_us: i64,
+    pub jitter_us: f64,
+    pub gaps: u64,
+    /// Missing ids that arrived later (already subtracted from `gaps`).
+    pub late_filled: u64,
+    pub out_of_order: u64,
+    pub resets: u64,
+    pub clock_regressions: u64,
+    pub reconnects: u64,
+    pub downtime_s: f64,
+    pub longest_outage_s: f64,
+    pub elapsed_s: f64,
+}
+
+impl StatsSnapshot {
+    pub fn from_stats(stats: &LatencyStats, reconnects: &ReconnectStats, elapsed_s: f64) -> Self {
+        let (gaps, out_of_order, _) = stats.integrity();
+        let mut snap = StatsSnapshot {
+            gaps,
+            late_filled: stats.late_filled(),
+            out_of_order,
+            resets: stats.id_resets(),
+            clock_regressions: stats.clock_regressions().0,
+            reconnects: reconnects.count(),
+            downtime_s: reconnects.total_downtime().as_secs_f64(),
+            longest_outage_s: reconnects.longest_outage().as_secs_f64(),
+            elapsed_s,
+            ..Default::default()
+        };
+        if let Some((count, avg, min, max, p50, p95, p99)) = stats.get() {
+            snap.trades = count;
+            snap.tps = count as f64 / elapsed_s.max(0.001);
+            snap.avg_us = avg;
+            // min > max: sem trades desde o último reset_extremes()
+            if min <= max {
+                snap.min_us = min;
+                snap.max_us = max;
+            }
+            snap.p50_us = p50;
+            snap.p95_us = p95;
+            snap.p99_us = p99;
+            snap.jitter_us = stats.jitter_us();
+        }
+        snap
+    }
+
+    fn to_json(&self, machine_id: &str, symbol: &str) -> String {
+        let doc = SnapshotJson { machine_id, symbol, snap: self };
+        serde_json::to_string(&doc).expect("snapshot serializes to JSON")
+    }
+
+    /// Prometheus text exposition format (0.0.4); latencies in seconds.
+    fn to_prometheus(&self, machine_id: &str, symbol: &str) -> String {
+        let labels = format!(
+            "{{machine_id=\"{}\",symbol=\"{}\"}}",
+            label_escape(machine_id),
+            label_escape(symbol)
+        );
+        let s = |us: f64| us / 1e6;
+        let gauges = [
+            ("binance_latency_avg_seconds", "Average trade latency", s(self.avg_us)),
+            ("binance_latency_p50_seconds", "Median trade latency", s(self.p50_us as f64)),
+            ("binance_latency_p95_seconds", "95th percentile trade latency", s(self.p95_us as f64)),
+            ("binance_latency_p99_seconds", "99th percentile trade latency", s(self.p99_us as f64)),
+            ("binance_latency_jitter_seconds", "RFC 3550 jitter of consecutive latencies", s(self.jitter_us)),
+            ("binance_trades_per_second", "Average trade throughput since start", self.tps),
+        ];
+        let counters = [
+            ("binance_trades_total", "Trades measured", self.trades),
+            // Contador não pode cair: exporta os detectados e os que chegaram depois
+            ("binance_missing_ids_total", "Trade ids seen missing from the sequence (gaps)", self.gaps + self.late_filled),
+            ("binance_late_ids_total", "Missing trade ids that arrived late (subtract for net gaps)", self.late_filled),
+            ("binance_out_of_order_total", "Trades received out of order", self.out_of_order),
+            ("binance_reconnects_total", "WebSocket reconnects", self.reconnects),
+        ];
+        let mut out = String::new();
+        for (name, help, value) in gauges {
+            out.push_str(&format!("# HELP {name} {help}\n# TYPE {name} gauge\n{name}{labels} {value}\n"));
+        }
+        for (name, help, value) in counters {
+            out.push_str(&format!("# HELP {name} {help}\n# TYPE {name} counter\n{name}{labels} {value}\n"));
+        }
+        out
+    }
+}
+
+/// `/stats.json` body: the snapshot tagged with machine and symbol.
+struct SnapshotJson<'a> {
+    machine_id: &'a str,
+    symbol: &'a str,
+    snap: &'a StatsSnapshot,
+}
+
+impl Serialize for SnapshotJson<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let snap = self.snap;
+        let mut doc = serializer.serialize_struct("StatsSnapshot", 19)?;
+        doc.serialize_field("machine_id", self.machine_id)?;
+        doc.serialize_field("symbol", self.symbol)?;
+        doc.serialize_field("trades", &snap.trades)?;
+        doc.serialize_field("tps", &round_to(snap.tps, 2))?;
+        doc.serialize_field("avg_us", &round_to(snap.avg_us, 1))?;
+        doc.serialize_field("min_us", &snap.min_us)?;
+        doc.serialize_field("max_us", &snap.max_us)?;
+        doc.serialize_field("p50_us", &snap.p50_us)?;
+        doc.serialize_field("p95_us", &snap.p95_us)?;
+        doc.serialize_field("p99_us", &snap.p99_us)?;
+        doc.serialize_field("jitter_us", &round_to(snap.jitter_us, 1))?;
+        doc.serialize_field("gaps", &snap.gaps)?;
+        doc.serialize_field("out_of_order", &snap.out_of_order)?;
+        doc.serialize_field("resets", &snap.resets)?;
+        doc.serialize_field("clock_regressions", &snap.clock_regressions)?;
+        doc.serialize_field("reconnects", &snap.reconnects)?;
+        doc.serialize_field("downtime_s", &round_to(snap.downtime_s, 3))?;
+        doc.serialize_field("longest_outage_s", &round_to(snap.longest_outage_s, 3))?;
+        doc.serialize_field("elapsed_s", &round_to(snap.elapsed_s, 1))?;
+        doc.end()
+    }
+}
+
+/// Prometheus label values escape `\`, `"` and newlines.
+fn label_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Serves the dashboard and `/stats.json` on `0.0.0.0:port`.
+pub async fn serve(
+    port: u16,
+    machine_id: String,
+    symbol: String,
+    snapshots: watch::Receiver<StatsSnapshot>,
+) {
+    let listener = match TcpListener::bind(("0.0.0.0", port)).await {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("  WARNING: metrics server on port {} unavailable: {}", port, e);
+            return;
+        }
+    };
+    eprintln!("Metrics server listening on http://0.0.0.0:{}/", port);
+
+    while let Ok((stream, _)) = listener.accept().await {
+        let snapshot = snapshots.borrow().clone();
+        let (machine_id, symbol) = (machine_id.clone(), symbol.clone());
+        tokio::spawn(async move {
+            let _ = handle_conn(stream, &snapshot, &machine_id, &symbol).await;
+        });
+    }
+}
+
+async fn handle_conn(
+    mut stream: TcpStream,
+    snapshot: &StatsSnapshot,
+    machine_id: &str,
+    symbol: &str,
+) -> std::io::Result<()> {
+    // Só a request line importa; o resto do request é ignorado
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+
+    let (status, content_type, body) = match path {
+        "/" | "/index.html" => ("200 OK", "text/html; charset=utf-8", DASHBOARD_HTML.to_string()),
+        "/stats.json" => ("200 OK", "application/json", snapshot.to_json(machine_id, symbol)),
+        "/metrics" => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            snapshot.to_prometheus(machine_id, symbol),
+        ),
+        _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_json_escapes_labels() {
+        let snap = StatsSnapshot { trades: 7, tps: 1.23456, avg_us: 10.06, ..Default::default() };
+        let v: serde_json::Value = serde_json::from_str(&snap.to_json("m\"1\n", "BTCUSDT")).unwrap();
+        assert_eq!(v["machine_id"], "m\"1\n");
+        assert_eq!(v["trades"], 7);
+        assert_eq!(v["tps"], 1.23);
+        assert_eq!(v["avg_us"], 10.1);
+    }
+
+    #[test]
+    fn prometheus_label_escape() {
+        assert_eq!(label_escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    /// Parses `{k="v",...}` with Prometheus escapes; None if malformed.
+    fn parse_labels(s: &str) -> Option<Vec<(String, String)>> {
+        let mut rest = s.strip_prefix('{')?.strip_suffix('}')?;
+        let mut labels = Vec::new();
+        while !rest.is_empty() {
+            let (key, after) = rest.split_once("=\"")?;
+            let mut value = String::new();
+            let mut chars = after.char_indices();
+            let end = loop {
+                match chars.next()? {
+                    (i, '"') => break i,
+                    (_, '\\') => match chars.next()?.1 {
+                        'n' => value.push('\n'),
+                        c @ ('\\' | '"') => value.push(c),
+                        _ => return None,
+                    },
+                    (_, c) => value.push(c),
+                }
+            };
+            labels.push((key.to_string(), value));
+            rest = &after[end + 1..];
+            rest = rest.strip_prefix(',').unwrap_or(rest);
+        }
+        Some(labels)
+    }
+
+    #[tokio::test]
+    async fn metrics_endpoint_serves_parseable_prometheus_text() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let snap = StatsSnapshot {
+            trades: 42,
+            tps: 3.5,
+            avg_us: 1250.5,
+            p50_us: 1000,
+            p95_us: 2000,
+            p99_us: 3000,
+            gaps: 2,
+            late_filled: 1,
+            reconnects: 1,
+            ..Default::default()
+        };
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            handle_conn(stream, &snap, "m\"1\\x", "BTCUSDT").await.unwrap();
+        });
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        server.await.unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK"), "{}", head);
+        assert!(head.contains("Content-Type: text/plain; version=0.0.4"), "{}", head);
+
+        let mut lines = body.lines();
+        let mut samples = std::collections::HashMap::new();
+        while let Some(help) = lines.next() {
+            let name = help.strip_prefix("# HELP ").unwrap().split(' ').next().unwrap();
+            let kind = lines.next().unwrap().strip_prefix(&format!("# TYPE {} ", name)).unwrap();
+            assert!(kind == "gauge" || kind == "counter", "{}", kind);
+            let sample = lines.next().unwrap();
+            let (series, value) = sample.rsplit_once(' ').unwrap();
+            let labels = parse_labels(series.strip_prefix(name).unwrap()).unwrap();
+            assert_eq!(
+                labels,
+                [("machine_id".to_string(), "m\"1\\x".to_string()), ("symbol".to_string(), "BTCUSDT".to_string())]
+            );
+            samples.insert(name.to_string(), value.parse::<f64>().unwrap());
+        }
+        assert_eq!(samples.len(), 11);
+        assert_eq!(samples["binance_trades_total"], 42.0);
+        assert_eq!(samples["binance_latency_p99_seconds"], 0.003);
+        assert_eq!(samples["binance_missing_ids_total"], 3.0);
+        assert_eq!(samples["binance_trades_per_second"], 3.5);
+    }
+}
@@ -0,0 +1,233 @@
+//! Combined stream vs conexões dedicadas: custo do multiplex.
+//!
+//! Abre, ao mesmo tempo, uma conexão combinada (`/stream?streams=a@trade/b@trade`,
+//! payload embrulhado em `{"stream":...,"data":...}`) e uma conexão dedicada
+//! por símbolo (`/ws/<symbol>@trade`). O mesmo trade chega pelos dois caminhos:
+//! a diferença de recebimento (combinado − dedicado) do mesmo `trade_id` é o
+//! custo atribuível ao multiplex, sem depender do relógio nem do timestamp do
+//! servidor.
+//!
+//! Uso:
+//!   cargo run --release -- --multiplex-vs-dedicated btcusdt,ethusdt [count]
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use futures_util::future::join_all;
+use futures_util::StreamExt;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::clock::{ms_to_us, RecvClock};
+use crate::collector::{try_connect_ws, BINANCE_WS_BASE};
+use crate::compare::{ks_p_value, ks_statistic, ALPHA};
+use crate::extract::extract_trade_with_symbol;
+use crate::stats::{percentile, PercentileMethod};
+
+pub const DEFAULT_SYMBOLS: &str = "btcusdt,ethusdt";
+pub const DEFAULT_COUNT: usize = 1000;
+const DEFAULT_TIMEOUT_S: f64 = 300.0;
+
+#[derive(Clone, Copy)]
+struct Sample {
+    trade_id: u64,
+    recv_us: i64,
+    latency_us: i64,
+}
+
+type BySymbol = HashMap<String, Vec<Sample>>;
+
+/// `/ws` endpoint -> combined `/stream?streams=...` endpoint.
+fn combined_url(symbols: &[String]) -> String {
+    let base = BINANCE_WS_BASE.trim_end_matches("/ws");
+    let streams: Vec<String> = symbols.iter().map(|s| format!("{}@trade", s)).collect();
+    format!("{}/stream?streams={}", base, streams.join("/"))
+}
+
+/// Samples of the trade's "s" (upper case on the wire). Compares bytes in
+/// place against the lower-cased keys: no String per message.
+fn slot<'a>(samples: &'a mut BySymbol, symbol: &[u8]) -> Option<&'a mut Vec<Sample>> {
+    samples
+        .iter_mut()
+        .find(|(s, _)| s.as_bytes().eq_ignore_ascii_case(symbol))
+        .map(|(_, v)| v)
+}
+
+/// Collects `count` trades per symbol from one connection (combined or
+/// dedicated: the symbol comes from each trade's "s").
+async fn collect(
+    url: String,
+    symbols: Vec<String>,
+    count: usize,
+    deadline: tokio::time::Instant,
+    clock: &RecvClock,
+) -> Result<BySymbol, String> {
+    let ws = try_connect_ws(&url).await?;
+    let (_write, mut read) = ws.split();
+    let mut samples: BySymbol = symbols
+        .iter()
+        .map(|s| (s.to_ascii_lowercase(), Vec::with_capacity(count)))
+        .collect();
+
+    while samples.values().any(|v| v.len() < count) {
+        let msg = tokio::select! {
+            msg = read.next() => msg,
+            () = tokio::time::sleep_until(deadline) => break,
+        };
+        let recv_instant = Instant::now();
+        let text = match msg {
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(format!("{}: {}", url, e)),
+            None => break,
+        };
+        let Some((symbol, trade_id, ts_ms)) = extract_trade_with_symbol(text.as_bytes()) else {
+            continue;
+        };
+        let Some(ts_us) = ms_to_us(ts_ms) else {
+            continue;
+        };
+        if let Some(v) = slot(&mut samples, symbol) {
+            let recv_us = clock.to_epoch_us(recv_instant) as i64;
+            v.push(Sample {
+                trade_id,
+                recv_us,
+                latency_us: recv_us - ts_us,
+            });
+        }
+    }
+    Ok(samples)
+}
+
+fn sorted(mut v: Vec<i64>) -> Vec<i64> {
+    v.sort_unstable();
+    v
+}
+
+/// Runs the combined and the dedicated connections concurrently and prints
+/// per-symbol latency plus the paired (same trade_id) multiplex delta.
+pub async fn run(symbols: &[String], count: usize) -> Result<(), String> {
+    if symbols.len() < 2 {
+        return Err("need at least two symbols to multiplex".to_string());
+    }
+    let clock = RecvClock::from_env();
+    let timeout =
+        Duration::from_secs_f64(crate::env_parse("MUX_TIMEOUT_S", DEFAULT_TIMEOUT_S).max(1.0));
+    let deadline = tokio::time::Instant::now() + timeout;
+
+    eprintln!("=== Combined Stream vs Dedicated Connections ===");
+    eprintln!("Symbols:   {}", symbols.join(", ").to_uppercase());
+    eprintln!("Trades:    {} per symbol (timeout {:?})", count, timeout);
+    eprintln!("Clock:     {}", clock.name());
+    eprintln!("Combined:  {}", combined_url(symbols));
+
+    let combined = collect(
+        combined_url(symbols),
+        symbols.to_vec(),
+        count,
+        deadline,
+        &clock,
+    );
+    let dedicated = join_all(symbols.iter().map(|s| {
+        let url = format!("{}/{}@trade", BINANCE_WS_BASE, s);
+        collect(url, vec![s.clone()], count, deadline, &clock)
+    }));
+    let (combined, dedicated) = tokio::join!(combined, dedicated);
+    let combined = combined?;
+    let mut dedicated_by_symbol = BySymbol::new();
+    for result in dedicated {
+        dedicated_by_symbol.extend(result?);
+    }
+
+    let q = |v: &[i64], p: f64| percentile(v, p, PercentileMethod::Nearest) as f64 / 1000.0;
+    eprintln!(
+        "\n{:<12} {:>8} {:>12} {:>12} {:>8} {:>14} {:>14}",
+        "Symbol", "Trades", "P50 comb", "P50 ded", "Pairs", "Δ P50 (ms)", "Δ P99 (ms)"
+    );
+
+    let (mut all_comb, mut all_ded, mut all_deltas) = (Vec::new(), Vec::new(), Vec::new());
+    for symbol in symbols {
+        let comb = combined.get(symbol).map(Vec::as_slice).unwrap_or_default();
+        let ded = dedicated_by_symbol
+            .get(symbol)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        // Mesmo trade pelos dois caminhos: diferença de chegada independe do relógio
+        let ded_recv: HashMap<u64, i64> = ded.iter().map(|s| (s.trade_id, s.recv_us)).collect();
+        let deltas = sorted(
+            comb.iter()
+                .filter_map(|s| ded_recv.get(&s.trade_id).map(|d| s.recv_us - d))
+                .collect(),
+        );
+        let comb_lat = sorted(comb.iter().map(|s| s.latency_us).collect());
+        let ded_lat = sorted(ded.iter().map(|s| s.latency_us).collect());
+
+        if comb_lat.is_empty() || ded_lat.is_empty() || deltas.is_empty() {
+            eprintln!(
+                "{:<12} {:>8} (no paired trades)",
+                symbol.to_uppercase(),
+                comb.len()
+            );
+            continue;
+        }
+        eprintln!(
+            "{:<12} {:>8} {:>12.3} {:>12.3} {:>8} {:>+14.3} {:>+14.3}",
+            symbol.to_uppercase(),
+            comb.len().min(ded.len()),
+            q(&comb_lat, 0.50),
+            q(&ded_lat, 0.50),
+            deltas.len(),
+            q(&deltas, 0.50),
+            q(&deltas, 0.99)
+        );
+        all_comb.extend(comb_lat);
+        all_ded.extend(ded_lat);
+        all_deltas.extend(deltas);
+    }
+
+    if all_deltas.is_empty() {
+        return Err("no trade was received on both paths".to_string());
+    }
+    let (all_comb, all_ded, all_deltas) = (sorted(all_comb), sorted(all_ded), sorted(all_deltas));
+    let later = all_deltas.iter().filter(|&&d| d > 0).count();
+    eprintln!(
+        "\nMultiplex delta (combined − dedicated, same trade): P50 {:+.3}ms | P95 {:+.3}ms | P99 {:+.3}ms | combined later in {:.1}% of {} pairs",
+        q(&all_deltas, 0.50),
+        q(&all_deltas, 0.95),
+        q(&all_deltas, 0.99),
+        later as f64 * 100.0 / all_deltas.len() as f64,
+        all_deltas.len()
+    );
+
+    let d = ks_statistic(&all_comb, &all_ded);
+    let p = ks_p_value(d, all_comb.len(), all_ded.len());
+    eprintln!("KS statistic (D): {:.6} | p-value (approx): {:.6}", d, p);
+    if p < ALPHA {
+        eprintln!(
+            "Result: latency distributions DIFFER between combined and dedicated (p < {})",
+            ALPHA
+        );
+    } else {
+        eprintln!("Result: no significant difference (p >= {})", ALPHA);
+    }
+    eprintln!("Note: todas as conexões são lidas pela mesma thread; o delta inclui a ordem de leitura entre elas (µs).");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slot_matches_symbol_case_insensitively() {
+        let mut samples: BySymbol = ["btcusdt", "ethusdt"]
+            .iter()
+            .map(|s| (s.to_string(), Vec::new()))
+            .collect();
+        let sample = Sample { trade_id: 1, recv_us: 0, latency_us: 0 };
+        slot(&mut samples, b"ETHUSDT").unwrap().push(sample);
+        assert_eq!(samples["ethusdt"].len(), 1);
+        assert!(samples["btcusdt"].is_empty());
+        assert!(slot(&mut samples, b"BNBUSDT").is_none());
+        assert!(slot(&mut samples, b"ETHUSD").is_none());
+    }
+}
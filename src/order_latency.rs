@@ -0,0 +1,383 @@
+//! Latência order-to-ack: coloca uma ordem LIMIT via REST assinado e mede o
+//! tempo até o `executionReport` correspondente chegar no user-data stream.
+//! Em seguida cancela a ordem e mede o cancel-to-ack da mesma forma.
+//!
+//! ATENÇÃO: coloca ordens REAIS na conta. Use um preço longe do mercado (para
+//! a ordem não ser executada) e a menor quantidade permitida pelo símbolo.
+//!
+//! Uso (ou BINANCE_CREDS_FILE=arquivo com api_key=/api_secret=):
+//!   BINANCE_API_KEY=... BINANCE_API_SECRET=... \
+//!   ORDER_SYMBOL=BTCUSDT ORDER_PRICE=10000 ORDER_QTY=0.001 \
+//!   cargo run --release -- --order-latency
+
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::clock::wall_clock_us;
+use crate::collector::{try_connect_ws, WsStream, BINANCE_WS_BASE};
+use crate::env_parse;
+use crate::extract::extract_str_field;
+use crate::signing::hmac_sha256_hex;
+use crate::stats::LatencyStats;
+
+const REST_BASE: &str = "https://api.binance.com";
+const RECV_WINDOW_MS: u64 = 5000;
+const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+const LISTEN_KEY_KEEPALIVE: Duration = Duration::from_secs(30 * 60);
+
+/// API key + secret. `Debug` never prints the secret.
+pub struct ApiCredentials {
+    pub api_key: String,
+    pub api_secret: String,
+}
+
+impl std::fmt::Debug for ApiCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiCredentials")
+            .field("api_key", &self.api_key)
+            .field("api_secret", &"<redacted>")
+            .finish()
+    }
+}
+
+impl ApiCredentials {
+    /// Reads `BINANCE_CREDS_FILE` if set, otherwise `BINANCE_API_KEY` /
+    /// `BINANCE_API_SECRET`.
+    pub fn from_env() -> Result<Self, String> {
+        if let Ok(path) = std::env::var("BINANCE_CREDS_FILE") {
+            return Self::from_file(&path);
+        }
+        let api_key = std::env::var("BINANCE_API_KEY").map_err(|_| "BINANCE_API_KEY not set")?;
+        let api_secret =
+            std::env::var("BINANCE_API_SECRET").map_err(|_| "BINANCE_API_SECRET not set")?;
+        Ok(Self {
+            api_key,
+            api_secret,
+        })
+    }
+
+    /// Reads `api_key=...` / `api_secret=...` lines (`#` comments allowed).
+    /// Warns if the file is readable by other users.
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let metadata =
+            std::fs::metadata(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = metadata.permissions().mode();
+            if mode & 0o004 != 0 {
+                eprintln!(
+                    "  WARNING: {} is world-readable (mode {:o}); run: chmod 600 {}",
+                    path,
+                    mode & 0o777,
+                    path
+                );
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = metadata;
+
+        let content =
+            std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+        let (mut api_key, mut api_secret) = (None, None);
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Nunca inclui o conteúdo da linha no erro: pode ser o secret
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("{}: expected key=value lines", path))?;
+            match key.trim() {
+                "api_key" | "BINANCE_API_KEY" => api_key = Some(value.trim().to_string()),
+                "api_secret" | "BINANCE_API_SECRET" => api_secret = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            api_key: api_key.ok_or_else(|| format!("{}: api_key missing", path))?,
+            api_secret: api_secret.ok_or_else(|| format!("{}: api_secret missing", path))?,
+        })
+    }
+}
+
+/// One `executionReport` event, timestamped on arrival.
+struct ExecEvent {
+    recv: Instant,
+    exec_type: String,      // "x": NEW, CANCELED, TRADE, REJECTED...
+    client_id: String,      // "c"
+    orig_client_id: String, // "C" (preenchido em cancelamentos)
+}
+
+fn now_ms() -> u64 {
+    (wall_clock_us().max(0) / 1000) as u64
+}
+
+/// Sends a request with the API key header; `sign` appends timestamp + signature.
+async fn api_request(
+    client: &reqwest::Client,
+    creds: &ApiCredentials,
+    method: reqwest::Method,
+    path: &str,
+    params: &str,
+    sign: bool,
+) -> Result<String, String> {
+    let query = if sign {
+        let query = format!(
+            "{}&timestamp={}&recvWindow={}",
+            params,
+            now_ms(),
+            RECV_WINDOW_MS
+        );
+        let signature = hmac_sha256_hex(creds.api_secret.as_bytes(), query.as_bytes());
+        format!("{}&signature={}", query, signature)
+    } else {
+        params.to_string()
+    };
+
+    let url = format!("{}{}?{}", REST_BASE, path, query);
+    let resp = client
+        .request(method, url)
+        .header("X-MBX-APIKEY", &creds.api_key)
+        .send()
+        .await
+        .map_err(|e| format!("request error: {}", e))?;
+
+    let status = resp.status();
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| format!("read error: {}", e))?;
+    if status.is_success() {
+        Ok(body)
+    } else {
+        Err(format!("HTTP {}: {}", status, body))
+    }
+}
+
+/// Reads the user-data stream and forwards every `executionReport`.
+async fn forward_exec_reports(
+    mut read: futures_util::stream::SplitStream<WsStream>,
+    tx: mpsc::UnboundedSender<ExecEvent>,
+) {
+    while let Some(msg) = read.next().await {
+        let recv = Instant::now();
+        let Ok(Message::Text(text)) = msg else {
+            continue;
+        };
+        let data = text.as_bytes();
+        if extract_str_field(data, b"\"e\":\"") != Some(b"executionReport".as_slice()) {
+            continue;
+        }
+        let field = |pattern: &[u8]| {
+            extract_str_field(data, pattern)
+                .map(|v| String::from_utf8_lossy(v).into_owned())
+                .unwrap_or_default()
+        };
+        let event = ExecEvent {
+            recv,
+            exec_type: field(b"\"x\":\""),
+            client_id: field(b"\"c\":\""),
+            orig_client_id: field(b"\"C\":\""),
+        };
+        if tx.send(event).is_err() {
+            break;
+        }
+    }
+}
+
+/// Waits for an event matching `pred`, discarding others.
+async fn wait_for(
+    rx: &mut mpsc::UnboundedReceiver<ExecEvent>,
+    pred: impl Fn(&ExecEvent) -> bool,
+) -> Option<ExecEvent> {
+    let deadline = tokio::time::Instant::now() + ACK_TIMEOUT;
+    loop {
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(ev)) if pred(&ev) => return Some(ev),
+            Ok(Some(_)) => continue,
+            _ => return None,
+        }
+    }
+}
+
+fn report(name: &str, stats: &LatencyStats) {
+    let Some((count, avg, min, max, p50, p95, p99)) = stats.get() else {
+        eprintln!("{:<14} no samples", name);
+        return;
+    };
+    eprintln!(
+        "{:<14} n={:<4} min {:>8.2}ms | avg {:>8.2}ms | p50 {:>8.2}ms | p95 {:>8.2}ms | p99 {:>8.2}ms | max {:>8.2}ms",
+        name,
+        count,
+        min as f64 / 1000.0,
+        avg / 1000.0,
+        p50 as f64 / 1000.0,
+        p95 as f64 / 1000.0,
+        p99 as f64 / 1000.0,
+        max as f64 / 1000.0,
+    );
+}
+
+/// Runs the order-to-ack measurement loop.
+pub async fn run(creds: ApiCredentials) -> Result<(), String> {
+    let symbol = std::env::var("ORDER_SYMBOL")
+        .unwrap_or_else(|_| "BTCUSDT".to_string())
+        .to_uppercase();
+    let price = std::env::var("ORDER_PRICE").map_err(|_| "ORDER_PRICE not set")?;
+    let qty = std::env::var("ORDER_QTY").map_err(|_| "ORDER_QTY not set")?;
+    let count: usize = env_parse("ORDER_COUNT", 10);
+    let interval = Duration::from_secs_f64(env_parse("ORDER_INTERVAL_S", 5.0));
+
+    eprintln!("=== Order-to-Ack Latency ===");
+    eprintln!("Symbol:   {}", symbol);
+    eprintln!("Order:    BUY LIMIT {} @ {}", qty, price);
+    eprintln!("Count:    {} (every {:?})", count, interval);
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| format!("Error creating HTTP client: {}", e))?;
+
+    // --- User-data stream (listenKey) ---
+    let body = api_request(
+        &client,
+        &creds,
+        reqwest::Method::POST,
+        "/api/v3/userDataStream",
+        "",
+        false,
+    )
+    .await?;
+    let listen_key = extract_str_field(body.as_bytes(), b"\"listenKey\":\"")
+        .map(|v| String::from_utf8_lossy(v).into_owned())
+        .ok_or_else(|| format!("unexpected listenKey response: {}", body))?;
+    let mut last_keepalive = Instant::now();
+
+    let ws = try_connect_ws(&format!("{}/{}", BINANCE_WS_BASE, listen_key)).await?;
+    let (_write, read) = ws.split();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    tokio::spawn(forward_exec_reports(read, tx));
+    eprintln!("User-data stream connected");
+
+    let mut rest_rtt = LatencyStats::new(count);
+    let mut order_ack = LatencyStats::new(count);
+    let mut cancel_ack = LatencyStats::new(count);
+    let run_id = now_ms() % 1_000_000_000;
+
+    for i in 0..count {
+        if last_keepalive.elapsed() >= LISTEN_KEY_KEEPALIVE {
+            let params = format!("listenKey={}", listen_key);
+            if let Err(e) = api_request(
+                &client,
+                &creds,
+                reqwest::Method::PUT,
+                "/api/v3/userDataStream",
+                &params,
+                false,
+            )
+            .await
+            {
+                eprintln!("  WARNING: listenKey keepalive failed: {}", e);
+            }
+            last_keepalive = Instant::now();
+        }
+
+        let client_id = format!("lat{}x{}", run_id, i);
+
+        // --- Place ---
+        let params = format!(
+            "symbol={}&side=BUY&type=LIMIT&timeInForce=GTC&quantity={}&price={}&newClientOrderId={}",
+            symbol, qty, price, client_id
+        );
+        let sent = Instant::now();
+        if let Err(e) = api_request(
+            &client,
+            &creds,
+            reqwest::Method::POST,
+            "/api/v3/order",
+            &params,
+            true,
+        )
+        .await
+        {
+            eprintln!("  [{}] order rejected: {}", i, e);
+            tokio::time::sleep(interval).await;
+            continue;
+        }
+        rest_rtt.record(sent.elapsed().as_micros() as i64);
+
+        match wait_for(&mut rx, |ev| {
+            ev.client_id == client_id && ev.exec_type != "CANCELED"
+        })
+        .await
+        {
+            Some(ev) => {
+                let us = ev.recv.duration_since(sent).as_micros() as i64;
+                order_ack.record(us);
+                eprintln!(
+                    "  [{}] {} ack in {:.2}ms",
+                    i,
+                    ev.exec_type,
+                    us as f64 / 1000.0
+                );
+                if ev.exec_type == "TRADE" {
+                    eprintln!(
+                        "  WARNING: order {} was FILLED — use a price further from the market",
+                        client_id
+                    );
+                }
+            }
+            None => eprintln!("  [{}] no executionReport within {:?}", i, ACK_TIMEOUT),
+        }
+
+        // --- Cancel ---
+        let params = format!("symbol={}&origClientOrderId={}", symbol, client_id);
+        let sent = Instant::now();
+        match api_request(
+            &client,
+            &creds,
+            reqwest::Method::DELETE,
+            "/api/v3/order",
+            &params,
+            true,
+        )
+        .await
+        {
+            Ok(_) => {
+                let ack = wait_for(&mut rx, |ev| {
+                    ev.exec_type == "CANCELED" && ev.orig_client_id == client_id
+                })
+                .await;
+                if let Some(ev) = ack {
+                    cancel_ack.record(ev.recv.duration_since(sent).as_micros() as i64);
+                }
+            }
+            Err(e) => eprintln!("  [{}] cancel failed: {}", i, e),
+        }
+
+        if i + 1 < count {
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    eprintln!("\n=== Order Latency Statistics ===");
+    report("REST RTT", &rest_rtt);
+    report("Order-to-ack", &order_ack);
+    report("Cancel-to-ack", &cancel_ack);
+
+    let params = format!("listenKey={}", listen_key);
+    let _ = api_request(
+        &client,
+        &creds,
+        reqwest::Method::DELETE,
+        "/api/v3/userDataStream",
+        &params,
+        false,
+    )
+    .await;
+    Ok(())
+}
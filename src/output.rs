@@ -0,0 +1,1120 @@
+//! Destinos por trade: CSV (e Parquet, com a feature `parquet`).
+//!
+//! Modo padrão: os trades ficam em memória e o arquivo é escrito de uma vez
+//! no fim da coleta (zero I/O no hot path). CSV_FLUSH_EVERY e CSV_ROTATE_*
+//! escrevem à medida que chegam, por uma thread própria (`CsvWriter`);
+//! CSV_FLUSH_ON_RECONNECT grava um checkpoint a cada reconexão e CSV_MAX_MB
+//! limita o tamanho do arquivo. `TradeOutput` junta tudo isso para o loop de
+//! coleta: guarda os trades, entrega cada um ao destino configurado e escreve
+//! o que faltar no final.
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::clock::wall_clock_us;
+use crate::config::RunConfig;
+use crate::{audit, cpu_affinity, csv, env_flag, env_parse, gzip, TradeRecord as Trade};
+
+/// Per-symbol file name: `{symbol}` in `base` is replaced, otherwise
+/// `_<symbol>` is inserted before the extension (`latency.csv` -> `latency_btcusdt.csv`).
+pub fn csv_path_for_symbol(base: &str, symbol: &str) -> String {
+    if base.contains("{symbol}") {
+        return base.replace("{symbol}", symbol);
+    }
+    let path = std::path::Path::new(base);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(ext)) => path
+            .with_file_name(format!("{}_{}.{}", stem.to_string_lossy(), symbol, ext.to_string_lossy()))
+            .to_string_lossy()
+            .into_owned(),
+        _ => format!("{}_{}", base, symbol),
+    }
+}
+
+pub const CSV_HEADER: &str =
+    "label,machine_id,trade_id,trade_ts_us,recv_ts_us,trade_latency_us,clock_offset_us,event_latency_us";
+
+fn write_csv_row(
+    out: &mut impl Write,
+    t: &Trade,
+    label: &str,
+    machine_id: &str,
+    clock_offset_us: i64,
+) -> std::io::Result<()> {
+    write!(
+        out,
+        "{},{},{},{},{},{},{},",
+        csv::escape_field(label),
+        csv::escape_field(machine_id),
+        t.trade_id,
+        t.trade_ts_us,
+        t.recv_ts_us,
+        t.latency_us,
+        clock_offset_us,
+    )?;
+    // Sem "E" (depth, ticker, feed sem evento): célula vazia
+    match t.event_latency_us {
+        Some(event_latency_us) => writeln!(out, "{}", event_latency_us),
+        None => writeln!(out),
+    }
+}
+
+/// Digits of `v` in decimal (as written by `{}`).
+fn decimal_len(v: i64) -> u64 {
+    v.unsigned_abs().checked_ilog10().map_or(1, |d| d as u64 + 1) + (v < 0) as u64
+}
+
+/// CSV_MAX_MB: teto de tamanho do CSV. Cada linha é contada quando o trade é
+/// registrado (já escrita ou ainda no buffer do modo padrão), então o arquivo
+/// final nunca passa do teto, qualquer que seja o modo de escrita.
+struct CsvBudget {
+    max_mb: f64,
+    max_bytes: u64,
+    used: u64,
+    /// label, machine_id, clock_offset, separators and newline.
+    fixed_row_len: u64,
+}
+
+impl CsvBudget {
+    fn from_env(label: &str, machine_id: &str, clock_offset_us: i64) -> Option<Self> {
+        let max_mb: f64 = env_parse("CSV_MAX_MB", 0.0);
+        if max_mb <= 0.0 {
+            return None;
+        }
+        eprintln!("CSV cap:    {} MB (run stops when reached)", max_mb);
+        Some(Self {
+            max_mb,
+            max_bytes: (max_mb * 1024.0 * 1024.0) as u64,
+            used: CSV_HEADER.len() as u64 + 1,
+            fixed_row_len: (csv::escape_field(label).len() + csv::escape_field(machine_id).len()) as u64
+                + decimal_len(clock_offset_us)
+                + 8,
+        })
+    }
+
+    /// Reserves the row for `t`; false (nothing reserved) if it would pass the cap.
+    #[inline(always)]
+    fn reserve(&mut self, t: &Trade) -> bool {
+        let row = self.fixed_row_len
+            + decimal_len(t.trade_id as i64)
+            + decimal_len(t.trade_ts_us as i64)
+            + decimal_len(t.recv_ts_us as i64)
+            + decimal_len(t.latency_us)
+            + t.event_latency_us.map_or(0, decimal_len);
+        if self.used + row > self.max_bytes {
+            return false;
+        }
+        self.used += row;
+        true
+    }
+}
+
+/// `fsync`: CSV_FSYNC=1, força os dados até o disco (não só até o kernel).
+/// `gzip`: CSV_GZIP=1, `path` já termina em `.gz`.
+fn save_csv(
+    path: &str,
+    trades: &[Trade],
+    label: &str,
+    machine_id: &str,
+    clock_offset_us: i64,
+    fsync: bool,
+    gzip: bool,
+) -> std::io::Result<()> {
+    let mut file = gzip::CsvOut::create(path, gzip)?;
+    writeln!(file, "{}", CSV_HEADER)?;
+    for t in trades {
+        write_csv_row(&mut file, t, label, machine_id, clock_offset_us)?;
+    }
+    file.finish(fsync)
+}
+
+/// OUTPUT_FORMAT=parquet: the whole run at once (see `parquet`).
+#[cfg(feature = "parquet")]
+fn save_parquet(
+    path: &str,
+    trades: &[Trade],
+    label: &str,
+    machine_id: &str,
+    clock_offset_us: i64,
+    fsync: bool,
+) -> std::io::Result<()> {
+    crate::parquet::save(path, trades, label, machine_id, clock_offset_us, fsync)
+}
+
+/// Without the feature OUTPUT_FORMAT=parquet is refused at startup.
+#[cfg(not(feature = "parquet"))]
+fn save_parquet(_: &str, _: &[Trade], _: &str, _: &str, _: i64, _: bool) -> std::io::Result<()> {
+    unreachable!("OUTPUT_FORMAT=parquet is refused at startup without the parquet feature")
+}
+
+/// CSV_FLUSH_ON_RECONNECT=1: no modo padrão (CSV no final), cada fronteira de
+/// reconexão grava no arquivo as linhas acumuladas desde a anterior. Se o
+/// processo morrer, perde-se no máximo a conexão atual, não a execução inteira.
+/// Com CSV_GZIP=1 cada checkpoint é um membro gzip completo anexado ao arquivo.
+struct CsvCheckpoint {
+    path: String,
+    fsync: bool,
+    gzip: bool,
+    /// Trades already in the file.
+    written: usize,
+    created: bool,
+}
+
+impl CsvCheckpoint {
+    fn new(path: &str, fsync: bool, gzip: bool) -> Self {
+        Self {
+            path: path.to_string(),
+            fsync,
+            gzip,
+            written: 0,
+            created: false,
+        }
+    }
+
+    /// Appends to a file that already has the header and rows (the
+    /// csv-writer stopped midway): nothing of `trades` is in it yet.
+    fn resume(path: &str, fsync: bool, gzip: bool) -> Self {
+        Self {
+            created: true,
+            ..Self::new(path, fsync, gzip)
+        }
+    }
+
+    /// Appends `trades[written..]` (creating the file with the header first).
+    fn flush(&mut self, trades: &[Trade], label: &str, machine_id: &str, clock_offset_us: i64) -> std::io::Result<()> {
+        let mut file = if self.created {
+            gzip::CsvOut::append(&self.path, self.gzip)?
+        } else {
+            gzip::CsvOut::create(&self.path, self.gzip)?
+        };
+        if !self.created {
+            writeln!(file, "{}", CSV_HEADER)?;
+            self.created = true;
+        }
+        for t in &trades[self.written..] {
+            write_csv_row(&mut file, t, label, machine_id, clock_offset_us)?;
+        }
+        file.finish(self.fsync)?;
+        self.written = trades.len();
+        Ok(())
+    }
+}
+
+/// CSV_FLUSH_EVERY=1: escreve cada trade direto no arquivo (um write por
+/// linha, sem buffer em user space), na thread csv-writer. Uma linha escrita
+/// sobrevive a um crash; as que ainda estão na fila da thread, não. Com
+/// CSV_FSYNC=1 cada linha também vai até o disco. Com
+/// CSV_GZIP=1 cada linha é um sync flush do encoder: o arquivo só fica
+/// completo (trailer) no `finish`.
+struct CsvStream {
+    file: gzip::CsvOut,
+    fsync: bool,
+    line: Vec<u8>,
+}
+
+impl CsvStream {
+    fn create(path: &str, fsync: bool, gzip: bool) -> std::io::Result<Self> {
+        let mut file = gzip::CsvOut::create(path, gzip)?;
+        writeln!(file, "{}", CSV_HEADER)?;
+        file.flush()?;
+        if fsync {
+            file.sync_data()?;
+        }
+        Ok(Self {
+            file,
+            fsync,
+            line: Vec::with_capacity(128),
+        })
+    }
+
+    fn append(&mut self, t: &Trade, label: &str, machine_id: &str, clock_offset_us: i64) -> std::io::Result<()> {
+        self.line.clear();
+        write_csv_row(&mut self.line, t, label, machine_id, clock_offset_us)?;
+        self.file.write_all(&self.line)?;
+        self.file.flush()?;
+        if self.fsync {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> std::io::Result<()> {
+        self.file.finish(self.fsync)
+    }
+}
+
+/// CSV_ROTATE_MB / CSV_ROTATE_SECS: escreve as linhas à medida que chegam e,
+/// ao passar do tamanho ou do intervalo, fecha o arquivo atual, renomeia para
+/// `<nome>_<início da parte UTC>.csv` e reabre `<nome>.csv` com o header. A
+/// linha que dispara a rotação já vai para o arquivo novo: nenhuma se perde
+/// nem se repete na fronteira. O último trecho fica com o nome original.
+struct CsvRotate {
+    path: String,
+    file: gzip::CsvOut,
+    fsync: bool,
+    /// CSV_GZIP=1: cada parte é um `.csv.gz` completo.
+    gzip: bool,
+    /// CSV_FLUSH_EVERY=1: flush a cada linha, como o CsvStream.
+    flush_each: bool,
+    max_bytes: Option<u64>,
+    every: Option<Duration>,
+    bytes: u64,
+    opened: Instant,
+    opened_us: i64,
+    line: Vec<u8>,
+    /// Rows in files already rotated away (fallback: reescreve só o resto).
+    rows_rotated: usize,
+    rows_current: usize,
+    parts: Vec<String>,
+}
+
+impl CsvRotate {
+    /// None when neither CSV_ROTATE_MB nor CSV_ROTATE_SECS is set.
+    fn from_env(path: &str, fsync: bool, flush_each: bool, gzip: bool) -> Option<std::io::Result<Self>> {
+        let max_mb: f64 = env_parse("CSV_ROTATE_MB", 0.0);
+        let secs: f64 = env_parse("CSV_ROTATE_SECS", 0.0);
+        if max_mb <= 0.0 && secs <= 0.0 {
+            return None;
+        }
+        let max_bytes = (max_mb > 0.0).then_some((max_mb * 1024.0 * 1024.0) as u64);
+        let every = (secs > 0.0).then(|| Duration::from_secs_f64(secs));
+        Some(Self::new(path, fsync, flush_each, gzip, max_bytes, every))
+    }
+
+    fn new(
+        path: &str,
+        fsync: bool,
+        flush_each: bool,
+        gzip: bool,
+        max_bytes: Option<u64>,
+        every: Option<Duration>,
+    ) -> std::io::Result<Self> {
+        Ok(Self {
+            path: path.to_string(),
+            file: Self::open(path, gzip)?,
+            fsync,
+            gzip,
+            flush_each,
+            max_bytes,
+            every,
+            bytes: CSV_HEADER.len() as u64 + 1,
+            opened: Instant::now(),
+            opened_us: wall_clock_us(),
+            line: Vec::with_capacity(128),
+            rows_rotated: 0,
+            rows_current: 0,
+            parts: Vec::new(),
+        })
+    }
+
+    fn open(path: &str, gzip: bool) -> std::io::Result<gzip::CsvOut> {
+        let mut file = gzip::CsvOut::create(path, gzip)?;
+        writeln!(file, "{}", CSV_HEADER)?;
+        Ok(file)
+    }
+
+    fn describe(&self) -> String {
+        let mut limits = Vec::new();
+        if let Some(max) = self.max_bytes {
+            limits.push(format!("{} KB", max / 1024));
+        }
+        if let Some(every) = self.every {
+            limits.push(format!("{:?}", every));
+        }
+        limits.join(" or ")
+    }
+
+    /// Appends one row, rotating first if it would pass the size or the
+    /// interval elapsed. Returns the path of a part closed by this call.
+    fn append(&mut self, t: &Trade, label: &str, machine_id: &str, clock_offset_us: i64) -> std::io::Result<Option<String>> {
+        self.line.clear();
+        write_csv_row(&mut self.line, t, label, machine_id, clock_offset_us)?;
+        let len = self.line.len() as u64;
+        let full = self.max_bytes.is_some_and(|max| self.bytes + len > max);
+        let due = self.every.is_some_and(|every| self.opened.elapsed() >= every);
+        // Parte vazia não roda (linha maior que o teto vai sozinha)
+        let rotated = if (full || due) && self.rows_current > 0 {
+            Some(self.rotate()?)
+        } else {
+            None
+        };
+        self.file.write_all(&self.line)?;
+        self.bytes += len;
+        self.rows_current += 1;
+        if self.flush_each {
+            self.file.flush()?;
+        }
+        Ok(rotated)
+    }
+
+    /// Closes the current file under its part name and reopens `path`.
+    fn rotate(&mut self) -> std::io::Result<String> {
+        self.finish()?;
+        let part = self.part_name();
+        std::fs::rename(&self.path, &part)?;
+        self.file = Self::open(&self.path, self.gzip)?;
+        self.bytes = CSV_HEADER.len() as u64 + 1;
+        self.opened = Instant::now();
+        self.opened_us = wall_clock_us();
+        self.rows_rotated += self.rows_current;
+        self.rows_current = 0;
+        self.parts.push(part.clone());
+        Ok(part)
+    }
+
+    /// `trades.csv` -> `trades_20260102T030405.csv` (start of the part; `_2`,
+    /// `_3`... if two parts start in the same second). `.csv.gz` stays whole.
+    fn part_name(&self) -> String {
+        let stamp = audit::format_utc_compact(self.opened_us);
+        let (path, gz) = match self.path.strip_suffix(".gz") {
+            Some(path) => (path, ".gz"),
+            None => (self.path.as_str(), ""),
+        };
+        let (stem, ext) = match path.rsplit_once('.') {
+            Some((stem, ext)) if !ext.contains('/') => (stem, format!(".{}{}", ext, gz)),
+            _ => (path, gz.to_string()),
+        };
+        let mut name = format!("{}_{}{}", stem, stamp, ext);
+        let mut n = 2;
+        while std::path::Path::new(&name).exists() {
+            name = format!("{}_{}_{}{}", stem, stamp, n, ext);
+            n += 1;
+        }
+        name
+    }
+
+    /// Flushes (gzip: closes the member) the current file; only at rotation
+    /// and at the end of the run.
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.file.finish(self.fsync)
+    }
+}
+
+/// CSV written as trades arrive (CSV_FLUSH_EVERY or CSV_ROTATE_*).
+enum CsvLive {
+    Stream(CsvStream),
+    Rotate(CsvRotate),
+}
+
+impl CsvLive {
+    /// Rows in parts already closed (kept if writing fails later).
+    fn rows_rotated(&self) -> usize {
+        match self {
+            CsvLive::Stream(_) => 0,
+            CsvLive::Rotate(rotate) => rotate.rows_rotated,
+        }
+    }
+}
+
+/// Sent back by the csv-writer thread.
+enum CsvEvent {
+    /// A part was closed; `rows`: rows written so far (all parts).
+    Rotated { part: String, rows: usize },
+    /// Writing stopped; `finish` returns the error.
+    Failed,
+}
+
+/// Rotated parts and the rows in them. On error, the thread returns the rows
+/// it wrote and `CsvWriter::finish` turns that into the rows lost in the queue.
+type CsvWriterResult = Result<(Vec<String>, usize), (std::io::Error, u64)>;
+
+/// Rows queued for the csv-writer thread before new ones are dropped
+/// (`CSV_QUEUE_ROWS`; ~12 MB of `Trade`s).
+const DEFAULT_CSV_QUEUE_ROWS: usize = 262_144;
+
+/// Thread de escrita do CSV linha a linha: o hot loop só faz `try_send` do
+/// `Trade` (cópia, sem alocação), e write/flush/fsync/rotação acontecem
+/// nesta thread. Um disco lento atrasa o arquivo, não a recepção.
+///
+/// Fila limitada: se o disco não acompanha, a linha é descartada e contada
+/// em vez de bloquear o coletor ou crescer sem limite. O CSV fica
+/// incompleto (o relatório avisa); as estatísticas não perdem nada.
+struct CsvWriter {
+    tx: std::sync::mpsc::SyncSender<Trade>,
+    events: std::sync::mpsc::Receiver<CsvEvent>,
+    thread: std::thread::JoinHandle<CsvWriterResult>,
+    /// Rows queued (only the hot loop touches the counters).
+    sent: u64,
+    /// Rows dropped because the queue was full.
+    dropped: u64,
+}
+
+impl CsvWriter {
+    /// `cpus`: where the thread may run (None = inherit the spawning thread's mask).
+    fn spawn(
+        mut live: CsvLive,
+        label: String,
+        machine_id: String,
+        clock_offset_us: i64,
+        cpus: Option<Vec<usize>>,
+        queue_rows: usize,
+    ) -> std::io::Result<Self> {
+        let (tx, rx) = std::sync::mpsc::sync_channel::<Trade>(queue_rows);
+        let (event_tx, events) = std::sync::mpsc::channel();
+        let thread = std::thread::Builder::new().name("csv-writer".to_string()).spawn(move || {
+            if let Some(cpus) = cpus {
+                cpu_affinity::set_cpu_set(&cpus);
+            }
+            let mut written: u64 = 0;
+            for t in rx {
+                let appended = match &mut live {
+                    CsvLive::Stream(stream) => stream.append(&t, &label, &machine_id, clock_offset_us).map(|()| None),
+                    CsvLive::Rotate(rotate) => rotate.append(&t, &label, &machine_id, clock_offset_us),
+                };
+                match appended {
+                    Ok(None) => {}
+                    Ok(Some(part)) => {
+                        let rows = written as usize + 1;
+                        let _ = event_tx.send(CsvEvent::Rotated { part, rows });
+                    }
+                    Err(e) => {
+                        let _ = event_tx.send(CsvEvent::Failed);
+                        return Err((e, written));
+                    }
+                }
+                written += 1;
+            }
+            // Canal fechado: fim da coleta
+            let rows_rotated = live.rows_rotated();
+            match live {
+                CsvLive::Stream(stream) => stream.finish().map(|()| Vec::new()),
+                CsvLive::Rotate(mut rotate) => rotate.finish().map(|()| std::mem::take(&mut rotate.parts)),
+            }
+            .map(|parts| (parts, rows_rotated))
+            .map_err(|e| (e, written))
+        })?;
+        Ok(Self {
+            tx,
+            events,
+            thread,
+            sent: 0,
+            dropped: 0,
+        })
+    }
+
+    /// Queues one row; never blocks. Ok(true) if the row was dropped (queue
+    /// full); Err gives the row back when the thread already stopped on an
+    /// error (the Failed event arrives through `poll`).
+    #[inline(always)]
+    fn send(&mut self, t: Trade) -> Result<bool, Trade> {
+        match self.tx.try_send(t) {
+            Ok(()) => {
+                self.sent += 1;
+                Ok(false)
+            }
+            Err(std::sync::mpsc::TrySendError::Full(_)) => {
+                self.dropped += 1;
+                Ok(true)
+            }
+            Err(std::sync::mpsc::TrySendError::Disconnected(t)) => Err(t),
+        }
+    }
+
+    /// Next event from the thread, if any (non-blocking).
+    fn poll(&self) -> Option<CsvEvent> {
+        self.events.try_recv().ok()
+    }
+
+    /// Closes the queue and waits for the remaining rows and the final flush.
+    /// Also returns the rows dropped on a full queue; on error, the rows
+    /// queued but never written.
+    fn finish(self) -> (CsvWriterResult, u64) {
+        drop(self.tx);
+        let sent = self.sent;
+        let result = self
+            .thread
+            .join()
+            .unwrap_or_else(|_| Err((std::io::Error::other("CSV writer thread panicked"), 0)))
+            .map_err(|(e, written)| (e, sent.saturating_sub(written)));
+        (result, self.dropped)
+    }
+}
+
+/// Final-report line for rows the csv-writer queue dropped (None if none).
+fn csv_dropped_warning(dropped: u64, queue_rows: usize) -> Option<String> {
+    (dropped > 0).then(|| {
+        format!(
+            "dropped_csv_rows={} (writer queue full, CSV_QUEUE_ROWS={}); the CSV is missing those trades",
+            dropped, queue_rows
+        )
+    })
+}
+
+/// Reported by `TradeOutput::poll` for the run log.
+pub enum OutputEvent {
+    /// A rotated part was closed; `rows`: rows written so far (all parts).
+    Rotated { part: String, rows: usize },
+    /// Writing stopped; the remaining rows are written at the end of the run.
+    Failed(std::io::Error),
+}
+
+/// Every trade of the run plus the file it goes to: kept in memory and
+/// written at the end, or handed to the csv-writer thread as it arrives
+/// (then only the bounded queue holds them; nothing is kept for the end).
+pub struct TradeOutput {
+    path: String,
+    label: String,
+    machine_id: String,
+    clock_offset_us: i64,
+    fsync: bool,
+    gzip: bool,
+    parquet: bool,
+    /// Trades not in the file yet: the whole run in the default mode; with
+    /// the csv-writer, only the rows after it stopped on an error.
+    trades: Vec<Trade>,
+    /// Trades accepted (kept or queued).
+    rows: usize,
+    writer: Option<CsvWriter>,
+    queue_rows: usize,
+    checkpoint: Option<CsvCheckpoint>,
+    budget: Option<CsvBudget>,
+    /// What the csv-writer thread returned (`finish_writer`).
+    live: Option<CsvWriterResult>,
+    dropped: u64,
+    /// Rows queued for the csv-writer when it stopped on an error.
+    lost: u64,
+}
+
+impl TradeOutput {
+    /// Sets up the CSV modes from the env and prints their banner lines.
+    /// SUMMARY_ONLY: only the in-memory trades (no file is written here).
+    /// `writer_cpus`: where the csv-writer thread may run.
+    pub fn from_env(config: &RunConfig, clock_offset_us: i64, writer_cpus: Option<Vec<usize>>) -> Self {
+        let (path, fsync, gzip) = (config.output_file.as_str(), config.csv_fsync, config.csv_gzip);
+        let (label, machine_id) = (config.label.as_str(), config.machine_id.as_str());
+        let summary_only = config.summary_only;
+        if gzip {
+            eprintln!("CSV:        gzip-compressed (level {})", env_parse("CSV_GZIP_LEVEL", gzip::DEFAULT_LEVEL).clamp(1, 9));
+            if env_flag("CSV_FLUSH_EVERY") {
+                // Sync flush por linha: legível até ali, mas comprime bem menos
+                eprintln!("  WARNING: CSV_FLUSH_EVERY with CSV_GZIP: every row is sync-flushed (poor compression); the trailer is only written at the end");
+            }
+        }
+        let stream = if env_flag("CSV_FLUSH_EVERY") && !summary_only {
+            match CsvStream::create(path, fsync, gzip) {
+                Ok(stream) => {
+                    eprintln!(
+                        "CSV:        flushed on every row{} (a crash loses the rows still queued for the writer)",
+                        if fsync { " + fsync" } else { "" }
+                    );
+                    Some(stream)
+                }
+                Err(e) => {
+                    eprintln!("  WARNING: cannot open {} ({}). CSV will be written at the end", path, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        // Rotação por tamanho/tempo: escreve à medida que chega (substitui o CsvStream)
+        let rotate = if summary_only {
+            None
+        } else {
+            match CsvRotate::from_env(path, fsync, stream.is_some(), gzip) {
+                Some(Ok(rotate)) => {
+                    eprintln!("CSV:        rotated at {} (closed parts renamed <name>_<UTC start>)", rotate.describe());
+                    Some(rotate)
+                }
+                Some(Err(e)) => {
+                    eprintln!("  WARNING: cannot open {} ({}). CSV will be written at the end", path, e);
+                    None
+                }
+                None => None,
+            }
+        };
+        // Linha a linha: o hot loop só entrega o trade à thread csv-writer
+        let live = match (stream, rotate) {
+            (_, Some(rotate)) => Some(CsvLive::Rotate(rotate)),
+            (Some(stream), None) => Some(CsvLive::Stream(stream)),
+            (None, None) => None,
+        };
+        let queue_rows: usize = env_parse("CSV_QUEUE_ROWS", DEFAULT_CSV_QUEUE_ROWS).max(1);
+        let writer = live.and_then(|live| {
+            match CsvWriter::spawn(live, label.to_string(), machine_id.to_string(), clock_offset_us, writer_cpus.clone(), queue_rows) {
+                Ok(writer) => {
+                    let cpus = writer_cpus
+                        .as_ref()
+                        .map_or(String::new(), |cpus| format!(", cpus {}", cpu_affinity::format_cpu_list(cpus)));
+                    eprintln!(
+                        "CSV:        rows written by the csv-writer thread (queue of {} rows, new rows dropped when full{})",
+                        queue_rows, cpus
+                    );
+                    Some(writer)
+                }
+                Err(e) => {
+                    eprintln!("  WARNING: cannot start the CSV writer thread ({}). CSV will be written at the end", e);
+                    None
+                }
+            }
+        });
+        // Linha a linha já está no disco: o checkpoint só vale para o modo padrão
+        let checkpoint = (env_flag("CSV_FLUSH_ON_RECONNECT") && !summary_only && writer.is_none()).then(|| {
+            eprintln!("CSV:        flushed at each reconnect{}", if fsync { " + fsync" } else { "" });
+            CsvCheckpoint::new(path, fsync, gzip)
+        });
+        let budget = if summary_only {
+            None
+        } else {
+            CsvBudget::from_env(label, machine_id, clock_offset_us)
+        };
+        Self {
+            path: path.to_string(),
+            label: label.to_string(),
+            machine_id: machine_id.to_string(),
+            clock_offset_us,
+            fsync,
+            gzip,
+            parquet: config.parquet_output,
+            // csv-writer: a memória fica no teto da fila (CSV_QUEUE_ROWS)
+            trades: Vec::with_capacity(if writer.is_some() { 0 } else { config.count }),
+            rows: 0,
+            writer,
+            queue_rows,
+            checkpoint,
+            budget,
+            live: None,
+            dropped: 0,
+            lost: 0,
+        }
+    }
+
+    /// Trades kept in memory (empty while the csv-writer takes them).
+    pub fn trades(&self) -> &[Trade] {
+        &self.trades
+    }
+
+    /// Trades accepted so far, whether kept or handed to the csv-writer.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// CSV_QUEUE_ROWS in effect.
+    pub fn queue_rows(&self) -> usize {
+        self.queue_rows
+    }
+
+    /// Rows missing from the CSV so far: dropped on a full csv-writer queue
+    /// or lost in it when writing failed.
+    pub fn dropped(&self) -> u64 {
+        self.writer.as_ref().map_or(self.dropped, |w| w.dropped) + self.lost
+    }
+
+    /// Hands `trade` to the csv-writer thread, or keeps it for the end. Err
+    /// (the trade is not taken) when the row would pass CSV_MAX_MB; Ok(true)
+    /// for the first row dropped on a full writer queue.
+    #[inline(always)]
+    pub fn push(&mut self, trade: Trade) -> Result<bool, String> {
+        // Teto do CSV: para antes da linha que passaria do limite
+        if let Some(budget) = self.budget.as_mut() {
+            if !budget.reserve(&trade) {
+                return Err(format!("CSV_MAX_MB reached ({} MB)", budget.max_mb));
+            }
+        }
+        self.rows += 1;
+        match self.writer.as_mut() {
+            Some(writer) => match writer.send(trade) {
+                Ok(dropped) => return Ok(dropped && writer.dropped == 1),
+                // Thread parada por erro: a linha fica para o fim
+                Err(trade) => self.trades.push(trade),
+            },
+            None => self.trades.push(trade),
+        }
+        Ok(false)
+    }
+
+    /// Next event from the csv-writer thread, if any (non-blocking).
+    pub fn poll(&mut self) -> Option<OutputEvent> {
+        match self.writer.as_ref()?.poll()? {
+            CsvEvent::Rotated { part, rows } => Some(OutputEvent::Rotated { part, rows }),
+            CsvEvent::Failed => {
+                // O que já está no arquivo fica; as linhas daqui em diante são
+                // anexadas no final (e nas reconexões, como no CSV_FLUSH_ON_RECONNECT)
+                let (result, dropped) = self.writer.take()?.finish();
+                self.dropped = dropped;
+                let (e, lost) = result.err()?;
+                self.lost += lost;
+                self.checkpoint = Some(CsvCheckpoint::resume(&self.path, self.fsync, self.gzip));
+                Some(OutputEvent::Failed(e))
+            }
+        }
+    }
+
+    /// Reconnect boundary (CSV_FLUSH_ON_RECONNECT): flushes the pending rows.
+    /// On failure the checkpoint is dropped and the whole CSV is written at
+    /// the end, as in the default mode.
+    pub fn checkpoint(&mut self) {
+        if let Some(cp) = self.checkpoint.as_mut() {
+            if let Err(e) = cp.flush(&self.trades, &self.label, &self.machine_id, self.clock_offset_us) {
+                eprintln!("  WARNING: CSV flush on reconnect failed ({}). CSV will be written at the end", e);
+                self.checkpoint = None;
+            }
+        }
+    }
+
+    /// Closes the csv-writer queue and waits for the remaining rows and the
+    /// final flush (before the summary, which reports the dropped rows).
+    pub fn finish_writer(&mut self) {
+        if let Some((result, dropped)) = self.writer.take().map(CsvWriter::finish) {
+            if let Err((_, lost)) = &result {
+                self.lost += lost;
+            }
+            self.live = Some(result);
+            self.dropped = dropped;
+        }
+    }
+
+    /// Writes what is not in the file yet (after `finish_writer`): nothing
+    /// in the row-by-row modes (or the rows after a write error), the rest
+    /// after the checkpoints, or the whole run in the default mode.
+    pub fn save(&mut self) -> std::io::Result<()> {
+        let live = self.live.take();
+        if let Some(Ok((parts, rows_rotated))) = &live {
+            if !parts.is_empty() {
+                eprintln!("\n🗂  CSV parts ({} rotated, {} rows):", parts.len(), rows_rotated);
+                for part in parts {
+                    eprintln!("   {}", part);
+                }
+            }
+        }
+        if let Some(warning) = csv_dropped_warning(self.dropped, self.queue_rows) {
+            eprintln!("\n  WARNING: {}", warning);
+        }
+        if self.lost > 0 {
+            eprintln!(
+                "\n  WARNING: lost_csv_rows={} (queued when the CSV write failed); the CSV is missing those trades",
+                self.lost
+            );
+        }
+        let (path, label, machine_id) = (&self.path, &self.label, &self.machine_id);
+        match (live, self.checkpoint.as_mut()) {
+            (Some(Ok(_)), _) => Ok(()),
+            // Falhou no fim (ou sem poll depois da falha): anexa o que ficou e
+            // reporta o erro, o arquivo está incompleto
+            (Some(Err((e, _))), _) => {
+                let mut cp = CsvCheckpoint::resume(path, self.fsync, self.gzip);
+                if !self.trades.is_empty() {
+                    cp.flush(&self.trades, label, machine_id, self.clock_offset_us)?;
+                }
+                Err(e)
+            }
+            // Checkpoints já gravaram o início: só anexa o restante
+            (None, Some(cp)) => cp.flush(&self.trades, label, machine_id, self.clock_offset_us),
+            (None, None) if self.parquet => save_parquet(path, &self.trades, label, machine_id, self.clock_offset_us, self.fsync),
+            (None, None) => save_csv(path, &self.trades, label, machine_id, self.clock_offset_us, self.fsync, self.gzip),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn trade(trade_id: u64) -> Trade {
+        Trade {
+            trade_id,
+            trade_ts_us: 1_700_000_000_000_000,
+            recv_ts_us: 1_700_000_000_001_500,
+            latency_us: 1500,
+            event_latency_us: None,
+        }
+    }
+
+    #[test]
+    fn rotate_part_names() {
+        let dir = temp_dir("rotate_names");
+        let path = dir.join("trades.csv").to_str().unwrap().to_string();
+        let mut rotate = CsvRotate::new(&path, false, false, false, None, None).unwrap();
+        // 2026-01-02T03:04:05.123456Z
+        rotate.opened_us = 1_767_323_045_123_456;
+        let first = rotate.part_name();
+        assert_eq!(first, dir.join("trades_20260102T030405.csv").to_str().unwrap());
+        // Duas partes no mesmo segundo: sufixo _2
+        std::fs::write(&first, "").unwrap();
+        assert_eq!(rotate.part_name(), dir.join("trades_20260102T030405_2.csv").to_str().unwrap());
+
+        rotate.path = dir.join("trades.csv.gz").to_str().unwrap().to_string();
+        assert_eq!(rotate.part_name(), dir.join("trades_20260102T030405.csv.gz").to_str().unwrap());
+        rotate.path = dir.join("trades").to_str().unwrap().to_string();
+        assert_eq!(rotate.part_name(), dir.join("trades_20260102T030405").to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotate_rolls_over_by_size_without_losing_rows() {
+        let dir = temp_dir("rotate_size");
+        let path = dir.join("trades.csv").to_str().unwrap().to_string();
+        let mut row = Vec::new();
+        write_csv_row(&mut row, &trade(1), "lab", "m1", 0).unwrap();
+        // Header + uma linha por parte
+        let max = (CSV_HEADER.len() + 1 + row.len()) as u64;
+        let mut rotate = CsvRotate::new(&path, false, false, false, Some(max), None).unwrap();
+
+        assert_eq!(rotate.append(&trade(1), "lab", "m1", 0).unwrap(), None);
+        let part = rotate.append(&trade(2), "lab", "m1", 0).unwrap().expect("second row rotates");
+        assert_eq!(rotate.rows_rotated, 1);
+        assert_eq!(rotate.rows_current, 1);
+        rotate.finish().unwrap();
+
+        let closed = std::fs::read_to_string(&part).unwrap();
+        let current = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(closed.lines().collect::<Vec<_>>(), [CSV_HEADER, "lab,m1,1,1700000000000000,1700000000001500,1500,0,"]);
+        assert_eq!(current.lines().collect::<Vec<_>>(), [CSV_HEADER, "lab,m1,2,1700000000000000,1700000000001500,1500,0,"]);
+        assert_eq!(rotate.parts, [part]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotate_twice_keeps_every_row() {
+        let dir = temp_dir("rotate_twice");
+        let path = dir.join("trades.csv").to_str().unwrap().to_string();
+        let mut row = Vec::new();
+        write_csv_row(&mut row, &trade(1), "lab", "m1", 0).unwrap();
+        // Header + três linhas por parte: 8 linhas rodam duas vezes (3 + 3 + 2)
+        let max = (CSV_HEADER.len() + 1 + 3 * row.len()) as u64;
+        let mut rotate = CsvRotate::new(&path, false, false, false, Some(max), None).unwrap();
+        let rotations = (1..=8)
+            .filter_map(|id| rotate.append(&trade(id), "lab", "m1", 0).unwrap())
+            .count();
+        rotate.finish().unwrap();
+        assert_eq!(rotations, 2);
+        assert_eq!(rotate.rows_rotated, 6);
+
+        let files: Vec<String> = rotate.parts.iter().cloned().chain([path.clone()]).collect();
+        let mut ids = Vec::new();
+        let mut rows_per_file = Vec::new();
+        for file in &files {
+            let contents = std::fs::read_to_string(file).unwrap();
+            let mut lines = contents.lines();
+            assert_eq!(lines.next(), Some(CSV_HEADER), "{}", file);
+            let rows: Vec<&str> = lines.collect();
+            rows_per_file.push(rows.len());
+            ids.extend(rows.iter().map(|r| r.split(',').nth(2).unwrap().parse::<u64>().unwrap()));
+        }
+        assert_eq!(rows_per_file, [3, 3, 2]);
+        assert_eq!(rows_per_file.iter().sum::<usize>(), 8);
+        // Nenhuma linha perdida ou repetida na fronteira, na ordem
+        assert_eq!(ids, (1..=8).collect::<Vec<_>>());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotate_row_larger_than_limit_goes_alone() {
+        let dir = temp_dir("rotate_tiny");
+        let path = dir.join("trades.csv").to_str().unwrap().to_string();
+        let mut rotate = CsvRotate::new(&path, false, false, false, Some(1), None).unwrap();
+        // Parte vazia não roda: a primeira linha fica no arquivo mesmo acima do teto
+        assert_eq!(rotate.append(&trade(1), "lab", "m1", 0).unwrap(), None);
+        assert!(rotate.append(&trade(2), "lab", "m1", 0).unwrap().is_some());
+        assert!(rotate.append(&trade(3), "lab", "m1", 0).unwrap().is_some());
+        assert_eq!(rotate.rows_rotated, 2);
+        rotate.finish().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn csv_writer_writes_each_record_as_sent() {
+        let dir = temp_dir("csv_writer_roundtrip");
+        let path = dir.join("trades.csv").to_str().unwrap().to_string();
+        let sent = [
+            Trade {
+                trade_id: 7,
+                trade_ts_us: 1_700_000_000_000_000,
+                recv_ts_us: 1_700_000_000_002_345,
+                latency_us: 2_345,
+                event_latency_us: Some(1_234),
+            },
+            // Relógio local atrás: latência negativa; sem "E": célula vazia
+            Trade {
+                trade_id: 8,
+                trade_ts_us: 1_700_000_000_010_000,
+                recv_ts_us: 1_700_000_000_009_100,
+                latency_us: -900,
+                event_latency_us: None,
+            },
+            Trade {
+                trade_id: 9,
+                trade_ts_us: 1_700_000_000_020_000,
+                recv_ts_us: 1_700_000_000_019_990,
+                latency_us: -10,
+                event_latency_us: Some(-25),
+            },
+        ];
+        let stream = CsvStream::create(&path, false, false).unwrap();
+        let mut writer =
+            CsvWriter::spawn(CsvLive::Stream(stream), "run,1".into(), "m8a \"x\"".into(), -150, None, 16).unwrap();
+        for t in sent {
+            assert_eq!(writer.send(t), Ok(false));
+        }
+        let (result, dropped) = writer.finish();
+        assert_eq!(result.unwrap(), (Vec::new(), 0));
+        assert_eq!(dropped, 0);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let mut lines = contents.lines();
+        assert_eq!(lines.next(), Some(CSV_HEADER));
+        let rows: Vec<&str> = lines.collect();
+        assert_eq!(rows.len(), sent.len());
+        for (row, t) in rows.iter().zip(&sent) {
+            let fields = csv::split_line(row);
+            let event = t.event_latency_us.map_or(String::new(), |v| v.to_string());
+            let expected = [
+                "run,1".to_string(),
+                "m8a \"x\"".to_string(),
+                t.trade_id.to_string(),
+                t.trade_ts_us.to_string(),
+                t.recv_ts_us.to_string(),
+                t.latency_us.to_string(),
+                "-150".to_string(),
+                event,
+            ];
+            assert_eq!(fields, expected, "{}", row);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Consumidor lento: um FIFO que ninguém lê trava a thread do CSV assim
+    /// que o buffer do pipe enche. O produtor tem de seguir, contando descartes.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn csv_writer_drops_rows_when_consumer_stalls() {
+        use std::io::Read;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        const ROWS: u64 = 100_000;
+        let dir = temp_dir("csv_writer_drops");
+        let path = dir.join("trades.csv").to_str().unwrap().to_string();
+        let c_path = std::ffi::CString::new(path.clone()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        // Leitor aberto antes (sem bloquear) para o open de escrita não esperar
+        let mut reader = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)
+            .unwrap();
+        let stream = CsvStream::create(&path, false, false).unwrap();
+        let writer = CsvWriter::spawn(CsvLive::Stream(stream), "lab".into(), "m1".into(), 0, None, 16).unwrap();
+
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut writer = writer;
+            let start = Instant::now();
+            for trade_id in 1..=ROWS {
+                let _ = writer.send(trade(trade_id));
+            }
+            let _ = done_tx.send((writer, start.elapsed()));
+        });
+        let (writer, elapsed) = done_rx
+            .recv_timeout(Duration::from_secs(30))
+            .expect("producer blocked on a full CSV queue");
+        // Consumidor parado o tempo todo: só um send que não bloqueia termina
+        // 100k linhas tão rápido, e só descartando
+        assert!(elapsed < Duration::from_secs(2), "send loop took {:?}", elapsed);
+        let dropped = writer.dropped;
+        assert!(dropped > ROWS / 2, "only {} rows dropped", dropped);
+
+        // Libera o consumidor: tudo o que não foi descartado chega ao arquivo
+        let drain = std::thread::spawn(move || {
+            let mut out = Vec::new();
+            let mut buf = [0u8; 65536];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) => return out,
+                    Ok(n) => out.extend_from_slice(&buf[..n]),
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+                    Err(e) => panic!("{}", e),
+                }
+            }
+        });
+        let (result, dropped_at_finish) = writer.finish();
+        assert!(result.is_ok());
+        assert_eq!(dropped_at_finish, dropped);
+        let written = String::from_utf8(drain.join().unwrap()).unwrap();
+        let mut lines = written.lines();
+        assert_eq!(lines.next(), Some(CSV_HEADER));
+        assert_eq!(lines.count() as u64, ROWS - dropped);
+        let warning = csv_dropped_warning(dropped, 16).unwrap();
+        assert!(warning.contains(&format!("dropped_csv_rows={}", dropped)), "{}", warning);
+        assert_eq!(csv_dropped_warning(0, 16), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn trade_output_memory_stays_bounded_while_writer_stalls() {
+        use std::io::Read;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        const ROWS: u64 = 100_000;
+        const QUEUE_ROWS: usize = 16;
+        let dir = temp_dir("trade_output_bounded");
+        let path = dir.join("trades.csv").to_str().unwrap().to_string();
+        let c_path = std::ffi::CString::new(path.clone()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        let mut reader = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)
+            .unwrap();
+        let stream = CsvStream::create(&path, false, false).unwrap();
+        let writer = CsvWriter::spawn(CsvLive::Stream(stream), "lab".into(), "m1".into(), 0, None, QUEUE_ROWS).unwrap();
+        let mut output = TradeOutput {
+            path: path.clone(),
+            label: "lab".into(),
+            machine_id: "m1".into(),
+            clock_offset_us: 0,
+            fsync: false,
+            gzip: false,
+            parquet: false,
+            trades: Vec::new(),
+            rows: 0,
+            writer: Some(writer),
+            queue_rows: QUEUE_ROWS,
+            checkpoint: None,
+            budget: None,
+            live: None,
+            dropped: 0,
+            lost: 0,
+        };
+
+        // Consumidor parado: nada se acumula fora da fila
+        let first_drops = (1..=ROWS).filter(|&id| output.push(trade(id)).unwrap()).count();
+        assert_eq!(first_drops, 1);
+        assert_eq!(output.rows(), ROWS as usize);
+        assert!(output.trades().is_empty());
+        assert_eq!(output.trades.capacity(), 0);
+        let dropped = output.dropped();
+        assert!(dropped > ROWS / 2, "only {} rows dropped", dropped);
+
+        let drain = std::thread::spawn(move || {
+            let mut out = Vec::new();
+            let mut buf = [0u8; 65536];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) => return out,
+                    Ok(n) => out.extend_from_slice(&buf[..n]),
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+                    Err(e) => panic!("{}", e),
+                }
+            }
+        });
+        output.finish_writer();
+        assert_eq!(output.dropped(), dropped);
+        // Descartadas não estão em memória: o save não tem o que reescrever
+        output.save().unwrap();
+        let written = String::from_utf8(drain.join().unwrap()).unwrap();
+        assert_eq!(written.lines().count() as u64, 1 + ROWS - dropped);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
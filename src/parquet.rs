@@ -0,0 +1,163 @@
+//! Saída por trade em Parquet (OUTPUT_FORMAT=parquet, feature `parquet`).
+//!
+//! Os trades viram `RecordBatch`es do arrow, um por row group, gravados pelo
+//! `ArrowWriter` da crate parquet (sem compressão). As colunas são as mesmas
+//! do CSV; ids e timestamps são UINT64, latências INT64 e
+//! `event_latency_us` é nullable (null onde o CSV tem célula vazia).
+//!
+//! Escrito de uma vez no fim da coleta, a partir dos trades em memória: não
+//! combina com os modos de escrita linha a linha do CSV.
+
+use std::fs::File;
+use std::io;
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, Int64Array, RecordBatch, StringArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+
+use crate::TradeRecord as Trade;
+
+/// Rows per row group (one `RecordBatch` each).
+const ROW_GROUP_ROWS: usize = 50_000;
+
+/// Schema of the file: the CSV columns, in the CSV order.
+fn schema() -> Schema {
+    Schema::new(vec![
+        Field::new("label", DataType::Utf8, false),
+        Field::new("machine_id", DataType::Utf8, false),
+        Field::new("trade_id", DataType::UInt64, false),
+        Field::new("trade_ts_us", DataType::UInt64, false),
+        Field::new("recv_ts_us", DataType::UInt64, false),
+        Field::new("trade_latency_us", DataType::Int64, false),
+        Field::new("clock_offset_us", DataType::Int64, false),
+        Field::new("event_latency_us", DataType::Int64, true),
+    ])
+}
+
+/// One row group's worth of trades as a `RecordBatch`.
+fn record_batch(
+    schema: &Arc<Schema>,
+    trades: &[Trade],
+    label: &str,
+    machine_id: &str,
+    clock_offset_us: i64,
+) -> io::Result<RecordBatch> {
+    let rows = trades.len();
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(vec![label; rows])),
+        Arc::new(StringArray::from(vec![machine_id; rows])),
+        Arc::new(UInt64Array::from_iter_values(trades.iter().map(|t| t.trade_id))),
+        Arc::new(UInt64Array::from_iter_values(trades.iter().map(|t| t.trade_ts_us))),
+        Arc::new(UInt64Array::from_iter_values(trades.iter().map(|t| t.recv_ts_us))),
+        Arc::new(Int64Array::from_iter_values(trades.iter().map(|t| t.latency_us))),
+        Arc::new(Int64Array::from(vec![clock_offset_us; rows])),
+        Arc::new(Int64Array::from_iter(trades.iter().map(|t| t.event_latency_us))),
+    ];
+    RecordBatch::try_new(schema.clone(), columns).map_err(io::Error::other)
+}
+
+/// Writes `trades` as one Parquet file (`fsync`: CSV_FSYNC=1).
+pub fn save(
+    path: &str,
+    trades: &[Trade],
+    label: &str,
+    machine_id: &str,
+    clock_offset_us: i64,
+    fsync: bool,
+) -> io::Result<()> {
+    let schema = Arc::new(schema());
+    let props = WriterProperties::builder()
+        .set_max_row_group_size(ROW_GROUP_ROWS)
+        .set_created_by(concat!("binance-trades version ", env!("CARGO_PKG_VERSION")).to_string())
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(props)).map_err(io::Error::other)?;
+    for chunk in trades.chunks(ROW_GROUP_ROWS) {
+        let batch = record_batch(&schema, chunk, label, machine_id, clock_offset_us)?;
+        writer.write(&batch).map_err(io::Error::other)?;
+    }
+    // Fecha o último row group e grava o footer
+    let file = writer.into_inner().map_err(io::Error::other)?;
+    if fsync {
+        file.sync_data()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::basic::{LogicalType, Repetition, Type as PhysicalType};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+
+    fn trades(n: u64) -> Vec<Trade> {
+        (0..n)
+            .map(|i| Trade {
+                trade_id: 1000 + i,
+                trade_ts_us: 1_700_000_000_000_000 + i * 1000,
+                recv_ts_us: 1_700_000_000_001_000 + i * 1000,
+                latency_us: 1000 - i as i64 * 50,
+                event_latency_us: (i % 3 != 0).then_some(i as i64),
+            })
+            .collect()
+    }
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("parquet_{}_{}.parquet", name, std::process::id()));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn read_back_with_the_parquet_crate() {
+        let path = temp_path("round_trip");
+        // Mais de um row group: o último fica parcial
+        let trades = trades(ROW_GROUP_ROWS as u64 + 40);
+        save(&path, &trades, "lab,1", "m\"1", -250, false).unwrap();
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+
+        let meta = reader.metadata();
+        assert_eq!(meta.file_metadata().num_rows(), trades.len() as i64);
+        assert_eq!(meta.num_row_groups(), 2);
+        assert_eq!(meta.row_group(0).num_rows(), ROW_GROUP_ROWS as i64);
+        let schema = meta.file_metadata().schema_descr();
+        let names: Vec<&str> = schema.columns().iter().map(|c| c.name()).collect();
+        assert_eq!(names, crate::output::CSV_HEADER.split(',').collect::<Vec<_>>());
+        let trade_id = schema.column(2);
+        assert_eq!(trade_id.physical_type(), PhysicalType::INT64);
+        assert_eq!(
+            trade_id.logical_type(),
+            Some(LogicalType::Integer { bit_width: 64, is_signed: false })
+        );
+        assert_eq!(schema.column(0).logical_type(), Some(LogicalType::String));
+        assert_eq!(schema.column(7).self_type().get_basic_info().repetition(), Repetition::OPTIONAL);
+        assert_eq!(schema.column(5).self_type().get_basic_info().repetition(), Repetition::REQUIRED);
+
+        let rows: Vec<_> = reader.get_row_iter(None).unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(rows.len(), trades.len());
+        for i in [0, 1, 3, ROW_GROUP_ROWS - 1, ROW_GROUP_ROWS, trades.len() - 1] {
+            let (row, t) = (&rows[i], &trades[i]);
+            assert_eq!(row.get_string(0).unwrap(), "lab,1");
+            assert_eq!(row.get_string(1).unwrap(), "m\"1");
+            assert_eq!(row.get_ulong(2).unwrap(), t.trade_id);
+            assert_eq!(row.get_ulong(3).unwrap(), t.trade_ts_us);
+            assert_eq!(row.get_ulong(4).unwrap(), t.recv_ts_us);
+            assert_eq!(row.get_long(5).unwrap(), t.latency_us);
+            assert_eq!(row.get_long(6).unwrap(), -250);
+            assert_eq!(row.get_long(7).ok(), t.event_latency_us, "row {}", i);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn empty_run_has_no_row_groups() {
+        let path = temp_path("empty");
+        save(&path, &[], "lab", "m1", 0, true).unwrap();
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 0);
+        assert_eq!(reader.metadata().num_row_groups(), 0);
+        assert_eq!(reader.metadata().file_metadata().schema_descr().num_columns(), 8);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+//! PTP hardware clock (PHC) como fonte de timestamps de recebimento.
+//!
+//! Em Linux, um dispositivo `/dev/ptpN` aberto vira um "dynamic clock" que
+//! pode ser lido com `clock_gettime` (clockid derivado do fd, ver
+//! `FD_TO_CLOCKID` no kernel). Diferente do CLOCK_REALTIME, não passa pelo
+//! vDSO: cada leitura é uma syscall.
+//!
+//! ATENÇÃO: PHCs normalmente rodam em TAI (hoje 37s à frente de UTC), a menos
+//! que o phc2sys/ptp4l estejam configurados para UTC. Use `PTP_UTC_OFFSET_S`
+//! para compensar.
+
+use std::fs::File;
+
+pub struct PtpClock {
+    // Mantém o fd aberto: o clockid só é válido enquanto o fd existir
+    _file: File,
+    #[cfg(target_os = "linux")]
+    clock_id: libc::clockid_t,
+}
+
+impl PtpClock {
+    /// Opens the PHC device and checks that it can be read.
+    #[cfg(target_os = "linux")]
+    pub fn open(path: &str) -> std::io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let file = File::open(path)?;
+        // FD_TO_CLOCKID(fd) = ((~fd) << 3) | CLOCKFD
+        let clock_id = ((!file.as_raw_fd()) << 3) | 3;
+        let clock = Self {
+            _file: file,
+            clock_id,
+        };
+        if clock.now_us().is_none() {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(clock)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn open(_path: &str) -> std::io::Result<Self> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "PTP clock is only supported on Linux",
+        ))
+    }
+
+    /// Current PHC time in microseconds since the epoch of the PHC timescale.
+    #[cfg(target_os = "linux")]
+    #[inline(always)]
+    pub fn now_us(&self) -> Option<u64> {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `ts` is a valid, writable timespec and `clock_id` refers to
+        // the fd kept alive by `self._file`.
+        let rc = unsafe { libc::clock_gettime(self.clock_id, &mut ts) };
+        if rc != 0 {
+            return None;
+        }
+        Some(ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1000)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn now_us(&self) -> Option<u64> {
+        None
+    }
+}
@@ -0,0 +1,255 @@
+//! Republicação opcional de cada trade num message bus (PUBLISH_URL).
+//!
+//! Destinos suportados (protocolos em texto, sem dependências extras):
+//!   nats://host:4222/<subject>     -> PUB <subject>
+//!   redis://host:6379/<stream>     -> XADD <stream> * trade <json>
+//!
+//! O hot path só faz `try_send` num canal limitado; uma task separada drena o
+//! canal em lotes e escreve o lote inteiro num único `write_all` (pipelining).
+//! Canal cheio, falha de conexão/escrita e erros do servidor contam como
+//! falhas de publicação; nada disso interrompe a coleta.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+
+use crate::extract::TradeFull;
+
+const CHANNEL_CAPACITY: usize = 65_536;
+const BATCH_MAX: usize = 512;
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug)]
+enum Bus {
+    Nats { subject: String },
+    Redis { stream: String },
+}
+
+impl Bus {
+    fn name(&self) -> &'static str {
+        match self {
+            Bus::Nats { .. } => "nats",
+            Bus::Redis { .. } => "redis",
+        }
+    }
+
+    /// Appends one publish command for `payload` to `out`.
+    fn encode(&self, payload: &str, out: &mut Vec<u8>) {
+        match self {
+            Bus::Nats { subject } => {
+                out.extend_from_slice(format!("PUB {} {}\r\n", subject, payload.len()).as_bytes());
+                out.extend_from_slice(payload.as_bytes());
+                out.extend_from_slice(b"\r\n");
+            }
+            Bus::Redis { stream } => {
+                out.extend_from_slice(b"*5\r\n");
+                for arg in ["XADD", stream, "*", "trade", payload] {
+                    out.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+                    out.extend_from_slice(arg.as_bytes());
+                    out.extend_from_slice(b"\r\n");
+                }
+            }
+        }
+    }
+}
+
+/// Parses `nats://host:port/subject` or `redis://host:port/stream`.
+fn parse_url(url: &str) -> Result<(Bus, String), String> {
+    let (scheme, rest) = url
+        .split_once("://")
+        .ok_or_else(|| format!("invalid PUBLISH_URL: {}", url))?;
+    let (addr, target) = rest
+        .split_once('/')
+        .filter(|(_, t)| !t.is_empty())
+        .ok_or_else(|| format!("PUBLISH_URL needs a subject/stream: {}", url))?;
+    let (bus, default_port) = match scheme {
+        "nats" => (Bus::Nats { subject: target.to_string() }, 4222),
+        "redis" => (Bus::Redis { stream: target.to_string() }, 6379),
+        _ => return Err(format!("unsupported PUBLISH_URL scheme: {}", scheme)),
+    };
+    let addr = if addr.contains(':') {
+        addr.to_string()
+    } else {
+        format!("{}:{}", addr, default_port)
+    };
+    Ok((bus, addr))
+}
+
+/// Handle used by the collection loop. Cheap to call per trade.
+pub struct Publisher {
+    tx: mpsc::Sender<String>,
+    sent: Arc<AtomicU64>,
+    failures: Arc<AtomicU64>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Publisher {
+    /// Parses `url` and spawns the background writer task.
+    pub fn spawn(url: &str) -> Result<Self, String> {
+        let (bus, addr) = parse_url(url)?;
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let sent = Arc::new(AtomicU64::new(0));
+        let failures = Arc::new(AtomicU64::new(0));
+        eprintln!("Publish:    {} {} ({:?})", bus.name(), addr, bus);
+        let task = tokio::spawn(writer(bus, addr, rx, sent.clone(), failures.clone()));
+        Ok(Self {
+            tx,
+            sent,
+            failures,
+            task,
+        })
+    }
+
+    /// Queues `payload` without blocking; a full queue counts as a failure.
+    #[inline]
+    pub fn publish(&self, payload: String) {
+        if self.tx.try_send(payload).is_err() {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Closes the queue and waits (bounded) for pending batches to be written.
+    pub async fn finish(self) -> (u64, u64) {
+        drop(self.tx);
+        if tokio::time::timeout(FLUSH_TIMEOUT, self.task).await.is_err() {
+            eprintln!("  WARNING: publisher flush timed out");
+        }
+        (self.sent.load(Ordering::Relaxed), self.failures.load(Ordering::Relaxed))
+    }
+}
+
+/// JSON record published for each trade.
+pub fn trade_json(
+    symbol: &str,
+    trade_id: u64,
+    trade_ts_us: u64,
+    recv_ts_us: u64,
+    latency_us: i64,
+    fill: Option<&TradeFull>,
+) -> String {
+    let mut json = format!(
+        "{{\"s\":\"{}\",\"t\":{},\"trade_ts_us\":{},\"recv_ts_us\":{},\"latency_us\":{}",
+        symbol, trade_id, trade_ts_us, recv_ts_us, latency_us
+    );
+    // Só no stream trade: preço/quantidade/lado para análise downstream (VWAP...)
+    if let Some(fill) = fill {
+        json.push_str(&format!(
+            ",\"p\":{},\"q\":{},\"m\":{}",
+            fill.price, fill.qty, fill.is_buyer_maker
+        ));
+    }
+    json.push('}');
+    json
+}
+
+async fn connect(bus: &Bus, addr: &str) -> std::io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.set_nodelay(true)?;
+    if let Bus::Nats { .. } = bus {
+        stream
+            .write_all(b"CONNECT {\"verbose\":false,\"pedantic\":false,\"name\":\"binance-trades\"}\r\n")
+            .await?;
+    }
+    Ok(stream)
+}
+
+/// Scans server replies: answers NATS PINGs and counts error replies.
+fn scan_replies(reply: &[u8], pongs: &mut usize) -> u64 {
+    let mut errors = 0;
+    for line in reply.split(|&b| b == b'\n') {
+        if line.starts_with(b"PING") {
+            *pongs += 1;
+        } else if line.starts_with(b"-") {
+            // NATS: -ERR '...'; Redis: -ERR/-WRONGTYPE...
+            errors += 1;
+        }
+    }
+    errors
+}
+
+async fn writer(
+    bus: Bus,
+    addr: String,
+    mut rx: mpsc::Receiver<String>,
+    sent: Arc<AtomicU64>,
+    failures: Arc<AtomicU64>,
+) {
+    let mut stream: Option<TcpStream> = None;
+    let mut batch: Vec<u8> = Vec::with_capacity(BATCH_MAX * 128);
+    let mut reply = [0u8; 4096];
+
+    loop {
+        let first = match stream.as_mut() {
+            Some(s) => tokio::select! {
+                first = rx.recv() => first,
+                n = s.read(&mut reply) => {
+                    let mut pongs = 0;
+                    match n {
+                        Ok(n) if n > 0 => {
+                            failures.fetch_add(scan_replies(&reply[..n], &mut pongs), Ordering::Relaxed);
+                            if pongs > 0 && s.write_all(b"PONG\r\n").await.is_err() {
+                                stream = None;
+                            }
+                        }
+                        _ => {
+                            eprintln!("  WARNING: publisher connection to {} closed", addr);
+                            stream = None;
+                        }
+                    }
+                    continue;
+                }
+            },
+            None => rx.recv().await,
+        };
+        let Some(first) = first else {
+            break;
+        };
+
+        batch.clear();
+        bus.encode(&first, &mut batch);
+        let mut n = 1u64;
+        while (n as usize) < BATCH_MAX {
+            match rx.try_recv() {
+                Ok(payload) => {
+                    bus.encode(&payload, &mut batch);
+                    n += 1;
+                }
+                Err(_) => break,
+            }
+        }
+
+        if stream.is_none() {
+            match connect(&bus, &addr).await {
+                Ok(s) => stream = Some(s),
+                Err(e) => {
+                    eprintln!("  WARNING: publisher connect to {} failed: {}", addr, e);
+                    failures.fetch_add(n, Ordering::Relaxed);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                    continue;
+                }
+            }
+        }
+
+        if let Some(s) = stream.as_mut() {
+            match s.write_all(&batch).await {
+                Ok(()) => {
+                    sent.fetch_add(n, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    eprintln!("  WARNING: publish to {} failed: {}", addr, e);
+                    failures.fetch_add(n, Ordering::Relaxed);
+                    stream = None;
+                }
+            }
+        }
+    }
+
+    if let Some(mut s) = stream {
+        let _ = s.flush().await;
+    }
+}
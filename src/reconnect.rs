@@ -0,0 +1,313 @@
+//! Reconexão forçada quando a latência fica alta de forma sustentada
+//! (RECONNECT_ON_HIGH_LATENCY_MS).
+//!
+//! Uma conexão degradada (rota ruim, buffer do servidor acumulado) tende a
+//! continuar lenta; abrir uma nova costuma cair em outro caminho/servidor.
+//! A p99 de cada janela de verificação é comparada com o limite: só depois de
+//! RECONNECT_SUSTAIN_S segundos seguidos acima dele a reconexão é disparada,
+//! e no máximo uma vez a cada RECONNECT_COOLDOWN_S.
+
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::stats::{percentile, PercentileMethod};
+
+/// How often the window p99 is evaluated.
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+const DEFAULT_SUSTAIN_S: f64 = 10.0;
+const DEFAULT_COOLDOWN_S: f64 = 60.0;
+
+pub struct HighLatencyPolicy {
+    threshold_us: i64,
+    sustain: Duration,
+    cooldown: Duration,
+    /// Latencies since the last check.
+    window: Vec<i64>,
+    high_since: Option<Instant>,
+    last_forced: Option<Instant>,
+}
+
+impl HighLatencyPolicy {
+    /// None unless RECONNECT_ON_HIGH_LATENCY_MS is set to a positive value.
+    pub fn from_env() -> Option<Self> {
+        let threshold_ms: f64 = crate::env_parse("RECONNECT_ON_HIGH_LATENCY_MS", 0.0);
+        if threshold_ms <= 0.0 {
+            return None;
+        }
+        let secs =
+            |name, default: f64| Duration::from_secs_f64(crate::env_parse(name, default).max(0.0));
+        let policy = Self {
+            threshold_us: (threshold_ms * 1000.0) as i64,
+            sustain: secs("RECONNECT_SUSTAIN_S", DEFAULT_SUSTAIN_S),
+            cooldown: secs("RECONNECT_COOLDOWN_S", DEFAULT_COOLDOWN_S),
+            window: Vec::new(),
+            high_since: None,
+            last_forced: None,
+        };
+        eprintln!(
+            "Reconnect:  when p99 > {:.2}ms for {:?} (cooldown {:?})",
+            threshold_ms, policy.sustain, policy.cooldown
+        );
+        Some(policy)
+    }
+
+    pub fn threshold_us(&self) -> i64 {
+        self.threshold_us
+    }
+
+    pub fn sustain(&self) -> Duration {
+        self.sustain
+    }
+
+    pub fn record(&mut self, latency_us: i64) {
+        self.window.push(latency_us);
+    }
+
+    /// Evaluates the window collected since the last call. Returns the window
+    /// p99 when a reconnect is due (and arms the cooldown).
+    pub fn check(&mut self, now: Instant) -> Option<i64> {
+        // Janela vazia (sem trades) não é evidência de nada: mantém o estado
+        if self.window.is_empty() {
+            return None;
+        }
+        self.window.sort_unstable();
+        let p99 = percentile(&self.window, 0.99, PercentileMethod::Nearest);
+        self.window.clear();
+
+        if p99 <= self.threshold_us {
+            self.high_since = None;
+            return None;
+        }
+        let since = *self.high_since.get_or_insert(now);
+        let cooled = self
+            .last_forced
+            .is_none_or(|t| now.duration_since(t) >= self.cooldown);
+        if now.duration_since(since) < self.sustain || !cooled {
+            return None;
+        }
+        self.high_since = None;
+        self.last_forced = Some(now);
+        Some(p99)
+    }
+}
+
+/// Connection stability over the run: reconnects, total downtime and the
+/// longest single outage (last message on the old connection until the
+/// first one on the new).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReconnectStats {
+    count: u64,
+    total_downtime: Duration,
+    longest_outage: Duration,
+    outage_start: Option<Instant>,
+}
+
+impl ReconnectStats {
+    /// Marks the start of an outage (idempotent while one is open).
+    pub fn disconnected(&mut self, at: Instant) {
+        self.outage_start.get_or_insert(at);
+    }
+
+    pub fn reconnected(&mut self) {
+        self.count += 1;
+    }
+
+    pub fn in_outage(&self) -> bool {
+        self.outage_start.is_some()
+    }
+
+    /// Closes the open outage (first message after a reconnect, or end of run).
+    pub fn restored(&mut self, at: Instant) {
+        if let Some(start) = self.outage_start.take() {
+            let outage = at.saturating_duration_since(start);
+            self.total_downtime += outage;
+            self.longest_outage = self.longest_outage.max(outage);
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn total_downtime(&self) -> Duration {
+        self.total_downtime
+    }
+
+    pub fn longest_outage(&self) -> Duration {
+        self.longest_outage
+    }
+
+    /// `overlap_duplicates`: trades dropped by SEAMLESS_RECONNECT dedup.
+    pub fn report(&self, overlap_duplicates: Option<u64>) {
+        let mut table = crate::report::Table::new("Connection Stability");
+        table
+            .count("Reconnects", self.count)
+            .latency("Downtime", self.total_downtime.as_micros() as f64)
+            .note("(total)")
+            .latency("Longest outage", self.longest_outage.as_micros() as f64);
+        if let Some(n) = overlap_duplicates {
+            table.count("Overlap duplicates", n).note("(dropped)");
+        }
+        table.print();
+    }
+}
+
+const DEFAULT_ROTATE_AFTER_S: f64 = 23.0 * 3600.0 + 50.0 * 60.0;
+const DEFAULT_MAX_OVERLAP_S: f64 = 10.0;
+
+/// Retry delay when opening the standby connection fails.
+pub const SEAMLESS_RETRY: Duration = Duration::from_secs(30);
+
+/// Ids remembered during the overlap (~1 min of BTCUSDT at peak).
+const DEDUP_WINDOW: usize = 65_536;
+
+/// Fixed-size set of the most recent ids (oldest evicted first).
+struct RecentIds {
+    order: VecDeque<u64>,
+    seen: HashSet<u64>,
+}
+
+impl RecentIds {
+    fn new() -> Self {
+        Self {
+            order: VecDeque::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// False if `id` is already in the window.
+    #[inline(always)]
+    fn insert(&mut self, id: u64) -> bool {
+        if !self.seen.insert(id) {
+            return false;
+        }
+        if self.order.len() == DEDUP_WINDOW {
+            if let Some(old) = self.order.pop_front() {
+                self.seen.remove(&old);
+            }
+        }
+        self.order.push_back(id);
+        true
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+        self.seen.clear();
+    }
+}
+
+/// Make-before-break rotation ahead of Binance's 24h disconnect
+/// (SEAMLESS_RECONNECT=1).
+///
+/// Um pouco antes do limite abre uma segunda conexão e lê das duas. Trades
+/// repetidos (mesmo id já entregue pela outra) são descartados: ids até o
+/// maior visto antes da sobreposição, e depois os que estão no conjunto de
+/// ids recentes (exato, mesmo com as duas conexões defasadas); a nova assume
+/// quando as duas se sobrepõem (um duplicado prova que não há buraco), quando
+/// a antiga cai, ou após SEAMLESS_OVERLAP_MAX_S entregando trades.
+pub struct SeamlessRotation {
+    rotate_after: Duration,
+    max_overlap: Duration,
+    highest_id: u64,
+    /// highest_id when the standby opened: anything up to it was already delivered.
+    open_mark: u64,
+    /// Ids delivered since the standby opened.
+    recent: RecentIds,
+    /// Standby connection open.
+    overlapping: bool,
+    /// Drop ids <= highest_id (overlap, and right after takeover until the
+    /// new connection passes the old one).
+    dedup: bool,
+    standby_trades_since: Option<Instant>,
+    duplicates: u64,
+}
+
+impl SeamlessRotation {
+    pub fn from_env() -> Option<Self> {
+        if !crate::env_flag("SEAMLESS_RECONNECT") {
+            return None;
+        }
+        let secs = |name, default: f64| {
+            Duration::from_secs_f64(crate::env_parse(name, default).max(1.0))
+        };
+        let rotation = Self {
+            rotate_after: secs("SEAMLESS_RECONNECT_AFTER_S", DEFAULT_ROTATE_AFTER_S),
+            max_overlap: secs("SEAMLESS_OVERLAP_MAX_S", DEFAULT_MAX_OVERLAP_S),
+            highest_id: 0,
+            open_mark: 0,
+            recent: RecentIds::new(),
+            overlapping: false,
+            dedup: false,
+            standby_trades_since: None,
+            duplicates: 0,
+        };
+        eprintln!(
+            "Seamless:   standby connection every {:?} (max overlap {:?})",
+            rotation.rotate_after, rotation.max_overlap
+        );
+        Some(rotation)
+    }
+
+    /// Connection age at which the standby is opened.
+    pub fn rotate_after(&self) -> Duration {
+        self.rotate_after
+    }
+
+    pub fn standby_opened(&mut self) {
+        self.overlapping = true;
+        self.dedup = true;
+        self.open_mark = self.highest_id;
+        self.recent.clear();
+        self.standby_trades_since = None;
+    }
+
+    /// Standby failed before taking over: back to a single connection.
+    pub fn standby_dropped(&mut self) {
+        self.overlapping = false;
+        self.dedup = false;
+        self.recent.clear();
+    }
+
+    pub fn promoted(&mut self) {
+        self.overlapping = false;
+    }
+
+    /// Registers a trade id; false if the other connection already delivered it.
+    #[inline(always)]
+    pub fn accept(&mut self, id: u64) -> bool {
+        if self.dedup {
+            if id <= self.open_mark || !self.recent.insert(id) {
+                self.duplicates += 1;
+                return false;
+            }
+            // Nova conexão já passou da antiga: fim da deduplicação
+            if !self.overlapping && id > self.highest_id {
+                self.dedup = false;
+                self.recent.clear();
+            }
+        }
+        self.highest_id = self.highest_id.max(id);
+        true
+    }
+
+    /// During overlap: whether the standby should take over now.
+    pub fn should_promote(&mut self, duplicate: bool, from_standby: bool, now: Instant) -> bool {
+        if !self.overlapping {
+            return false;
+        }
+        if duplicate {
+            return true;
+        }
+        if !from_standby {
+            return false;
+        }
+        let since = *self.standby_trades_since.get_or_insert(now);
+        now.duration_since(since) >= self.max_overlap
+    }
+
+    /// Trades dropped because both connections delivered them.
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+}
@@ -0,0 +1,157 @@
+//! Tabela de largura fixa para o relatório final (formato humano padrão).
+//!
+//! Rótulos alinhados à esquerda, valores à direita com casas decimais fixas
+//! por tipo (latência sempre em ms com 3 casas = resolução de 1µs), para que
+//! dois relatórios lado a lado no terminal fiquem comparáveis coluna a coluna.
+//! As seções com colunas próprias (por símbolo, latency budget) ficam aqui também.
+
+use crate::stats::SymbolStats;
+
+struct Row {
+    label: String,
+    value: String,
+    unit: &'static str,
+    note: String,
+    /// Free text: not right-aligned and kept out of the column widths.
+    free: bool,
+}
+
+pub struct Table {
+    title: String,
+    rows: Vec<Row>,
+}
+
+impl Table {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            rows: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, label: &str, value: String, unit: &'static str) -> &mut Self {
+        self.rows.push(Row {
+            label: label.to_string(),
+            value,
+            unit,
+            note: String::new(),
+            free: false,
+        });
+        self
+    }
+
+    /// Latency given in µs, shown in ms.
+    pub fn latency(&mut self, label: &str, us: f64) -> &mut Self {
+        self.push(label, format!("{:.3}", us / 1000.0), "ms")
+    }
+
+    pub fn count(&mut self, label: &str, n: u64) -> &mut Self {
+        self.push(label, n.to_string(), "")
+    }
+
+    pub fn text(&mut self, label: &str, value: impl Into<String>) -> &mut Self {
+        self.push(label, value.into(), "");
+        if let Some(row) = self.rows.last_mut() {
+            row.free = true;
+        }
+        self
+    }
+
+    /// Free-text annotation after the unit of the last row.
+    pub fn note(&mut self, note: impl Into<String>) -> &mut Self {
+        if let Some(row) = self.rows.last_mut() {
+            row.note = note.into();
+        }
+        self
+    }
+
+    pub fn print(&self) {
+        eprintln!("\n=== {} ===", self.title);
+        let label_w = self
+            .rows
+            .iter()
+            .map(|r| r.label.chars().count())
+            .max()
+            .unwrap_or(0);
+        let value_w = self
+            .rows
+            .iter()
+            .filter(|r| !r.free)
+            .map(|r| r.value.chars().count())
+            .max()
+            .unwrap_or(0);
+        let unit_w = self
+            .rows
+            .iter()
+            .map(|r| r.unit.chars().count())
+            .max()
+            .unwrap_or(0);
+        for r in &self.rows {
+            if r.free {
+                eprintln!("{:<lw$}  {}", r.label, r.value, lw = label_w);
+                continue;
+            }
+            let line = format!(
+                "{:<lw$}  {:>vw$} {:<uw$} {}",
+                r.label,
+                r.value,
+                r.unit,
+                r.note,
+                lw = label_w,
+                vw = value_w,
+                uw = unit_w
+            );
+            eprintln!("{}", line.trim_end());
+        }
+    }
+}
+
+/// Per-symbol table for multi-symbol runs (latency + sequence integrity).
+pub fn print_symbol_table(per_symbol: &SymbolStats) {
+    let ms = |us: i64| us as f64 / 1000.0;
+    eprintln!("\n=== Per-Symbol Statistics ===");
+    eprintln!(
+        "{:<12} {:>9} {:>10} {:>10} {:>10} {:>10} {:>10} {:>8} {:>8}",
+        "Symbol", "Trades", "Avg (ms)", "P50 (ms)", "P95 (ms)", "P99 (ms)", "Max (ms)", "Missing", "OOO"
+    );
+    for (symbol, stats) in per_symbol.iter() {
+        let (gaps, out_of_order, _) = stats.integrity();
+        match stats.get() {
+            Some((count, avg, _, max, p50, p95, p99)) => eprintln!(
+                "{:<12} {:>9} {:>10.3} {:>10.3} {:>10.3} {:>10.3} {:>10.3} {:>8} {:>8}",
+                symbol,
+                count,
+                avg / 1000.0,
+                ms(p50),
+                ms(p95),
+                ms(p99),
+                ms(max),
+                gaps,
+                out_of_order
+            ),
+            None => eprintln!("{:<12} {:>9}", symbol, 0),
+        }
+    }
+}
+
+/// Prints the average latency split into stages, with each stage's share.
+/// Stages without data (`None`) are listed as unavailable.
+pub fn report_latency_budget(stages: &[(&str, Option<f64>)]) {
+    let total: f64 = stages.iter().filter_map(|(_, v)| *v).map(|v| v.max(0.0)).sum();
+    if total <= 0.0 {
+        return;
+    }
+    eprintln!("\n=== Latency Budget (avg per message) ===");
+    for (name, value) in stages {
+        match value {
+            Some(us) => eprintln!(
+                "{:<24} {:>10.1}µs {:>6.1}%",
+                name,
+                us,
+                us.max(0.0) * 100.0 / total
+            ),
+            None => eprintln!("{:<24} {:>12}", name, "n/a"),
+        }
+    }
+    eprintln!("{:<24} {:>10.1}µs", "Total", total);
+}
@@ -0,0 +1,1819 @@
+//! Uma coleta completa: o `Stream` do collector ligado a todos os destinos.
+//!
+//! `Session::start` imprime o banner, fixa a thread, calibra o relógio e lê a
+//! configuração de cada destino (CSV, estatísticas, métricas, publicação);
+//! `connect` espera a primeira conexão e liga o display e os sinks por
+//! intervalo. O binário dirige o loop: cada evento do collector e cada timer
+//! vai para o método correspondente, que diz se a coleta continua. `finish`
+//! imprime o relatório final e grava os arquivos.
+
+use std::ops::ControlFlow::{self, Break, Continue};
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use tokio::sync::watch;
+use tokio_tungstenite::tungstenite::{self, Message};
+
+use crate::clock::{calibrate_clock, ms_to_us, wall_clock_us, RecvClock};
+use crate::collector::{self, Backoff, Collector, CollectorConfig, ConnectReason, Event};
+use crate::config::{Exchange, RunConfig};
+use crate::display::{self, DisplayMode, Renderer};
+use crate::extract::{
+    extract_error_frame, extract_event_ts, extract_frame, extract_quoted_f64, extract_trade_frame_with_symbol,
+    extract_trade_full, FieldMap, Frame, FrameDetail, StreamKind,
+};
+use crate::output::{OutputEvent, TradeOutput};
+use crate::stats::{self, DepthSequence, LatencyStats, PercentileMethod, ProcessingStats, SampleMode, SymbolStats};
+use crate::{
+    anomaly, audit, control, cpu_affinity, cross_check, env_flag, env_parse, influx, metrics, publish, reconnect,
+    report, s3, statsd, summary, tcp_info, TradeRecord as Trade,
+};
+
+pub const DISPLAY_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_PROC_THRESHOLD_US: u64 = 100;
+const ALERT_LOG_INTERVAL: Duration = Duration::from_secs(1);
+const GAP_BURST_WINDOW: Duration = Duration::from_secs(1);
+const DEFAULT_GAP_BURST_MIN: u64 = 5;
+
+/// WebSocket frames received per `Message` variant.
+#[derive(Default)]
+struct FrameCounts {
+    text: u64,
+    binary: u64,
+    ping: u64,
+    pong: u64,
+    close: u64,
+    other: u64,
+    /// Binary frames / Text payloads that are not valid UTF-8.
+    invalid_utf8: u64,
+    /// Pongs sent in reply to server pings (and failed sends).
+    pong_sent: u64,
+    pong_failed: u64,
+}
+
+/// Hex dump of the first bytes of a frame, for diagnostics.
+fn hex_preview(bytes: &[u8]) -> String {
+    const MAX: usize = 32;
+    let mut out: Vec<String> = bytes.iter().take(MAX).map(|b| format!("{:02x}", b)).collect();
+    if bytes.len() > MAX {
+        out.push("...".to_string());
+    }
+    out.join(" ")
+}
+
+impl FrameCounts {
+    #[inline(always)]
+    fn count(&mut self, msg: &Message) {
+        match msg {
+            Message::Text(_) => self.text += 1,
+            Message::Binary(_) => self.binary += 1,
+            Message::Ping(_) => self.ping += 1,
+            Message::Pong(_) => self.pong += 1,
+            Message::Close(_) => self.close += 1,
+            Message::Frame(_) => self.other += 1,
+        }
+    }
+
+    fn report(&self) {
+        let total = self.text + self.binary + self.ping + self.pong + self.close + self.other;
+        if total == 0 {
+            return;
+        }
+        eprintln!("\n=== WebSocket Frames ===");
+        for (name, n) in [
+            ("Text", self.text),
+            ("Binary", self.binary),
+            ("Ping", self.ping),
+            ("Pong", self.pong),
+            ("Close", self.close),
+            ("Other", self.other),
+        ] {
+            if n > 0 {
+                eprintln!("{:<7} {:>10} ({:.2}%)", name, n, n as f64 * 100.0 / total as f64);
+            }
+        }
+        // A Binance só envia JSON em Text; Binary indica endpoint/proxy diferente
+        if self.binary > 0 {
+            eprintln!("Note: Binary frames received (unexpected for Binance JSON streams)");
+        }
+        if self.invalid_utf8 > 0 {
+            eprintln!("Invalid UTF-8 frames: {}", self.invalid_utf8);
+        }
+        if self.ping > 0 {
+            eprintln!("Pong replies:  {} sent, {} failed", self.pong_sent, self.pong_failed);
+        }
+    }
+}
+
+/// Sequence gaps concentrated in time (GAP_BURST_MIN within GAP_BURST_WINDOW):
+/// a gap isolado é ruído, vários seguidos indicam perda real na conexão.
+struct GapBurst {
+    min_gaps: u64,
+    window_start: Option<Instant>,
+    gaps_at_start: u64,
+    reported: bool,
+}
+
+impl GapBurst {
+    /// None when GAP_BURST_MIN=0.
+    fn from_env() -> Option<Self> {
+        let min_gaps = env_parse("GAP_BURST_MIN", DEFAULT_GAP_BURST_MIN);
+        (min_gaps > 0).then_some(Self {
+            min_gaps,
+            window_start: None,
+            gaps_at_start: 0,
+            reported: false,
+        })
+    }
+
+    /// `gaps_total`: running gap count. Returns the gaps in the current window
+    /// the first time it reaches the threshold (at most once per window).
+    #[inline(always)]
+    fn update(&mut self, gaps_total: u64, now: Instant) -> Option<u64> {
+        if self.window_start.is_none_or(|t| now.duration_since(t) >= GAP_BURST_WINDOW) {
+            self.window_start = Some(now);
+            self.gaps_at_start = gaps_total;
+            self.reported = false;
+        }
+        // saturating: o contador zera com o comando `reset` do control socket
+        let gaps = gaps_total.saturating_sub(self.gaps_at_start);
+        if self.reported || gaps < self.min_gaps {
+            return None;
+        }
+        self.reported = true;
+        Some(gaps)
+    }
+}
+
+/// Latency stats as configured by STATS_SAMPLES / STATS_MEMORY_KB,
+/// SAMPLE_MODE, PERCENTILE_METHOD, HIST_* and the sequence settings, with
+/// the banner lines.
+fn latency_stats_from_env(count: usize) -> (LatencyStats, SampleMode) {
+    // STATS_SAMPLES: tamanho da amostra de percentis (padrão: todos os trades)
+    // STATS_MEMORY_KB: alternativa, deriva o tamanho de um orçamento de memória
+    let stats_samples = match (
+        std::env::var("STATS_SAMPLES").ok().and_then(|v| v.parse::<usize>().ok()),
+        std::env::var("STATS_MEMORY_KB").ok().and_then(|v| v.parse::<usize>().ok()),
+    ) {
+        (Some(n), Some(_)) => {
+            eprintln!("  WARNING: STATS_SAMPLES and STATS_MEMORY_KB are mutually exclusive; using STATS_SAMPLES");
+            n
+        }
+        (Some(n), None) => n,
+        (None, Some(kb)) => stats::samples_for_memory_kb(kb),
+        (None, None) => count,
+    };
+    let sample_mode = SampleMode::from_env();
+    let mut stats = LatencyStats::new(stats_samples);
+    stats.set_sample_mode(sample_mode);
+    let percentile_method = PercentileMethod::from_env();
+    stats.set_percentile_method(percentile_method);
+    stats.set_histogram_spec(stats::HistogramSpec::from_env());
+    if let stats::HistogramSpec::Linear { min_us, max_us, bucket_us } = stats.histogram_spec() {
+        eprintln!(
+            "Histogram:  ({:.3}, {:.3}] ms in {}µs buckets",
+            min_us as f64 / 1000.0,
+            max_us as f64 / 1000.0,
+            bucket_us
+        );
+    }
+    stats.set_id_reset_threshold(env_parse("ID_RESET_THRESHOLD", stats::DEFAULT_ID_RESET_THRESHOLD));
+    stats.set_ooo_tolerance(env_parse("OOO_TOLERANCE", 0));
+    stats.set_gap_fill_window(env_parse("GAP_FILL_WINDOW", stats::DEFAULT_GAP_FILL_WINDOW));
+    stats.set_keep_negative(env_flag("KEEP_NEGATIVE_LATENCY"));
+    let warmup_trades: u64 = env_parse("WARMUP_TRADES", 0);
+    if warmup_trades > 0 {
+        stats.set_warmup(warmup_trades);
+        eprintln!("Warm-up:    first {} trades left out of the latency stats (ids still checked)", warmup_trades);
+    }
+    if stats.ooo_tolerance() > 0 {
+        eprintln!("OOO tol.:   {} ids (strict count reported alongside)", stats.ooo_tolerance());
+    }
+    if sample_mode == SampleMode::Histogram {
+        eprintln!(
+            "Samples:    whole run in a log histogram (~{} KB, <0.4% error, percentiles: {:?})",
+            stats::LOG_HISTOGRAM_BYTES / 1024,
+            percentile_method
+        );
+    } else {
+        eprintln!(
+            "Samples:    {} ({:?}, ~{} KB, percentiles: {:?})",
+            stats_samples,
+            sample_mode,
+            stats_samples * stats::SAMPLE_BYTES / 1024,
+            percentile_method
+        );
+    }
+    (stats, sample_mode)
+}
+
+/// A frame that parsed into an id and a timestamp.
+struct Parsed<'a> {
+    trade_id: u64,
+    trade_ts_ms: u64,
+    id_range: Option<(u64, u64)>,
+    /// Combined stream: the symbol of the trade.
+    symbol: Option<&'a [u8]>,
+}
+
+/// How the run ended, for the exit code.
+pub struct Outcome {
+    pub stats: LatencyStats,
+    /// The stream failed (not just ended, filled up or was interrupted).
+    pub stream_error: bool,
+}
+
+/// State of one collection run, from the banner to the final report.
+pub struct Session {
+    config: RunConfig,
+    field_map: Option<FieldMap>,
+    clock: RecvClock,
+    clock_sync: bool,
+    measured_offset_us: Option<i64>,
+    clock_offset_us: i64,
+    audit: audit::AuditLog,
+    s3_upload: Option<s3::S3Upload>,
+    output: TradeOutput,
+
+    stats: LatencyStats,
+    sample_mode: SampleMode,
+    symbol_stats: Option<SymbolStats>,
+    stats_every: usize,
+    log_first_n_raw: usize,
+    raw_logged: usize,
+    track_notional: bool,
+    total_notional: f64,
+    proc_stats: Option<ProcessingStats>,
+    alert_threshold_us: i64,
+    alerts: u64,
+    last_alert_log: Option<Instant>,
+    gap_burst: Option<GapBurst>,
+    last_regression_log: Option<Instant>,
+
+    snapshot_tx: Option<watch::Sender<metrics::StatsSnapshot>>,
+    publisher: Option<publish::Publisher>,
+    publish_symbol: String,
+    tcp_info: bool,
+    tcp_info_log: bool,
+    tcp_monitor: Option<tcp_info::TcpInfoMonitor>,
+
+    backoff: Option<Backoff>,
+    high_latency_policy: Option<reconnect::HighLatencyPolicy>,
+    reconnects: reconnect::ReconnectStats,
+    seamless: Option<reconnect::SeamlessRotation>,
+    /// Standby aberto (ou pedido ao collector e ainda não confirmado)
+    standby_open: bool,
+    /// Motivo da queda em andamento (AUTO_RECONNECT), para o log da reconexão
+    lost_reason: Option<String>,
+    /// p99 que disparou a reconexão forçada em andamento
+    forced_p99_us: Option<i64>,
+    rotate: Pin<Box<tokio::time::Sleep>>,
+    first_trade_seen: bool,
+
+    quiet: bool,
+    realtime: bool,
+    renderer: Renderer,
+    stats_csv: Option<display::StatsCsv>,
+    anomaly_dump: Option<anomaly::AnomalyDump>,
+    statsd_sink: Option<statsd::StatsdSink>,
+    influx_sink: Option<influx::InfluxSink>,
+    collection_start: Instant,
+    collection_start_us: i64,
+    timeseries: Option<display::TimeseriesCsv>,
+    realtime_window: Option<display::RealtimeWindow>,
+    symbol_windows: Vec<display::RealtimeWindow>,
+    reset_extremes_per_tick: bool,
+
+    /// Motivo de parada antecipada (stream fechado/erro), reportado no final
+    stop_reason: Option<String>,
+    stream_error: bool,
+    parse_failures: u64,
+    error_frames: u64,
+    frame_counts: FrameCounts,
+
+    // Depth parcial: snapshots sem sequência; mede só latência (se houver "E")
+    // e o intervalo entre chegadas
+    interarrival: LatencyStats,
+    last_snapshot_recv: Option<Instant>,
+    snapshots_without_ts: usize,
+    /// Depth diff: regra da Binance, U == u anterior + 1 a cada evento
+    depth_sequence: Option<DepthSequence>,
+    last_depth_break_log: Option<Instant>,
+    /// bookTicker: último bid/ask recebido
+    last_quote: Option<(f64, f64)>,
+    // !ticker@arr: mensagens recebidas, entradas por mensagem e dispersão de "E"
+    ticker_messages: u64,
+    ticker_entries: u64,
+    ticker_spread: LatencyStats,
+
+    // Latency budget: exchange (E−T) e rede+decode (recv−E), quando há "E"
+    budget_samples: u64,
+    budget_exchange_us: i64,
+    budget_network_us: i64,
+}
+
+impl Session {
+    /// Banner, thread placement, clock calibration and every sink that does
+    /// not need the connection. Ends with "Connecting to ...".
+    pub async fn start(config: RunConfig) -> Self {
+        eprintln!("=== Binance Latency Benchmark ===");
+        eprintln!("Label:      {}", config.label);
+        eprintln!("Machine ID: {}", config.machine_id);
+        if config.exchange != Exchange::Com {
+            eprintln!("Exchange:   {}", config.exchange.name());
+        }
+        let symbol_upper = config.symbol.to_uppercase();
+        let symbol_lower = config.symbol.to_lowercase();
+        let alias = display::symbol_display_name(&config.symbol);
+        if alias == symbol_upper {
+            eprintln!("Symbol:     {}", symbol_upper);
+        } else {
+            eprintln!("Symbol:     {} ({})", symbol_upper, alias);
+        }
+        eprintln!("Trades:     {}", config.count);
+        eprintln!("Output:     {}", config.output_file);
+        eprintln!("CPU cores:  {}", cpu_affinity::get_num_cores());
+        let writer_cpus = cpu_affinity::pin_collector_from_env();
+
+        // --- Clock reference (monotonic -> epoch without syscall, or PTP) ---
+        let clock = RecvClock::from_env();
+        eprintln!("Clock:      {}", clock.name());
+        eprintln!("WebSocket:  {}", collector::describe_ws_config(&collector::ws_config_from_env()));
+        let s3_upload = s3::S3Upload::from_env(&config.machine_id, &symbol_lower, &config.output_file);
+
+        // --- Audit log de ciclo de vida (AUDIT_LOG=arquivo, append-only) ---
+        let mut audit = audit::AuditLog::from_env();
+        audit.event(
+            "start",
+            &format!(
+                "label={:?} machine_id={:?} symbol={} target={} output={:?} clock={}",
+                config.label,
+                config.machine_id,
+                symbol_upper,
+                config.count,
+                config.output_file,
+                clock.name()
+            ),
+        );
+
+        // --- Clock Calibration ---
+        // Reduzido para 20 amostras (suficiente e rápido: ~1 segundo)
+        // CLOCK_SYNC=0: relógio já disciplinado (chrony/PTP), latência crua
+        let clock_sync = std::env::var("CLOCK_SYNC").as_deref() != Ok("0");
+        let measured_offset_us = if clock_sync {
+            calibrate_clock(20, &clock, config.exchange.rest_base()).await
+        } else {
+            eprintln!("Clock sync: disabled (CLOCK_SYNC=0), offset = 0");
+            None
+        };
+        let clock_offset_us = measured_offset_us.unwrap_or(0);
+
+        // --- CSV: no final (padrão) ou linha a linha (CSV_FLUSH_EVERY=1) ---
+        let output = TradeOutput::from_env(&config, clock_offset_us, writer_cpus);
+
+        let (stats, sample_mode) = latency_stats_from_env(config.count);
+
+        // --- Instrumentação opcional do tempo de processamento (PROC_TIMING=1) ---
+        let proc_stats = env_flag("PROC_TIMING").then(|| {
+            let threshold_us = env_parse("PROC_TIMING_THRESHOLD_US", DEFAULT_PROC_THRESHOLD_US);
+            eprintln!("Processing-time instrumentation ON (slow > {}µs)", threshold_us);
+            ProcessingStats::new(config.count, threshold_us)
+        });
+
+        // --- Servidor de métricas + dashboard opcional (METRICS_PORT) ---
+        let snapshot_tx = std::env::var("METRICS_PORT").ok().and_then(|p| p.parse().ok()).map(|port| {
+            let (tx, rx) = watch::channel(metrics::StatsSnapshot::default());
+            tokio::spawn(metrics::serve(port, config.machine_id.clone(), config.symbol.clone(), rx));
+            tx
+        });
+
+        // --- Republicação opcional dos trades (PUBLISH_URL=nats://... ou redis://...) ---
+        let publisher = match std::env::var("PUBLISH_URL") {
+            Ok(url) => match publish::Publisher::spawn(&url) {
+                Ok(p) => Some(p),
+                Err(e) => {
+                    eprintln!("  WARNING: {}. Publishing disabled", e);
+                    None
+                }
+            },
+            Err(_) => None,
+        };
+
+        let field_map = config.field_map();
+        eprintln!("Connecting to {}...", config.url);
+
+        // QUIET=1: sem display/checkpoints; só alertas, reconexões, gap bursts e o resumo final
+        let quiet = env_flag("QUIET");
+        let realtime = !quiet && std::env::var("REALTIME").map(|v| v != "0").unwrap_or(true);
+        let count = config.count;
+        let depth_sequence = (config.stream_kind == StreamKind::DepthDiff).then(DepthSequence::new);
+
+        Self {
+            field_map,
+            clock,
+            clock_sync,
+            measured_offset_us,
+            clock_offset_us,
+            audit,
+            s3_upload,
+            output,
+
+            stats,
+            sample_mode,
+            symbol_stats: None,
+            // --- Resumo a cada N trades (STATS_EVERY_TRADES, 0 = desligado) ---
+            // Independente do display por tempo: logs comparáveis entre máquinas
+            stats_every: env_parse("STATS_EVERY_TRADES", 0),
+            // --- Primeiras N mensagens cruas (LOG_FIRST_N_RAW): conferir o schema ---
+            log_first_n_raw: env_parse("LOG_FIRST_N_RAW", 0),
+            raw_logged: 0,
+            // --- Notional (preço × quantidade) opcional (TRACK_NOTIONAL=1) ---
+            track_notional: env_flag("TRACK_NOTIONAL"),
+            total_notional: 0.0,
+            proc_stats,
+            // --- Alerta de latência (ALERT_LATENCY_MS, 0 = desligado) ---
+            alert_threshold_us: (env_parse("ALERT_LATENCY_MS", 0.0f64) * 1000.0) as i64,
+            alerts: 0,
+            last_alert_log: None,
+            // Rajada de gaps (GAP_BURST_MIN por segundo, 0 = desligado)
+            gap_burst: GapBurst::from_env(),
+            // recv_ts voltando no tempo (step do relógio), log limitado como os alertas
+            last_regression_log: None,
+
+            snapshot_tx,
+            publisher,
+            publish_symbol: symbol_upper,
+            // --- TCP_INFO do kernel (TCP_INFO=1, só Linux) ---
+            tcp_info: env_flag("TCP_INFO"),
+            tcp_info_log: env_flag("TCP_INFO_LOG"),
+            tcp_monitor: None,
+
+            // --- Reconexão automática com backoff quando o stream cai (AUTO_RECONNECT=0 desliga) ---
+            backoff: Backoff::from_env(),
+            high_latency_policy: None,
+            reconnects: reconnect::ReconnectStats::default(),
+            seamless: None,
+            standby_open: false,
+            lost_reason: None,
+            forced_p99_us: None,
+            rotate: Box::pin(tokio::time::sleep(Duration::ZERO)),
+            first_trade_seen: false,
+
+            quiet,
+            realtime,
+            renderer: Renderer::new(DisplayMode::from_env()),
+            stats_csv: None,
+            anomaly_dump: None,
+            statsd_sink: None,
+            influx_sink: None,
+            collection_start: Instant::now(),
+            collection_start_us: wall_clock_us(),
+            timeseries: None,
+            realtime_window: None,
+            symbol_windows: Vec::new(),
+            // RESET_EXTREMES_PER_TICK=1: min/max passam a ser do último intervalo
+            reset_extremes_per_tick: env_flag("RESET_EXTREMES_PER_TICK"),
+
+            stop_reason: None,
+            stream_error: false,
+            parse_failures: 0,
+            error_frames: 0,
+            frame_counts: FrameCounts::default(),
+
+            interarrival: LatencyStats::new(count),
+            last_snapshot_recv: None,
+            snapshots_without_ts: 0,
+            depth_sequence,
+            last_depth_break_log: None,
+            last_quote: None,
+            ticker_messages: 0,
+            ticker_entries: 0,
+            ticker_spread: LatencyStats::new(count),
+
+            budget_samples: 0,
+            budget_exchange_us: 0,
+            budget_network_us: 0,
+            config,
+        }
+    }
+
+    /// What `run_collector` needs for this run (URL, SUBSCRIBE, `WS_*`, backoff).
+    pub fn collector_config(&self) -> CollectorConfig {
+        let mut collector_config = CollectorConfig::new(&self.config.symbol);
+        collector_config.ws_url = Some(self.config.url.clone());
+        collector_config.subscribe = self.config.subscribe.clone();
+        collector_config.ws_config = Some(collector::ws_config_from_env());
+        collector_config.reconnect = self.backoff.clone();
+        collector_config
+    }
+
+    /// Waits for the first connection, then sets up the reconnect policies,
+    /// the display and the per-interval sinks. Err: the connection failed.
+    pub async fn connect(&mut self, collector: &mut Collector) -> Result<(), String> {
+        if let Some(stream) = &self.config.subscribe {
+            eprintln!("Subscribing to {}...", stream);
+        }
+        match collector.next().await {
+            Some(Event::Connected { socket, .. }) => {
+                if self.config.subscribe.is_some() {
+                    eprintln!("Subscription confirmed");
+                }
+                if self.tcp_info {
+                    self.tcp_monitor = tcp_info::TcpInfoMonitor::new(socket);
+                    if self.tcp_monitor.is_none() {
+                        eprintln!("  WARNING: TCP_INFO unavailable on this platform/socket");
+                    }
+                }
+            }
+            Some(Event::ConnectFailed(e)) => return Err(e),
+            other => return Err(format!("unexpected collector event before connecting: {:?}", other)),
+        }
+
+        // --- Reconexão forçada por latência alta sustentada (RECONNECT_ON_HIGH_LATENCY_MS) ---
+        self.high_latency_policy = reconnect::HighLatencyPolicy::from_env();
+
+        // --- Rotação make-before-break antes do corte de 24h (SEAMLESS_RECONNECT=1) ---
+        self.seamless = reconnect::SeamlessRotation::from_env();
+        if self.config.multi_symbol && self.seamless.is_some() {
+            // Dedup por trade_id não distingue símbolos
+            eprintln!("  WARNING: SEAMLESS_RECONNECT is not supported with multiple symbols; disabled");
+            self.seamless = None;
+        }
+        let rotate_after = self.seamless.as_ref().map_or(Duration::ZERO, |s| s.rotate_after());
+        self.rotate.as_mut().reset(tokio::time::Instant::now() + rotate_after);
+
+        eprintln!("Connected! Collecting {} trades...", self.config.count);
+        self.audit.event(
+            "connect",
+            &format!("url={} stream={}", self.config.url, self.config.stream_name),
+        );
+        // Fronteira de conexão: o primeiro trade só define a base de sequência
+        self.stats.mark_resync();
+        self.symbol_stats = self
+            .config
+            .multi_symbol
+            .then(|| SymbolStats::new(&self.config.symbols, &self.stats));
+
+        let (machine_id, symbol) = (&self.config.machine_id, self.config.symbol.to_lowercase());
+        // STATS_CSV: série temporal por intervalo do display (inclusive intervalos sem trades)
+        self.stats_csv = std::env::var("STATS_CSV").ok().and_then(|path| {
+            match display::StatsCsv::create(&path, &self.stats) {
+                Ok(csv) => {
+                    eprintln!("Stats CSV:  {} (every {:?})", path, DISPLAY_INTERVAL);
+                    Some(csv)
+                }
+                Err(e) => {
+                    eprintln!("  WARNING: cannot open STATS_CSV {} ({}); disabled", path, e);
+                    None
+                }
+            }
+        });
+        // ANOMALY_DUMP: ring buffer dos últimos trades, gravado em gap/ooo/dup/latência
+        self.anomaly_dump = anomaly::AnomalyDump::from_env(machine_id, &symbol);
+        // STATSD_ADDR: agregado do intervalo por UDP (DogStatsD), um pacote por tick
+        self.statsd_sink = statsd::StatsdSink::from_env(machine_id, &symbol);
+        // INFLUX_URL: um ponto por tick (line protocol) enviado por uma task separada
+        self.influx_sink = influx::InfluxSink::from_env(machine_id, &symbol, &self.stats);
+        // Elapsed/throughput ancorados em Instant (monotônico): um step de NTP ou
+        // leap second no relógio de parede não afeta nem derruba o display
+        self.collection_start = Instant::now();
+        self.collection_start_us = wall_clock_us();
+        // TIMESERIES_FILE: uma linha por segundo (pelo horário de recebimento)
+        self.timeseries = std::env::var("TIMESERIES_FILE").ok().and_then(|path| {
+            match display::TimeseriesCsv::create(&path, &self.stats, self.collection_start_us) {
+                Ok(csv) => {
+                    eprintln!("Timeseries: {} (every 1s)", path);
+                    Some(csv)
+                }
+                Err(e) => {
+                    eprintln!("  WARNING: cannot open TIMESERIES_FILE {} ({}); disabled", path, e);
+                    None
+                }
+            }
+        });
+        // REALTIME_WINDOW_SECS: display dos últimos N segundos (relatório continua acumulado)
+        let realtime_window_secs: f64 = env_parse("REALTIME_WINDOW_SECS", 0.0);
+        self.realtime_window = (self.realtime && realtime_window_secs > 0.0).then(|| {
+            let window = display::RealtimeWindow::new(Duration::from_secs_f64(realtime_window_secs));
+            eprintln!("Display:    {} (final report is cumulative)", window.label());
+            window
+        });
+        if let (Some(_), Some(per_symbol)) = (&self.realtime_window, &self.symbol_stats) {
+            self.symbol_windows = per_symbol
+                .iter()
+                .map(|_| display::RealtimeWindow::new(Duration::from_secs_f64(realtime_window_secs)))
+                .collect();
+        }
+        Ok(())
+    }
+
+    /// SEAMLESS_RECONNECT: time to open the standby connection.
+    pub fn wants_standby(&self) -> bool {
+        self.seamless.is_some() && !self.standby_open
+    }
+
+    /// Completes when the connection is due for rotation (see `wants_standby`).
+    pub async fn standby_due(&mut self) {
+        self.rotate.as_mut().await
+    }
+
+    /// Display, metrics or a per-interval sink is on.
+    pub fn wants_display_tick(&self) -> bool {
+        self.realtime
+            || self.snapshot_tx.is_some()
+            || self.stats_csv.is_some()
+            || self.timeseries.is_some()
+            || self.statsd_sink.is_some()
+            || self.influx_sink.is_some()
+    }
+
+    /// TCP_INFO is being sampled.
+    pub fn wants_tcp_tick(&self) -> bool {
+        self.tcp_monitor.is_some()
+    }
+
+    /// RECONNECT_ON_HIGH_LATENCY_MS is on and no forced reconnect is pending.
+    pub fn wants_health_tick(&self) -> bool {
+        self.high_latency_policy.is_some() && self.forced_p99_us.is_none()
+    }
+
+    pub fn open_standby(&mut self, collector: &Collector) -> ControlFlow<()> {
+        self.renderer.finish();
+        eprintln!("\n🔁 Seamless reconnect: opening standby connection...");
+        self.standby_open = true;
+        collector.open_standby();
+        Continue(())
+    }
+
+    /// One collector event; Break ends the collection.
+    pub fn on_event(&mut self, event: Option<Event>, collector: &Collector) -> ControlFlow<()> {
+        match event {
+            Some(Event::Message { msg, recv_instant, standby }) => {
+                return self.on_frame(Ok(msg), recv_instant, standby, collector)
+            }
+            Some(Event::Error(e)) => return self.on_frame(Err(e), Instant::now(), false, collector),
+            Some(Event::Pong(result)) => {
+                // O write nunca é lido: o collector responde o ping explicitamente
+                // na conexão que o recebeu; aqui só se conta o resultado
+                match result {
+                    Ok(()) => self.frame_counts.pong_sent += 1,
+                    Err(e) => {
+                        self.frame_counts.pong_failed += 1;
+                        self.renderer.finish();
+                        eprintln!("\n  WARNING: failed to send pong ({})", e);
+                    }
+                }
+            }
+            Some(Event::Connected { reason, socket }) => {
+                if self.tcp_info {
+                    match self.tcp_monitor.as_mut() {
+                        Some(monitor) => monitor.attach(socket),
+                        None => self.tcp_monitor = tcp_info::TcpInfoMonitor::new(socket),
+                    }
+                }
+                if self.config.subscribe.is_some() {
+                    eprintln!("Subscription confirmed");
+                }
+                match reason {
+                    ConnectReason::Initial => {}
+                    ConnectReason::Reconnect { attempt } => {
+                        // Stats preservadas; só a sequência de ids recomeça
+                        self.mark_resync();
+                        self.reconnects.reconnected();
+                        let reason = self.lost_reason.take().unwrap_or_default();
+                        eprintln!(
+                            "✅ Reconnected (attempt {}, {} reconnects so far)",
+                            attempt,
+                            self.reconnects.count()
+                        );
+                        self.audit.event(
+                            "reconnect",
+                            &format!("reason={:?} attempt={} trades={}", reason, attempt, self.output.rows()),
+                        );
+                    }
+                    ConnectReason::Forced => {
+                        self.mark_resync();
+                        self.reconnects.reconnected();
+                        self.audit.event(
+                            "reconnect",
+                            &format!(
+                                "reason=\"high-latency\" p99_us={} trades={}",
+                                self.forced_p99_us.take().unwrap_or_default(),
+                                self.output.rows()
+                            ),
+                        );
+                    }
+                    ConnectReason::Standby => {
+                        if let Some(rotation) = self.seamless.as_mut() {
+                            rotation.standby_opened();
+                        }
+                    }
+                }
+            }
+            Some(Event::ForcedReconnectFailed(e)) => {
+                // A conexão antiga seguiu aberta: não houve queda
+                self.forced_p99_us = None;
+                self.reconnects.restored(Instant::now());
+                eprintln!("  WARNING: forced reconnect failed ({}); keeping current connection", e);
+            }
+            Some(Event::StandbyFailed(e)) => {
+                self.standby_open = false;
+                eprintln!(
+                    "  WARNING: standby connection failed ({}); retrying in {:?}",
+                    e,
+                    reconnect::SEAMLESS_RETRY
+                );
+                self.rotate.as_mut().reset(tokio::time::Instant::now() + reconnect::SEAMLESS_RETRY);
+            }
+            Some(Event::StandbyLost(reason)) => {
+                self.standby_open = false;
+                self.renderer.finish();
+                eprintln!(
+                    "\n  WARNING: standby connection {} before taking over; retrying in {:?}",
+                    reason,
+                    reconnect::SEAMLESS_RETRY
+                );
+                if let Some(rotation) = self.seamless.as_mut() {
+                    rotation.standby_dropped();
+                }
+                self.rotate.as_mut().reset(tokio::time::Instant::now() + reconnect::SEAMLESS_RETRY);
+            }
+            // Standby assumiu: a antiga só foi fechada agora, com a nova já entregando
+            Some(Event::Promoted(reason)) => {
+                self.standby_open = false;
+                self.output.checkpoint();
+                if let Some(rotation) = self.seamless.as_mut() {
+                    rotation.promoted();
+                    self.rotate
+                        .as_mut()
+                        .reset(tokio::time::Instant::now() + rotation.rotate_after());
+                }
+                self.reconnects.reconnected();
+                self.renderer.finish();
+                eprintln!("\n🔁 Seamless reconnect: standby took over ({})", reason);
+                self.audit.event(
+                    "reconnect",
+                    &format!("reason=\"seamless\" detail={:?} trades={}", reason, self.output.rows()),
+                );
+            }
+            Some(Event::Disconnected) => {
+                self.renderer.finish();
+                self.reconnects.disconnected(Instant::now());
+                self.output.checkpoint();
+                let reason = self.stop_reason.take().unwrap_or_else(|| "stream ended".to_string());
+                eprintln!("\n🔄 Connection lost ({}); reconnecting...", reason);
+                self.lost_reason = Some(reason);
+            }
+            Some(Event::Reconnecting { attempt, delay }) => {
+                eprintln!("🔄 Reconnect attempt {} in {:?}...", attempt, delay);
+            }
+            Some(Event::ReconnectFailed { attempt, error }) => {
+                eprintln!("  attempt {} failed: {}", attempt, error);
+            }
+            Some(Event::GaveUp { attempts }) => {
+                let reason = self.lost_reason.take().unwrap_or_default();
+                self.stop_reason = Some(format!("{}; gave up after {} reconnect attempts", reason, attempts));
+                self.stream_error = true;
+                return Break(());
+            }
+            Some(Event::ConnectFailed(e)) => {
+                self.stop_reason = Some(e);
+                self.stream_error = true;
+                return Break(());
+            }
+            None => {
+                self.stop_reason.get_or_insert_with(|| "stream ended".to_string());
+                return Break(());
+            }
+        }
+        Continue(())
+    }
+
+    /// New connection: stats are kept, only the id sequence starts over.
+    fn mark_resync(&mut self) {
+        self.stats.mark_resync();
+        if let Some(per_symbol) = self.symbol_stats.as_mut() {
+            per_symbol.mark_resync();
+        }
+    }
+
+    pub fn on_display_tick(&mut self) -> ControlFlow<()> {
+        let elapsed = self.collection_start.elapsed();
+        if let Some(csv) = self.stats_csv.as_mut() {
+            if let Err(e) = csv.append(wall_clock_us(), elapsed, &self.stats, self.output.dropped()) {
+                self.renderer.finish();
+                eprintln!("\n  WARNING: STATS_CSV write failed ({}); disabled", e);
+                self.stats_csv = None;
+            }
+        }
+        if let Some(csv) = self.timeseries.as_mut() {
+            if let Err(e) = csv.tick(wall_clock_us()) {
+                self.renderer.finish();
+                eprintln!("\n  WARNING: TIMESERIES_FILE write failed ({}); disabled", e);
+                self.timeseries = None;
+            }
+        }
+        if self.realtime {
+            if let Some(window) = self.realtime_window.as_mut() {
+                window.tick(&mut self.stats, elapsed);
+                for ((_, sym_stats), window) in self
+                    .symbol_stats
+                    .iter_mut()
+                    .flat_map(|s| s.iter_mut())
+                    .zip(&mut self.symbol_windows)
+                {
+                    window.tick(sym_stats, elapsed);
+                }
+            }
+            let notional = self.track_notional.then_some(self.total_notional);
+            let mut lines = display::stats_lines(
+                &self.config.display_name,
+                &self.stats,
+                elapsed,
+                notional,
+                self.realtime_window.as_ref(),
+            );
+            // Uma linha por símbolo
+            for (i, (sym, sym_stats)) in self.symbol_stats.iter().flat_map(|s| s.iter()).enumerate() {
+                lines.extend(display::stats_lines(
+                    &format!("  {}", sym),
+                    sym_stats,
+                    elapsed,
+                    None,
+                    self.symbol_windows.get(i),
+                ));
+            }
+            self.renderer.draw(&lines);
+        }
+        if let Some(sink) = self.statsd_sink.as_mut() {
+            sink.emit(&self.stats);
+        }
+        if let Some(sink) = self.influx_sink.as_mut() {
+            sink.emit(wall_clock_us(), elapsed, &self.stats);
+        }
+        if let Some(tx) = &self.snapshot_tx {
+            let _ = tx.send(metrics::StatsSnapshot::from_stats(
+                &self.stats,
+                &self.reconnects,
+                elapsed.as_secs_f64(),
+            ));
+        }
+        if self.reset_extremes_per_tick {
+            self.stats.reset_extremes();
+            if let Some(per_symbol) = self.symbol_stats.as_mut() {
+                per_symbol.reset_extremes();
+            }
+        }
+        Continue(())
+    }
+
+    pub fn on_tcp_tick(&mut self) -> ControlFlow<()> {
+        if let Some((sample, new_retrans)) = self.tcp_monitor.as_mut().and_then(|m| m.sample()) {
+            if self.tcp_info_log {
+                eprintln!("{}", tcp_info::TcpInfoMonitor::interval_line(&sample, new_retrans));
+                self.renderer.finish();
+            }
+        }
+        Continue(())
+    }
+
+    /// RECONNECT_ON_HIGH_LATENCY_MS: forces a reconnect once the p99 stayed
+    /// above the limit for RECONNECT_SUSTAIN_S.
+    pub fn on_health_tick(&mut self, collector: &Collector) -> ControlFlow<()> {
+        let Some(policy) = self.high_latency_policy.as_mut() else {
+            return Continue(());
+        };
+        let Some(p99_us) = policy.check(Instant::now()) else {
+            return Continue(());
+        };
+        eprintln!(
+            "\n🔄 Forced reconnect: p99 {:.2}ms > {:.2}ms for {:?}",
+            p99_us as f64 / 1000.0,
+            policy.threshold_us() as f64 / 1000.0,
+            policy.sustain()
+        );
+        self.renderer.finish();
+        self.reconnects.disconnected(Instant::now());
+        self.output.checkpoint();
+        // Make-before-break no collector: a conexão antiga só é fechada se a nova subir
+        self.forced_p99_us = Some(p99_us);
+        collector.reconnect();
+        Continue(())
+    }
+
+    /// Ctrl+C: the run ends the same way as a normal one (report + CSV).
+    pub fn interrupt(&mut self) -> ControlFlow<()> {
+        let trades = self.output.rows();
+        self.renderer.finish();
+        eprintln!("\n🛑 Interrupted (SIGINT); finishing with {} trades...", trades);
+        self.audit.event("interrupt", &format!("trades={}", trades));
+        self.stop_reason = Some("interrupted (SIGINT)".to_string());
+        Break(())
+    }
+
+    /// Applies a control socket command between messages.
+    pub fn on_control(&mut self, req: control::Request) -> ControlFlow<()> {
+        let response = match req.command {
+            control::Command::Snapshot => control::snapshot_text(&self.stats, self.alert_threshold_us, self.alerts),
+            control::Command::Reset => {
+                self.stats.reset();
+                if let Some(per_symbol) = self.symbol_stats.as_mut() {
+                    per_symbol.reset();
+                }
+                self.alerts = 0;
+                "ok: stats reset".to_string()
+            }
+            control::Command::SetAlert(ms) => {
+                self.alert_threshold_us = (ms * 1000.0) as i64;
+                format!("ok: alert threshold {}ms", ms)
+            }
+            control::Command::SetSamples(n) => {
+                self.stats.set_max_samples(n);
+                if let Some(per_symbol) = self.symbol_stats.as_mut() {
+                    per_symbol.set_max_samples(n);
+                }
+                format!("ok: sample window {}", n)
+            }
+        };
+        let _ = req.reply.send(response);
+        Continue(())
+    }
+
+    /// One frame (or read error). `recv_instant`: taken by the collector
+    /// right after the read.
+    fn on_frame(
+        &mut self,
+        msg: Result<Message, tungstenite::Error>,
+        recv_instant: Instant,
+        from_standby: bool,
+        collector: &Collector,
+    ) -> ControlFlow<()> {
+        match &msg {
+            Ok(m) => self.frame_counts.count(m),
+            // Text com UTF-8 inválido: o tungstenite não entrega o payload e
+            // o tokio-tungstenite encerra o stream depois de qualquer erro
+            Err(tungstenite::Error::Utf8) => {
+                self.frame_counts.invalid_utf8 += 1;
+                self.renderer.finish();
+                eprintln!("\n  WARNING: Text frame with invalid UTF-8 (payload not available); the stream ends here");
+                self.stop_reason = Some("invalid UTF-8 in Text frame".to_string());
+            }
+            Err(_) => {}
+        }
+        if self.reconnects.in_outage() {
+            self.reconnects.restored(recv_instant);
+        }
+
+        let data = match &msg {
+            Ok(Message::Text(text)) => {
+                // Depois do timestamp: o log não entra na latência medida
+                if self.raw_logged < self.log_first_n_raw {
+                    self.raw_logged += 1;
+                    self.renderer.finish();
+                    eprintln!("RAW #{}: {}", self.raw_logged, text);
+                }
+                text.as_bytes()
+            }
+            Ok(Message::Binary(bin)) => {
+                // Proxy/compressão no caminho: primeiro frame em hex para diagnóstico
+                if std::str::from_utf8(bin).is_err() {
+                    self.frame_counts.invalid_utf8 += 1;
+                    if self.frame_counts.invalid_utf8 == 1 {
+                        self.renderer.finish();
+                        eprintln!(
+                            "\n  WARNING: non-UTF-8 Binary frame ({} bytes), skipping: {}",
+                            bin.len(),
+                            hex_preview(bin)
+                        );
+                    }
+                    return Continue(());
+                }
+                if self.frame_counts.binary == 1 {
+                    self.renderer.finish();
+                    eprintln!("\n  WARNING: Binary frame received ({} bytes): {}", bin.len(), hex_preview(bin));
+                }
+                bin.as_slice()
+            }
+            Ok(Message::Close(frame)) => {
+                let reason = match frame {
+                    Some(f) => format!("server closed connection ({}: {})", f.code, f.reason),
+                    None => "server closed connection".to_string(),
+                };
+                eprintln!("\n{}", reason);
+                self.renderer.finish();
+                self.audit.event(
+                    "disconnect",
+                    &format!("reason={:?} trades={}", reason, self.output.rows()),
+                );
+                self.stop_reason = Some(reason);
+                return Continue(());
+            }
+            // Pong já enviado pelo collector (Event::Pong); Pong não
+            // solicitado (keepalive unidirecional): só contado
+            Ok(_) => return Continue(()),
+            // Já contado e logado acima; o próximo read devolve None
+            Err(tungstenite::Error::Utf8) => return Continue(()),
+            Err(e) => {
+                eprintln!("\n❌ WebSocket error: {}", e);
+                self.renderer.finish();
+                self.audit.event(
+                    "disconnect",
+                    &format!("reason={:?} trades={}", e.to_string(), self.output.rows()),
+                );
+                self.stop_reason = Some(format!("WebSocket error: {}", e));
+                // AUTO_RECONNECT: o collector reconecta em seguida
+                if self.backoff.is_some() {
+                    return Continue(());
+                }
+                self.stream_error = true;
+                return Break(());
+            }
+        };
+
+        if let Some(parsed) = self.parse(data, recv_instant) {
+            return self.on_trade(data, parsed, recv_instant, from_standby, collector);
+        }
+        if let Some((code, msg)) = extract_error_frame(data) {
+            // Erro da API (símbolo inválido, limite...): não é falha de parse
+            self.error_frames += 1;
+            eprintln!("\n❌ Binance error frame: code={} msg={}", code, String::from_utf8_lossy(msg));
+            self.renderer.finish();
+        } else {
+            self.parse_failures += 1;
+        }
+        Continue(())
+    }
+
+    /// Zero-alloc parse, pelo StreamKind do stream assinado
+    /// aggTrade: trade_id = id do agregado ("a"), integridade por faixa f/l
+    /// depth diff: trade_id = "u", integridade por faixa U/u, timestamp "E"
+    fn parse<'a>(&mut self, data: &'a [u8], recv_instant: Instant) -> Option<Parsed<'a>> {
+        let stream_kind = self.config.stream_kind;
+        let frame = match (stream_kind, &self.field_map) {
+            (StreamKind::Trade, _) if self.symbol_stats.is_some() => extract_trade_frame_with_symbol(data),
+            (StreamKind::Trade, Some(map)) => map.extract(data).map(|(id, ts_ms)| Frame {
+                id,
+                ts_ms,
+                id_range: None,
+                detail: FrameDetail::None,
+            }),
+            _ => extract_frame(stream_kind, data),
+        }?;
+        let mut trade_id = frame.id;
+        let mut symbol = None;
+        match frame.detail {
+            FrameDetail::None => {}
+            FrameDetail::Symbol(sym) => symbol = Some(sym),
+            // Array do mercado inteiro: latência pelo "E" mais novo; id = nº da mensagem
+            FrameDetail::Ticker { oldest_ms, entries } => {
+                self.ticker_messages += 1;
+                self.ticker_entries += entries;
+                self.ticker_spread
+                    .record(ms_to_us(frame.ts_ms.saturating_sub(oldest_ms)).unwrap_or(i64::MAX));
+                trade_id = self.ticker_messages;
+            }
+            FrameDetail::Quote { bid, ask } => self.last_quote = Some((bid, ask)),
+        }
+        // Snapshots e bookTicker: intervalo entre chegadas
+        if matches!(
+            stream_kind,
+            StreamKind::TickerArray | StreamKind::DepthPartial | StreamKind::BookTicker
+        ) {
+            if let Some(prev) = self.last_snapshot_recv {
+                let gap_us = recv_instant.duration_since(prev).as_micros() as i64;
+                // bookTicker spot sem timestamp: a sequência de "u" vai junto do intervalo
+                if stream_kind == StreamKind::BookTicker && frame.ts_ms == 0 {
+                    self.interarrival.update(trade_id, gap_us);
+                } else {
+                    self.interarrival.record(gap_us);
+                }
+            }
+            self.last_snapshot_recv = Some(recv_instant);
+        }
+        Some(Parsed {
+            trade_id,
+            trade_ts_ms: frame.ts_ms,
+            id_range: frame.id_range,
+            symbol,
+        })
+    }
+
+    /// A parsed frame: latency, integrity, the sinks and the alerts.
+    fn on_trade(
+        &mut self,
+        data: &[u8],
+        parsed: Parsed,
+        recv_instant: Instant,
+        from_standby: bool,
+        collector: &Collector,
+    ) -> ControlFlow<()> {
+        let Parsed {
+            trade_id,
+            trade_ts_ms,
+            id_range,
+            symbol: trade_symbol,
+        } = parsed;
+        let stream_kind = self.config.stream_kind;
+        if matches!(stream_kind, StreamKind::DepthPartial | StreamKind::BookTicker) && trade_ts_ms == 0 {
+            // Sem timestamp do servidor: só o intervalo entre chegadas
+            self.snapshots_without_ts += 1;
+            if self.snapshots_without_ts >= self.config.count {
+                return Break(());
+            }
+            return Continue(());
+        }
+
+        // Validação básica: ignora trades inválidos
+        if trade_id == 0 || trade_ts_ms == 0 {
+            return Continue(());
+        }
+        // "T" absurdo (não cabe em µs num i64): conta como falha de parse
+        let Some(trade_ts_us) = ms_to_us(trade_ts_ms) else {
+            self.parse_failures += 1;
+            return Continue(());
+        };
+
+        // Duas conexões sobrepostas: cada trade_id entra uma vez só
+        if let Some(rotation) = self.seamless.as_mut() {
+            let duplicate = !rotation.accept(trade_id);
+            if rotation.should_promote(duplicate, from_standby, recv_instant) {
+                collector.promote(if duplicate { "streams overlap" } else { "overlap limit reached" });
+            }
+            if duplicate {
+                return Continue(());
+            }
+        }
+
+        let recv_ts_us = self.clock.to_epoch_us(recv_instant);
+        if let Some(back_us) = self.stats.check_recv_ts(recv_ts_us) {
+            if self
+                .last_regression_log
+                .is_none_or(|t| recv_instant.duration_since(t) >= ALERT_LOG_INTERVAL)
+            {
+                self.renderer.finish();
+                eprintln!(
+                    "\n⚠️  Clock regression: recv_ts went back {:.3}ms (trade {}); latencies around it are unreliable",
+                    back_us as f64 / 1000.0,
+                    trade_id
+                );
+                self.audit
+                    .event("clock_regression", &format!("back_us={} trade_id={}", back_us, trade_id));
+                self.last_regression_log = Some(recv_instant);
+            }
+        }
+        let latency_us = recv_ts_us as i64 - trade_ts_us - self.clock_offset_us;
+        let trade_ts_us = trade_ts_us as u64;
+
+        if !self.first_trade_seen {
+            self.first_trade_seen = true;
+            self.audit.event(
+                "first-trade",
+                &format!("trade_id={} latency_us={}", trade_id, latency_us),
+            );
+        }
+
+        // Depth diff usa o próprio "E" como timestamp: não há etapa E−T
+        let mut event_latency_us = None;
+        if matches!(stream_kind, StreamKind::Trade | StreamKind::AggTrade | StreamKind::BookTicker) {
+            if let Some(event_ts_us) = extract_event_ts(data).and_then(ms_to_us) {
+                let network_us = recv_ts_us as i64 - event_ts_us - self.clock_offset_us;
+                self.budget_samples += 1;
+                self.budget_exchange_us += event_ts_us - trade_ts_us as i64;
+                self.budget_network_us += network_us;
+                event_latency_us = Some(network_us);
+            }
+        }
+
+        let trade = Trade {
+            trade_id,
+            trade_ts_us,
+            recv_ts_us,
+            latency_us,
+            event_latency_us,
+        };
+        match self.output.push(trade) {
+            // Teto do CSV: para antes da linha que passaria do limite
+            Err(reason) => {
+                self.stop_reason = Some(reason);
+                return Break(());
+            }
+            Ok(true) => {
+                let queue_rows = self.output.queue_rows();
+                self.renderer.finish();
+                eprintln!(
+                    "\n  WARNING: CSV writer queue full ({} rows); rows are being dropped from the CSV (stats unaffected)",
+                    queue_rows
+                );
+                self.audit
+                    .event("csv_drop", &format!("trade_id={} queue_rows={}", trade_id, queue_rows));
+            }
+            Ok(false) => {}
+        }
+        match self.output.poll() {
+            None => {}
+            Some(OutputEvent::Rotated { part, rows }) => {
+                self.renderer.finish();
+                eprintln!("\n🗂  CSV rotated: {}", part);
+                self.audit.event("csv_rotate", &format!("part={:?} trades={}", part, rows));
+            }
+            Some(OutputEvent::Failed(e)) => {
+                eprintln!("\n  WARNING: CSV write failed ({}). Remaining rows will be written at the end", e);
+                self.renderer.finish();
+            }
+        }
+        let resync_pending = self.stats.resync_pending();
+        if let (Some(sequence), Some((first_id, last_id))) = (self.depth_sequence.as_mut(), id_range) {
+            if let Some(expected) = sequence.check(first_id, last_id, resync_pending) {
+                if self
+                    .last_depth_break_log
+                    .is_none_or(|t| recv_instant.duration_since(t) >= ALERT_LOG_INTERVAL)
+                {
+                    self.renderer.finish();
+                    eprintln!(
+                        "\n⚠️  Depth sequence break: U={} but expected {} (local book would need a resync)",
+                        first_id, expected
+                    );
+                    self.audit.event(
+                        "depth_break",
+                        &format!("expected_U={} U={} u={}", expected, first_id, last_id),
+                    );
+                    self.last_depth_break_log = Some(recv_instant);
+                }
+            }
+        }
+        if let Some(policy) = self.high_latency_policy.as_mut() {
+            policy.record(latency_us);
+        }
+        // Contadores antes do update: a diferença diz o que este trade causou
+        let integrity_before = self
+            .anomaly_dump
+            .as_ref()
+            .map(|_| (self.stats.integrity(), self.stats.out_of_order_strict()));
+        match (stream_kind, id_range) {
+            (StreamKind::AggTrade, Some((first_id, last_id))) => {
+                self.stats.update_agg(trade_id, first_id, last_id, latency_us)
+            }
+            (_, Some((first_id, last_id))) => self.stats.update_range(first_id, last_id, latency_us),
+            // Snapshots não têm sequência: só latência
+            (StreamKind::DepthPartial | StreamKind::TickerArray, None) => self.stats.record(latency_us),
+            // Vários símbolos: sequência por símbolo; o agregado só soma latência
+            (_, None) => match (self.symbol_stats.as_mut(), trade_symbol) {
+                (Some(per_symbol), Some(sym)) => {
+                    per_symbol.update(sym, trade_id, latency_us);
+                    self.stats.record(latency_us);
+                }
+                _ => self.stats.update(trade_id, latency_us),
+            },
+        }
+        if let Some(csv) = self.stats_csv.as_mut() {
+            csv.record(latency_us);
+        }
+        if let Some(csv) = self.timeseries.as_mut() {
+            csv.record(recv_ts_us as i64, latency_us);
+        }
+        if let Some(sink) = self.influx_sink.as_mut() {
+            sink.record(latency_us);
+        }
+        if resync_pending && !self.stats.resync_pending() {
+            eprintln!("Resynced at trade {}", trade_id);
+            self.renderer.finish();
+        }
+
+        if let (Some(dump), Some(((gaps, ooo, _), strict))) = (self.anomaly_dump.as_mut(), integrity_before) {
+            let (gaps_now, ooo_now, _) = self.stats.integrity();
+            let mut events = anomaly::Events::NONE;
+            if self.alert_threshold_us > 0 && latency_us > self.alert_threshold_us {
+                events = events | anomaly::Events::LATENCY;
+            }
+            if gaps_now > gaps {
+                events = events | anomaly::Events::GAP;
+            }
+            // Atrás do maior id: repetido se ainda está no ring, senão fora de ordem
+            if self.stats.out_of_order_strict() > strict {
+                if dump.watches(anomaly::Events::DUPLICATE) && dump.seen(trade_id) {
+                    events = events | anomaly::Events::DUPLICATE;
+                } else if ooo_now > ooo {
+                    events = events | anomaly::Events::OUT_OF_ORDER;
+                }
+            }
+            match dump.record(trade_id, trade_ts_us, recv_ts_us, latency_us, events) {
+                Some(Ok(path)) => {
+                    self.renderer.finish();
+                    eprintln!("\n⚠️  Anomaly dump: {}", path);
+                    self.audit
+                        .event("anomaly_dump", &format!("path={} trade_id={}", path, trade_id));
+                }
+                Some(Err(e)) => {
+                    self.renderer.finish();
+                    eprintln!("\n  WARNING: anomaly dump failed: {}", e);
+                }
+                None => {}
+            }
+        }
+
+        // trade: preço/quantidade/maker num parse só (republicação e notional)
+        let fill = if stream_kind == StreamKind::Trade
+            && self.field_map.is_none()
+            && (self.publisher.is_some() || self.track_notional)
+        {
+            extract_trade_full(data)
+        } else {
+            None
+        };
+
+        if let Some(publisher) = &self.publisher {
+            publisher.publish(publish::trade_json(
+                &self.publish_symbol,
+                trade_id,
+                trade_ts_us,
+                recv_ts_us,
+                latency_us,
+                fill.as_ref(),
+            ));
+        }
+
+        if let Some(burst) = self.gap_burst.as_mut() {
+            // Eventos de gap (histograma de tamanhos), não ids perdidos
+            let (missing_total, _, gap_sizes) = self.stats.integrity();
+            let gaps_total: u64 = gap_sizes.iter().sum();
+            if let Some(gaps) = burst.update(gaps_total, recv_instant) {
+                self.renderer.finish();
+                eprintln!(
+                    "\n⚠️  Gap burst: {} sequence gaps in {:?} (trade {}, {} missing ids so far)",
+                    gaps, GAP_BURST_WINDOW, trade_id, missing_total
+                );
+                self.audit.event(
+                    "gap_burst",
+                    &format!("gaps={} trade_id={} missing_total={}", gaps, trade_id, missing_total),
+                );
+            }
+        }
+
+        let trades = self.output.rows();
+        if !self.quiet && self.stats_every > 0 && trades.is_multiple_of(self.stats_every) {
+            eprintln!("{}", display::checkpoint_line(trades, &self.stats));
+            self.renderer.finish();
+        }
+
+        if self.track_notional {
+            let price_qty = match (&self.field_map, &fill) {
+                (Some(map), _) => map.price_qty(data),
+                (None, Some(fill)) => Some((fill.price, fill.qty)),
+                (None, None) => extract_quoted_f64(data, b"\"p\":\"").zip(extract_quoted_f64(data, b"\"q\":\"")),
+            };
+            if let Some((price, qty)) = price_qty {
+                self.total_notional += price * qty;
+            }
+        }
+
+        if self.alert_threshold_us > 0 && latency_us > self.alert_threshold_us {
+            self.alerts += 1;
+            if self
+                .last_alert_log
+                .is_none_or(|t| recv_instant.duration_since(t) >= ALERT_LOG_INTERVAL)
+            {
+                eprintln!(
+                    "⚠️  Latency alert: trade {} at {:.2}ms (> {:.2}ms)",
+                    trade_id,
+                    latency_us as f64 / 1000.0,
+                    self.alert_threshold_us as f64 / 1000.0
+                );
+                self.renderer.finish();
+                self.audit.event(
+                    "alert",
+                    &format!(
+                        "trade_id={} latency_us={} threshold_us={} alerts={}",
+                        trade_id, latency_us, self.alert_threshold_us, self.alerts
+                    ),
+                );
+                self.last_alert_log = Some(recv_instant);
+            }
+        }
+
+        if let Some(proc_stats) = self.proc_stats.as_mut() {
+            if let Some(utilization) = proc_stats.update(trade_id, recv_instant) {
+                self.renderer.finish();
+                eprintln!(
+                    "\n⚠️  Collector is CPU-bound ({:.0}% busy), latency measurements may be inflated",
+                    utilization * 100.0
+                );
+            }
+        }
+
+        // Para quando buffer estiver cheio
+        if trades >= self.config.count {
+            return Break(());
+        }
+        Continue(())
+    }
+
+    /// Final report, JSON/summary, CSV (or Parquet), S3 upload and samples.
+    pub async fn finish(mut self) -> Outcome {
+        let trades = self.output.rows();
+        let count = self.config.count;
+        self.renderer.finish();
+        eprintln!("Collection finished: {} trades", trades);
+        let collection_secs = self.collection_start.elapsed().as_secs_f64();
+        let collection_end_us = wall_clock_us();
+        if let Some(Err(e)) = self.timeseries.take().map(|csv| csv.finish(collection_end_us)) {
+            eprintln!("  WARNING: TIMESERIES_FILE write failed ({})", e);
+        }
+        if self.parse_failures > 0 || self.error_frames > 0 {
+            eprintln!("Parse failures: {} | Error frames: {}", self.parse_failures, self.error_frames);
+        }
+        if self.clock.read_failures() > 0 {
+            eprintln!(
+                "  WARNING: {} PTP clock reads failed; those timestamps came from the system clock",
+                self.clock.read_failures()
+            );
+        }
+        if trades < count {
+            if let Some(reason) = &self.stop_reason {
+                eprintln!("Stopped early: {}", reason);
+            }
+        }
+
+        self.report_latency(collection_secs);
+        self.report_interarrival();
+
+        // csv-writer: os trades não ficam em memória, não há o que cruzar
+        if self.output.trades().len() == trades {
+            let samples: Vec<cross_check::Sample> = self
+                .output
+                .trades()
+                .iter()
+                .map(|t| (t.trade_ts_us, t.recv_ts_us, t.latency_us))
+                .collect();
+            cross_check::report(&samples);
+        } else {
+            eprintln!("\n(clock-independent cross-check skipped: trades streamed to the CSV are not kept in memory)");
+        }
+
+        self.frame_counts.report();
+        // Queda ainda aberta no fim da execução conta até aqui
+        self.reconnects.restored(Instant::now());
+        self.reconnects.report(self.seamless.as_ref().map(|r| r.duplicates()));
+        if let Some(monitor) = &self.tcp_monitor {
+            monitor.report();
+        }
+
+        if let Some(proc_stats) = &self.proc_stats {
+            proc_stats.report();
+        }
+
+        if self.budget_samples > 0 {
+            let n = self.budget_samples as f64;
+            report::report_latency_budget(&[
+                ("Exchange (E−T)", Some(self.budget_exchange_us as f64 / n)),
+                ("Network+decode (recv−E)", Some(self.budget_network_us as f64 / n)),
+                (
+                    "Tool processing",
+                    self.proc_stats.as_ref().and_then(|p| p.avg_ns()).map(|ns| ns / 1000.0),
+                ),
+            ]);
+            if self.proc_stats.is_none() {
+                eprintln!("(tool processing: enable PROC_TIMING=1)");
+            }
+        }
+
+        if let Some(mut dump) = self.anomaly_dump.take() {
+            // Dump ainda esperando os trades "depois": grava o que houver
+            match dump.finish() {
+                Some(Ok(path)) => self.audit.event("anomaly_dump", &format!("path={} partial=1", path)),
+                Some(Err(e)) => eprintln!("  WARNING: anomaly dump failed: {}", e),
+                None => {}
+            }
+            let (written, suppressed) = dump.counts();
+            eprintln!("\n=== Anomaly Dumps ===");
+            eprintln!("Written: {} | Suppressed (ANOMALY_MAX_DUMPS): {}", written, suppressed);
+        }
+
+        if let Some(mut sink) = self.statsd_sink.take() {
+            // Último intervalo (parcial)
+            sink.emit(&self.stats);
+            eprintln!("\n=== StatsD ===");
+            eprintln!("{}", sink.summary());
+        }
+
+        if let Some(mut sink) = self.influx_sink.take() {
+            // Último intervalo (parcial)
+            sink.emit(collection_end_us, Duration::from_secs_f64(collection_secs), &self.stats);
+            let summary = sink.finish().await;
+            eprintln!("\n=== InfluxDB ===");
+            eprintln!("{}", summary);
+        }
+
+        if let Some(publisher) = self.publisher.take() {
+            let (sent, failures) = publisher.finish().await;
+            eprintln!("\n=== Publishing ===");
+            eprintln!("Published: {} | Failures: {}", sent, failures);
+        }
+
+        // Thread do CSV: espera as linhas ainda na fila e o flush final (antes do
+        // resumo, que informa as linhas descartadas)
+        self.output.finish_writer();
+
+        let symbol = self.config.symbol.to_uppercase();
+        let run_info = summary::RunInfo {
+            label: &self.config.label,
+            machine_id: &self.config.machine_id,
+            symbol: &symbol,
+            duration_s: collection_secs,
+            start_us: self.collection_start_us,
+            end_us: collection_end_us,
+            clock_offset_us: self.clock_offset_us,
+            stop_reason: self.stop_reason.as_deref().filter(|_| trades < count),
+            reconnects: &self.reconnects,
+            symbols: self.symbol_stats.as_ref(),
+            depth: self.depth_sequence.as_ref(),
+            csv_dropped: self.output.dropped(),
+        };
+
+        // --- Estatísticas finais em JSON (STATS_JSON=1 -> stdout, STATS_JSON_FILE) ---
+        // stdout só recebe o JSON: o relatório humano vai todo para stderr
+        if env_flag("STATS_JSON") {
+            println!("{}", summary::to_json(&run_info, &self.stats));
+        }
+        if let Ok(path) = std::env::var("STATS_JSON_FILE") {
+            match summary::save(&path, &run_info, &self.stats) {
+                Ok(()) => eprintln!("✅ Stats JSON saved to: {}", path),
+                Err(e) => eprintln!("❌ Error saving stats JSON: {}", e),
+            }
+        }
+
+        // --- Save CSV (ou resumo) ---
+        // Modo linha a linha: o arquivo já está completo
+        let output_file = &self.config.output_file;
+        let csv_result = if self.config.summary_only {
+            summary::save(output_file, &run_info, &self.stats)
+        } else {
+            self.output.save()
+        };
+        match &csv_result {
+            Ok(()) => eprintln!("\n✅ Data saved to: {}", output_file),
+            Err(e) => eprintln!(
+                "\n❌ Error saving {}: {}",
+                if self.config.summary_only { "summary" } else { "CSV" },
+                e
+            ),
+        }
+
+        // --- Upload para o S3 (S3_BUCKET / S3_PUT_URL); o arquivo local fica ---
+        if let (Some(upload), Ok(())) = (&self.s3_upload, &csv_result) {
+            match upload.upload(output_file).await {
+                Ok(dest) => {
+                    eprintln!("✅ Uploaded to: {}", dest);
+                    self.audit.event("upload", &format!("destination={:?}", dest));
+                }
+                Err(e) => {
+                    eprintln!("❌ S3 upload failed ({}); local file kept: {}", e, output_file);
+                    self.audit
+                        .event("upload_failed", &format!("error={:?} file={:?}", e, output_file));
+                }
+            }
+        }
+
+        // --- Amostras cruas para análise externa (SAMPLES_FILE) ---
+        if let Ok(path) = std::env::var("SAMPLES_FILE") {
+            // SAMPLE_MODE=histogram não guarda amostras: só o histograma existe
+            let histogram = std::env::var("SAMPLES_FORMAT").as_deref() == Ok("histogram")
+                || self.sample_mode == SampleMode::Histogram;
+            match summary::save_samples(&path, &self.stats, histogram) {
+                Ok(()) => eprintln!("✅ Samples saved to: {}", path),
+                Err(e) => eprintln!("❌ Error saving samples: {}", e),
+            }
+        }
+
+        if self.audit.enabled() {
+            let (gaps, out_of_order, _) = self.stats.integrity();
+            let (p50, p99) = self
+                .stats
+                .get()
+                .map(|(_, _, _, _, p50, _, p99)| (p50, p99))
+                .unwrap_or_default();
+            self.audit.event(
+                "finalize",
+                &format!(
+                    "trades={} duration_s={:.1} p50_us={} p99_us={} gaps={} out_of_order={} \
+                     parse_failures={} error_frames={} stop_reason={:?} csv={}",
+                    trades,
+                    collection_secs,
+                    p50,
+                    p99,
+                    gaps,
+                    out_of_order,
+                    self.parse_failures,
+                    self.error_frames,
+                    self.stop_reason.as_deref().unwrap_or("complete"),
+                    if csv_result.is_ok() { "saved" } else { "failed" }
+                ),
+            );
+        }
+
+        Outcome {
+            stats: self.stats,
+            stream_error: self.stream_error,
+        }
+    }
+
+    /// Latency table, per-symbol table and sequence integrity.
+    fn report_latency(&self, collection_secs: f64) {
+        let stats = &self.stats;
+        let stream_kind = self.config.stream_kind;
+        let Some((_, avg, min, max, median, p95, p99)) = stats.get() else {
+            if stats.negative_latency() > 0 {
+                // Todas negativas: nada entrou nas estatísticas
+                eprintln!(
+                    "\n  WARNING: all {} latencies were negative and excluded (local clock ahead of Binance?)",
+                    stats.negative_latency()
+                );
+            } else if stats.warmup_skipped() > 0 {
+                eprintln!(
+                    "\n  WARNING: all {} trades fell in the warm-up (WARMUP_TRADES); no latency stats",
+                    stats.warmup_skipped()
+                );
+            }
+            return;
+        };
+        let mut table = report::Table::new(format!("Latency Statistics ({})", self.config.display_name));
+        // Com RESET_EXTREMES_PER_TICK, min/max cobrem só o último intervalo
+        if min <= max {
+            let scope = if self.reset_extremes_per_tick { "(last interval)" } else { "" };
+            table.latency("Min", min as f64).note(scope);
+            table.latency("Max", max as f64).note(scope);
+        }
+        table.latency("Avg", avg);
+        if let Some(stddev) = stats.stddev_us() {
+            table.latency("Std dev", stddev).note("(whole run)");
+        }
+        table
+            .latency("Median", median as f64)
+            .latency("P95", p95 as f64)
+            .latency("P99", p99 as f64);
+        match self.measured_offset_us {
+            Some(offset_us) => {
+                table
+                    .latency("Clock offset", offset_us as f64)
+                    .note("(local − Binance, subtracted)");
+            }
+            None if self.clock_sync => {
+                table.text("Clock offset", "not measured (calibration failed)");
+            }
+            None => {
+                table.text("Clock offset", "not measured (CLOCK_SYNC=0)");
+            }
+        }
+        if stats.warmup_skipped() > 0 {
+            table.count("Warm-up trades", stats.warmup_skipped()).note("(excluded from stats)");
+        }
+        if stats.negative_latency() > 0 {
+            table.count("Negative latency", stats.negative_latency()).note(if stats.keeps_negative() {
+                "(kept in stats: local clock ahead?)"
+            } else {
+                "(excluded from stats: local clock ahead?)"
+            });
+        }
+        let (clock_regressions, max_back_us) = stats.clock_regressions();
+        if clock_regressions > 0 {
+            table
+                .count("Clock regressions", clock_regressions)
+                .note(format!("(recv_ts backwards, max {:.3}ms)", max_back_us as f64 / 1000.0));
+        }
+        if self.alert_threshold_us > 0 {
+            table
+                .count("Alerts", self.alerts)
+                .note(format!("(> {:.2}ms)", self.alert_threshold_us as f64 / 1000.0));
+        }
+        // Histograma configurado: amostras fora da faixa ficam só nas contagens à parte
+        if matches!(stats.histogram_spec(), stats::HistogramSpec::Linear { .. }) {
+            let hist = stats.histogram();
+            if hist.underflow + hist.overflow > 0 {
+                table
+                    .count("Hist out of range", hist.underflow + hist.overflow)
+                    .note(format!("(underflow {}, overflow {})", hist.underflow, hist.overflow));
+            }
+        }
+        if self.track_notional {
+            table.text(
+                "Notional",
+                format!(
+                    "{} total, {}/s",
+                    display::format_notional(self.total_notional),
+                    display::format_notional(self.total_notional / collection_secs.max(0.001))
+                ),
+            );
+        }
+        table.print();
+
+        if let Some(per_symbol) = &self.symbol_stats {
+            report::print_symbol_table(per_symbol);
+        }
+
+        // Depth parcial / ticker array: não há sequência a verificar
+        // (vários símbolos: integridade por símbolo, na tabela acima)
+        if !stream_kind.is_sequenced() || self.symbol_stats.is_some() {
+            return;
+        }
+        let (gaps, out_of_order, gap_sizes) = stats.integrity();
+        let mut table = report::Table::new("Sequence Integrity");
+        if stream_kind == StreamKind::BookTicker {
+            table
+                .count("Skipped update ids", gaps)
+                .note("(updates below the top of book; not loss)");
+        } else {
+            table.count("Missing ids", gaps);
+        }
+        table.count("Out of order", out_of_order);
+        // OOO_TOLERANCE: reordenação adjacente fica só na contagem estrita
+        if stats.ooo_tolerance() > 0 {
+            table
+                .note(format!("(> {} ids behind highest)", stats.ooo_tolerance()))
+                .count("Out of order (strict)", stats.out_of_order_strict());
+        }
+        if stats.late_filled() > 0 {
+            table
+                .count("Late ids", stats.late_filled())
+                .note("(arrived behind the highest id; not counted as missing)");
+        }
+        table
+            .count("Resyncs", stats.resyncs())
+            .count("Counter resets", stats.id_resets());
+        if stream_kind == StreamKind::AggTrade {
+            table.count("Missed aggregates", stats.missed_aggregates());
+        }
+        if let Some(sequence) = &self.depth_sequence {
+            let (breaks, ahead, behind) = sequence.breaks();
+            table
+                .count("Depth breaks", breaks)
+                .note(format!("(U ≠ prev u + 1, {} events checked)", sequence.events()))
+                .count("  U ahead", ahead)
+                .count("  U behind", behind);
+            if let Some((expected, got)) = sequence.first_break() {
+                table.text("  first break", format!("U={} (expected {})", got, expected));
+            }
+        }
+        if gaps > 0 {
+            for (label, n) in stats::GAP_BUCKET_LABELS.iter().zip(gap_sizes) {
+                table.count(&format!("  gap {}", label), n);
+            }
+        }
+        table.print();
+    }
+
+    /// Snapshots, bookTicker and ticker array: time between arrivals.
+    fn report_interarrival(&self) {
+        let Some((n, avg, min, max, p50, p95, p99)) = self.interarrival.get() else {
+            return;
+        };
+        let stream_kind = self.config.stream_kind;
+        let title = match stream_kind {
+            StreamKind::TickerArray => "Ticker Array Interarrival",
+            StreamKind::BookTicker => "Book Ticker Interarrival",
+            _ => "Depth Snapshot Interarrival",
+        };
+        let mut table = report::Table::new(title);
+        table
+            .count("Samples", n)
+            .latency("Min", min as f64)
+            .latency("Max", max as f64)
+            .latency("Avg", avg)
+            .latency("Median", p50 as f64)
+            .latency("P95", p95 as f64)
+            .latency("P99", p99 as f64);
+        if self.ticker_messages > 0 {
+            table.text(
+                "Entries/message",
+                format!("{:.1} avg", self.ticker_entries as f64 / self.ticker_messages as f64),
+            );
+            if let Some((_, avg, _, max, ..)) = self.ticker_spread.get() {
+                table
+                    .latency("\"E\" spread avg", avg)
+                    .note("(newest − oldest entry)")
+                    .latency("\"E\" spread max", max as f64);
+            }
+        }
+        if let Some((bid, ask)) = self.last_quote {
+            table.text("Last quote", format!("{} / {}", bid, ask));
+        }
+        if self.snapshots_without_ts > 0 {
+            let label = if stream_kind == StreamKind::BookTicker {
+                "Updates without \"T\"/\"E\""
+            } else {
+                "Snapshots without \"E\""
+            };
+            table
+                .count(label, self.snapshots_without_ts as u64)
+                .note("(latency not measurable)");
+            // bookTicker spot: a sequência de "u" ficou no intervalo entre chegadas
+            if stream_kind == StreamKind::BookTicker {
+                let (skipped, out_of_order, _) = self.interarrival.integrity();
+                table
+                    .count("Skipped update ids", skipped)
+                    .note("(updates below the top of book; not loss)")
+                    .count("Out of order", out_of_order);
+            }
+        }
+        table.print();
+    }
+}
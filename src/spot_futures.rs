@@ -0,0 +1,156 @@
+//! Spot vs futures: latência do mesmo ativo pelas duas infraestruturas.
+//!
+//! Abre uma conexão em `stream.binance.com` (`<symbol>@trade`) e outra em
+//! `fstream.binance.com` (`<symbol>@aggTrade`, o stream de trades dos
+//! futuros USD-M), coleta as duas em paralelo e compara as distribuições
+//! (percentis lado a lado + KS). As duas latências usam o mesmo relógio
+//! local, então a diferença entre elas não depende do offset de relógio
+//! local (assume os servidores de spot e futuros sincronizados entre si).
+//!
+//! Uso:
+//!   cargo run --release -- --spot-vs-futures [symbol] [count]
+
+use std::time::Instant;
+
+use futures_util::StreamExt;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::clock::{ms_to_us, RecvClock};
+use crate::collector::{try_connect_ws, BINANCE_WS_BASE};
+use crate::compare::{ks_p_value, ks_statistic, ALPHA};
+use crate::extract::{extract_agg_trade, extract_trade_data};
+use crate::stats::{percentile, PercentileMethod};
+
+pub const DEFAULT_COUNT: usize = 1000;
+const DEFAULT_FUTURES_WS_BASE: &str = "wss://fstream.binance.com/ws";
+
+#[derive(Clone, Copy)]
+enum Market {
+    Spot,
+    Futures,
+}
+
+impl Market {
+    fn name(self) -> &'static str {
+        match self {
+            Market::Spot => "Spot",
+            Market::Futures => "Futures",
+        }
+    }
+}
+
+/// Collects `count` recv − T latencies (µs) from one market.
+async fn collect(
+    market: Market,
+    url: String,
+    count: usize,
+    clock: &RecvClock,
+) -> Result<Vec<i64>, String> {
+    let ws = try_connect_ws(&url).await?;
+    eprintln!("{:<8} connected: {}", market.name(), url);
+    let (_write, mut read) = ws.split();
+
+    let mut latencies = Vec::with_capacity(count);
+    while latencies.len() < count {
+        let Some(msg) = read.next().await else {
+            break;
+        };
+        let recv_instant = Instant::now();
+        let text = match msg {
+            Ok(Message::Text(text)) => text,
+            Ok(_) => continue,
+            Err(e) => return Err(format!("{}: {}", market.name(), e)),
+        };
+        // Cada mercado com o seu formato: trade ("t"/"T") vs aggTrade ("a"/"T")
+        let trade_ts_ms = match market {
+            Market::Spot => extract_trade_data(text.as_bytes()).map(|(_, ts)| ts),
+            Market::Futures => extract_agg_trade(text.as_bytes()).map(|(_, _, _, ts)| ts),
+        };
+        if let Some(ts_us) = trade_ts_ms.filter(|&ts| ts > 0).and_then(ms_to_us) {
+            latencies.push(clock.to_epoch_us(recv_instant) as i64 - ts_us);
+        }
+    }
+    if latencies.len() < count {
+        eprintln!(
+            "  WARNING: {} stream ended after {} trades",
+            market.name(),
+            latencies.len()
+        );
+    }
+    Ok(latencies)
+}
+
+/// Runs both collectors concurrently and prints the comparison.
+pub async fn run(symbol: &str, count: usize) -> Result<(), String> {
+    let symbol = symbol.to_lowercase();
+    let clock = RecvClock::from_env();
+    let futures_base =
+        std::env::var("FUTURES_WS_BASE").unwrap_or_else(|_| DEFAULT_FUTURES_WS_BASE.to_string());
+    let spot_url = format!("{}/{}@trade", BINANCE_WS_BASE, symbol);
+    let futures_url = format!("{}/{}@aggTrade", futures_base, symbol);
+
+    eprintln!("=== Spot vs Futures Latency ===");
+    eprintln!("Symbol:  {}", symbol.to_uppercase());
+    eprintln!("Trades:  {} per market", count);
+    eprintln!("Clock:   {}", clock.name());
+
+    let (spot, futures) = tokio::join!(
+        collect(Market::Spot, spot_url, count, &clock),
+        collect(Market::Futures, futures_url, count, &clock)
+    );
+    let (mut spot, mut futures) = (spot?, futures?);
+    if spot.is_empty() || futures.is_empty() {
+        return Err("no trades received from one of the markets".to_string());
+    }
+    spot.sort_unstable();
+    futures.sort_unstable();
+
+    let q = |sorted: &[i64], p: f64| percentile(sorted, p, PercentileMethod::Nearest);
+    let avg = |v: &[i64]| v.iter().sum::<i64>() as f64 / v.len() as f64;
+
+    eprintln!(
+        "\n{:<8} {:>12} {:>12} {:>16}",
+        "", "Spot (ms)", "Futures (ms)", "Fut − Spot (ms)"
+    );
+    let rows = [
+        ("Min", spot[0] as f64, futures[0] as f64),
+        ("Avg", avg(&spot), avg(&futures)),
+        ("P50", q(&spot, 0.50) as f64, q(&futures, 0.50) as f64),
+        ("P95", q(&spot, 0.95) as f64, q(&futures, 0.95) as f64),
+        ("P99", q(&spot, 0.99) as f64, q(&futures, 0.99) as f64),
+        (
+            "Max",
+            spot[spot.len() - 1] as f64,
+            futures[futures.len() - 1] as f64,
+        ),
+    ];
+    for (name, s, f) in rows {
+        eprintln!(
+            "{:<8} {:>12.3} {:>12.3} {:>+16.3}",
+            name,
+            s / 1000.0,
+            f / 1000.0,
+            (f - s) / 1000.0
+        );
+    }
+    eprintln!("{:<8} {:>12} {:>12}", "Samples", spot.len(), futures.len());
+
+    let d = ks_statistic(&spot, &futures);
+    let p = ks_p_value(d, spot.len(), futures.len());
+    eprintln!("\nKS statistic (D): {:.6} | p-value (approx): {:.6}", d, p);
+    if p < ALPHA {
+        let faster = if q(&futures, 0.50) < q(&spot, 0.50) {
+            "Futures"
+        } else {
+            "Spot"
+        };
+        eprintln!(
+            "Result: distributions DIFFER; faster feed from here (by median): {}",
+            faster
+        );
+    } else {
+        eprintln!("Result: no significant difference (p >= {})", ALPHA);
+    }
+    eprintln!("Note: Futures usa aggTrade (T = último trade do agregado); a diferença independe do offset do relógio local.");
+    Ok(())
+}
@@ -0,0 +1,1795 @@
+//! Latency statistics accumulated during collection.
+//!
+//! `update` roda no hot path: apenas aritmética e um push no VecDeque
+//! pré-alocado. Ordenação/percentis só acontecem em `get`.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Instant;
+
+/// Bytes per stored sample in `recent_latencies`.
+pub const SAMPLE_BYTES: usize = std::mem::size_of::<i64>();
+
+/// Sample count that fits in a `kb` KiB budget (VecDeque stores samples contiguously).
+pub fn samples_for_memory_kb(kb: usize) -> usize {
+    (kb * 1024 / SAMPLE_BYTES).max(1)
+}
+
+/// Non-zero seed for the reservoir PRNG.
+fn rng_seed() -> u64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    nanos | 1
+}
+
+/// Gap-size buckets: 1, 2–10, 11–100, >100 missing ids.
+pub const GAP_BUCKET_LABELS: [&str; 4] = ["1", "2-10", "11-100", ">100"];
+
+#[inline(always)]
+fn gap_bucket(missing: u64) -> usize {
+    match missing {
+        1 => 0,
+        2..=10 => 1,
+        11..=100 => 2,
+        _ => 3,
+    }
+}
+
+/// Default `GAP_FILL_WINDOW`: a missing id arriving up to this many ids
+/// behind the highest seen still fills its gap (0 = never, gaps are final).
+pub const DEFAULT_GAP_FILL_WINDOW: u64 = 10_000;
+
+/// Open gaps kept at most; beyond that the oldest become final.
+const MAX_OPEN_GAPS: usize = 1024;
+
+/// Missing ids that may still arrive late (reordering), as disjoint ranges.
+///
+/// Só é tocado quando há gap ou id atrasado: o caminho em ordem não paga nada.
+#[derive(Default)]
+struct PendingGaps {
+    /// first id -> (last id, start of the gap it belongs to), inclusive.
+    ranges: BTreeMap<u64, (u64, u64)>,
+    /// gap start -> (size, ids filled late, ids still open).
+    gaps: BTreeMap<u64, (u64, u64, u64)>,
+}
+
+impl PendingGaps {
+    fn open(&mut self, first: u64, last: u64) {
+        let size = last - first + 1;
+        self.ranges.insert(first, (last, first));
+        self.gaps.insert(first, (size, 0, size));
+    }
+
+    /// Drops ranges more than `window` ids behind `highest` (or past
+    /// MAX_OPEN_GAPS): those ids are lost for good.
+    fn prune(&mut self, highest: u64, window: u64) {
+        while let Some((&first, &(last, gap))) = self.ranges.first_key_value() {
+            if last.saturating_add(window) >= highest && self.ranges.len() <= MAX_OPEN_GAPS {
+                break;
+            }
+            self.ranges.remove(&first);
+            self.close(gap, last - first + 1);
+        }
+    }
+
+    fn close(&mut self, gap: u64, ids: u64) {
+        if let Some(entry) = self.gaps.get_mut(&gap) {
+            entry.2 -= ids;
+            if entry.2 == 0 {
+                self.gaps.remove(&gap);
+            }
+        }
+    }
+
+    /// Marks `first..=last` as arrived. Returns the ids that were missing;
+    /// gaps filled completely leave `gap_sizes`.
+    fn fill(&mut self, first: u64, last: u64, gap_sizes: &mut [u64; 4]) -> u64 {
+        // Ranges disjuntos e ordenados: os que cruzam [first, last] são contíguos
+        let hits: Vec<(u64, u64, u64)> = self
+            .ranges
+            .range(..=last)
+            .rev()
+            .take_while(|(_, &(end, _))| end >= first)
+            .map(|(&start, &(end, gap))| (start, end, gap))
+            .collect();
+        let mut filled = 0;
+        for (start, end, gap) in hits {
+            self.ranges.remove(&start);
+            let (lo, hi) = (start.max(first), end.min(last));
+            if start < lo {
+                self.ranges.insert(start, (lo - 1, gap));
+            }
+            if hi < end {
+                self.ranges.insert(hi + 1, (end, gap));
+            }
+            let n = hi - lo + 1;
+            filled += n;
+            if let Some(entry) = self.gaps.get_mut(&gap) {
+                entry.1 += n;
+                if entry.1 == entry.0 {
+                    let bucket = &mut gap_sizes[gap_bucket(entry.0)];
+                    *bucket = bucket.saturating_sub(1);
+                }
+            }
+            self.close(gap, n);
+        }
+        filled
+    }
+
+    fn clear(&mut self) {
+        self.ranges.clear();
+        self.gaps.clear();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Sequência do depth (U/u por mensagem)
+// ---------------------------------------------------------------------------
+
+/// Binance's diff depth check (`depth` / `depth@100ms`): each event must
+/// start right after the previous one, `U == prev u + 1`. Any other value
+/// breaks the local order book, which then has to be rebuilt from a snapshot.
+///
+/// Modelo por mensagem, separado da contagem de ids do `update_range`: não
+/// há tolerância de reordenação nem preenchimento tardio, qualquer quebra conta.
+#[derive(Debug, Default)]
+pub struct DepthSequence {
+    last_u: Option<u64>,
+    events: u64,
+    /// U > prev u + 1: updates never received.
+    ahead: u64,
+    /// U <= prev u: overlapping or repeated updates.
+    behind: u64,
+    /// (expected U, received U) of the first break.
+    first_break: Option<(u64, u64)>,
+}
+
+impl DepthSequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks one event `U..=u`. `resync`: first event after a (re)connect,
+    /// which only sets the baseline. Returns the expected U on a break.
+    #[inline(always)]
+    pub fn check(&mut self, first_id: u64, last_id: u64, resync: bool) -> Option<u64> {
+        self.events += 1;
+        let prev = self.last_u.replace(last_id);
+        let expected = prev.filter(|_| !resync)? + 1;
+        if first_id == expected {
+            return None;
+        }
+        if first_id > expected {
+            self.ahead += 1;
+        } else {
+            self.behind += 1;
+        }
+        self.first_break.get_or_insert((expected, first_id));
+        Some(expected)
+    }
+
+    /// Events checked (baselines included).
+    pub fn events(&self) -> u64 {
+        self.events
+    }
+
+    /// (breaks, U ahead of prev u + 1, U at or behind prev u).
+    pub fn breaks(&self) -> (u64, u64, u64) {
+        (self.ahead + self.behind, self.ahead, self.behind)
+    }
+
+    /// (expected U, received U) of the first break.
+    pub fn first_break(&self) -> Option<(u64, u64)> {
+        self.first_break
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Latência: amostragem, histograma e agregado da execução
+// ---------------------------------------------------------------------------
+
+/// How `recent_latencies` is filled once it holds `max_samples` samples.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SampleMode {
+    /// Últimos N trades (janela deslizante).
+    Window,
+    /// Amostra aleatória uniforme de toda a execução (reservoir sampling, Algorithm R).
+    Reservoir,
+    /// Sem amostras: histograma log-linear da execução inteira (`LogHistogram`),
+    /// memória fixa e percentis sem ordenação, com erro relativo < 0,4%.
+    Histogram,
+}
+
+impl SampleMode {
+    /// `SAMPLE_MODE=window|reservoir|histogram` (default: window).
+    pub fn from_env() -> Self {
+        match std::env::var("SAMPLE_MODE").as_deref() {
+            Ok("reservoir") => SampleMode::Reservoir,
+            Ok("histogram") => SampleMode::Histogram,
+            _ => SampleMode::Window,
+        }
+    }
+}
+
+/// Linear sub-buckets per power of two in `LogHistogram` (2^8 = 256).
+const LOG_HIST_SUB_BITS: u32 = 8;
+const LOG_HIST_SUB: u64 = 1 << LOG_HIST_SUB_BITS;
+/// Largest magnitude tracked exactly by bucket (~12 days in µs); larger
+/// values share the last bucket (min/max stay exact).
+const LOG_HIST_MAX: u64 = (1 << 40) - 1;
+
+/// Memory of one `LogHistogram` (positive side; the negative side is only
+/// allocated if a negative latency shows up).
+pub const LOG_HISTOGRAM_BYTES: usize = (LogHistogram::index(LOG_HIST_MAX) + 1) * 8;
+
+/// HDR-style log-linear histogram of the whole run (`SAMPLE_MODE=histogram`).
+///
+/// Valores < 512 têm bucket próprio (exatos); acima disso cada potência de 2
+/// é dividida em 256 buckets lineares, então o bucket de v tem largura
+/// < v/256. Latência negativa (offset de relógio) vai para uma tabela espelhada,
+/// alocada só quando aparece.
+struct LogHistogram {
+    positive: Vec<u64>,
+    negative: Vec<u64>,
+    total: u64,
+}
+
+impl LogHistogram {
+    fn new() -> Self {
+        Self {
+            positive: vec![0; Self::index(LOG_HIST_MAX) + 1],
+            negative: Vec::new(),
+            total: 0,
+        }
+    }
+
+    /// Bucket of magnitude `v`: `shift * SUB + (v >> shift)`, where the
+    /// mantissa `v >> shift` lands in [SUB, 2·SUB) for v >= 2·SUB.
+    #[inline(always)]
+    const fn index(v: u64) -> usize {
+        let v = if v > LOG_HIST_MAX { LOG_HIST_MAX } else { v };
+        let msb = 63 - (v | 1).leading_zeros();
+        let shift = msb.saturating_sub(LOG_HIST_SUB_BITS);
+        (shift as u64 * LOG_HIST_SUB + (v >> shift)) as usize
+    }
+
+    /// Midpoint of the magnitudes that map to bucket `i`.
+    fn value(i: usize) -> u64 {
+        let i = i as u64;
+        if i < 2 * LOG_HIST_SUB {
+            return i;
+        }
+        let shift = i / LOG_HIST_SUB - 1;
+        let mantissa = i % LOG_HIST_SUB + LOG_HIST_SUB;
+        (mantissa << shift) + ((1 << shift) - 1) / 2
+    }
+
+    #[inline(always)]
+    fn record(&mut self, latency_us: i64) {
+        self.total += 1;
+        if latency_us >= 0 {
+            self.positive[Self::index(latency_us as u64)] += 1;
+        } else {
+            if self.negative.is_empty() {
+                self.negative = vec![0; self.positive.len()];
+            }
+            self.negative[Self::index(latency_us.unsigned_abs())] += 1;
+        }
+    }
+
+    /// (representative value, count) of every non-empty bucket, ascending.
+    fn buckets(&self) -> impl Iterator<Item = (i64, u64)> + '_ {
+        let negative = self
+            .negative
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, &n)| (-(Self::value(i) as i64), n));
+        let positive = self
+            .positive
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| (Self::value(i) as i64, n));
+        negative.chain(positive).filter(|&(_, n)| n > 0)
+    }
+
+    /// Values at 1-based ascending `ranks` (sorted), in one pass.
+    fn values_at_ranks(&self, ranks: &[u64]) -> Vec<i64> {
+        let mut out = Vec::with_capacity(ranks.len());
+        let mut cumulative = 0;
+        let mut next = ranks.iter().peekable();
+        for (value, n) in self.buckets() {
+            cumulative += n;
+            while next.next_if(|&&r| r <= cumulative).is_some() {
+                out.push(value);
+            }
+            if next.peek().is_none() {
+                break;
+            }
+        }
+        out
+    }
+
+    /// Percentiles `qs` with the same definitions as `percentile`, resolved to
+    /// bucket values and clamped to the exact [min, max].
+    fn percentiles(&self, qs: &[f64], method: PercentileMethod, min: i64, max: i64) -> Vec<i64> {
+        let n = self.total;
+        if n == 0 {
+            return Vec::new();
+        }
+        // (rank baixo, rank alto, fração) por quantil; nearest usa só o baixo
+        let plan: Vec<(u64, u64, f64)> = qs
+            .iter()
+            .map(|&q| {
+                let q = q.clamp(0.0, 1.0);
+                match method {
+                    PercentileMethod::Nearest => {
+                        let rank = ((q * n as f64).ceil() as u64).clamp(1, n);
+                        (rank, rank, 0.0)
+                    }
+                    PercentileMethod::Linear => {
+                        let h = (n - 1) as f64 * q;
+                        let lo = h.floor() as u64;
+                        (lo + 1, (lo + 2).min(n), h - lo as f64)
+                    }
+                }
+            })
+            .collect();
+        let mut ranks: Vec<u64> = plan.iter().flat_map(|&(lo, hi, _)| [lo, hi]).collect();
+        ranks.sort_unstable();
+        ranks.dedup();
+        let values = self.values_at_ranks(&ranks);
+        let at = |rank: u64| values[ranks.binary_search(&rank).unwrap_or(0)];
+        plan.iter()
+            .map(|&(lo, hi, frac)| {
+                let (a, b) = (at(lo), at(hi));
+                let v = (a as f64 + frac * (b - a) as f64).round() as i64;
+                // min > max logo após reset_extremes: sem clamp
+                if min <= max {
+                    v.clamp(min, max)
+                } else {
+                    v
+                }
+            })
+            .collect()
+    }
+}
+
+/// Percentile definition used by `get`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PercentileMethod {
+    /// Nearest-rank: menor valor com pelo menos q·n amostras <= ele
+    /// (x[ceil(q·n) - 1]). Sempre devolve um valor observado.
+    Nearest,
+    /// Interpolação linear entre ranks (Hyndman-Fan tipo 7; padrão do
+    /// numpy/pandas/Excel PERCENTILE.INC).
+    Linear,
+}
+
+impl PercentileMethod {
+    /// `PERCENTILE_METHOD=nearest|linear` (default: nearest).
+    pub fn from_env() -> Self {
+        match std::env::var("PERCENTILE_METHOD").as_deref() {
+            Ok("linear") => PercentileMethod::Linear,
+            _ => PercentileMethod::Nearest,
+        }
+    }
+}
+
+/// Upper limit on configured histogram buckets (memória e tamanho do JSON).
+const MAX_HIST_BUCKETS: i64 = 100_000;
+
+/// Latency histogram layout.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HistogramSpec {
+    /// 1-2-5 bounds from 1µs to 100s (default, serve para qualquer escala).
+    Log,
+    /// Fixed-width buckets over (min_us, max_us] (HIST_MIN_MS/HIST_MAX_MS/HIST_BUCKET_US).
+    Linear { min_us: i64, max_us: i64, bucket_us: i64 },
+}
+
+impl HistogramSpec {
+    /// Linear when any of `HIST_MIN_MS`, `HIST_MAX_MS`, `HIST_BUCKET_US` is set.
+    pub fn from_env() -> Self {
+        if ["HIST_MIN_MS", "HIST_MAX_MS", "HIST_BUCKET_US"]
+            .iter()
+            .all(|name| std::env::var(name).is_err())
+        {
+            return HistogramSpec::Log;
+        }
+        let min_us = (crate::env_parse("HIST_MIN_MS", 0.0f64) * 1000.0) as i64;
+        let max_us = (crate::env_parse("HIST_MAX_MS", 1000.0f64) * 1000.0) as i64;
+        let mut bucket_us = crate::env_parse("HIST_BUCKET_US", 1000i64).max(1);
+        if max_us <= min_us {
+            eprintln!("  WARNING: HIST_MAX_MS must be above HIST_MIN_MS; using the default histogram");
+            return HistogramSpec::Log;
+        }
+        let range = max_us - min_us;
+        if range / bucket_us > MAX_HIST_BUCKETS {
+            bucket_us = (range + MAX_HIST_BUCKETS - 1) / MAX_HIST_BUCKETS;
+            eprintln!("  WARNING: too many histogram buckets; HIST_BUCKET_US raised to {}", bucket_us);
+        }
+        HistogramSpec::Linear { min_us, max_us, bucket_us }
+    }
+
+    /// Values <= this go to underflow.
+    fn floor_us(&self) -> i64 {
+        match *self {
+            HistogramSpec::Log => 0,
+            HistogramSpec::Linear { min_us, .. } => min_us,
+        }
+    }
+
+    /// Ascending bucket upper bounds; values above the last go to overflow.
+    fn bounds(&self) -> Vec<i64> {
+        match *self {
+            HistogramSpec::Log => {
+                let mut bounds = Vec::new();
+                let mut decade = 1i64;
+                while decade <= 100_000_000 {
+                    for m in [1, 2, 5] {
+                        bounds.push(decade * m);
+                    }
+                    decade *= 10;
+                }
+                bounds
+            }
+            HistogramSpec::Linear { min_us, max_us, bucket_us } => {
+                let mut bounds: Vec<i64> = (1..)
+                    .map(|i| min_us + i * bucket_us)
+                    .take_while(|&b| b < max_us)
+                    .collect();
+                bounds.push(max_us);
+                bounds
+            }
+        }
+    }
+}
+
+/// Sample histogram: (upper bound µs, count) plus out-of-range counts.
+pub struct Histogram {
+    pub buckets: Vec<(i64, u64)>,
+    /// Samples <= `floor_us` (latência negativa/zero no layout padrão).
+    pub underflow: u64,
+    pub floor_us: i64,
+    /// Samples above the last bound.
+    pub overflow: u64,
+}
+
+/// Percentile `q` (0.0..=1.0) of an ascending, non-empty slice.
+pub fn percentile(sorted: &[i64], q: f64, method: PercentileMethod) -> i64 {
+    let n = sorted.len();
+    let q = q.clamp(0.0, 1.0);
+    match method {
+        PercentileMethod::Nearest => {
+            let rank = (q * n as f64).ceil() as usize;
+            sorted[rank.clamp(1, n) - 1]
+        }
+        PercentileMethod::Linear => {
+            let h = (n - 1) as f64 * q;
+            let lo = h.floor() as usize;
+            let hi = (lo + 1).min(n - 1);
+            let frac = h - lo as f64;
+            (sorted[lo] as f64 + frac * (sorted[hi] - sorted[lo]) as f64).round() as i64
+        }
+    }
+}
+
+/// `LatencyStats::get()`: (count, avg_us, min_us, max_us, p50_us, p95_us, p99_us).
+pub type LatencySummary = (u64, f64, i64, i64, i64, i64, i64);
+
+/// Default `ID_RESET_THRESHOLD`: trade ids never go back this far through
+/// reordering alone, so a larger drop means the counter restarted.
+pub const DEFAULT_ID_RESET_THRESHOLD: u64 = 1_000_000;
+
+/// Running latency statistics (microseconds) plus trade_id integrity.
+///
+/// min/max/avg cover every trade; percentiles cover the last `max_samples` trades
+/// (Window), a uniform sample of the whole run (Reservoir) or every trade,
+/// bucketed (Histogram).
+pub struct LatencyStats {
+    count: u64,
+    // i128: u64::MAX amostras de i64::MAX ainda cabem, soma exata sem overflow
+    total_latency: i128,
+    min_latency: i64,
+    max_latency: i64,
+    // Extremos desde o último `take_tick_extremes` (janela do display)
+    tick_min: i64,
+    tick_max: i64,
+    recent_latencies: VecDeque<i64>,
+    max_samples: usize,
+    sample_mode: SampleMode,
+    // SAMPLE_MODE=histogram: substitui `recent_latencies`
+    log_histogram: Option<Box<LogHistogram>>,
+    rng_state: u64,
+    percentile_method: PercentileMethod,
+    histogram_spec: HistogramSpec,
+    // Latência negativa = relógio local adiantado: contada e, por padrão,
+    // fora de min/max/avg/percentis (KEEP_NEGATIVE_LATENCY=1 mantém)
+    negative_latency: u64,
+    keep_negative: bool,
+    // WARMUP_TRADES: primeiros trades (handshake, caches frios) fora da
+    // latência; a sequência de ids continua sendo validada
+    warmup_remaining: u64,
+    warmup_skipped: u64,
+    // Jitter RFC 3550: média móvel (1/16) de |latência - latência anterior|
+    jitter_us: f64,
+    last_latency_us: Option<i64>,
+    // Welford: média e M2 da execução inteira (desvio padrão sem guardar amostras)
+    welford_mean: f64,
+    welford_m2: f64,
+
+    // --- Integridade da sequência de trade_id ---
+    last_trade_id: u64,
+    // (Re)conexão: o próximo trade só define a nova base, sem contar gap
+    resync_pending: bool,
+    resyncs: u64,
+    // Queda de id maior que isto = contador reiniciado (0 = desligado)
+    id_reset_threshold: u64,
+    id_resets: u64,
+    gaps_detected: u64,     // total de ids faltando
+    // Fora de ordem só se ficar mais de `ooo_tolerance` ids atrás do maior visto
+    ooo_tolerance: u64,
+    out_of_order: u64,
+    out_of_order_strict: u64, // qualquer id <= maior visto (sem tolerância)
+    gap_sizes: [u64; 4],    // histograma de tamanhos de gap (GAP_BUCKET_LABELS)
+    // Id atrasado que preenche um gap aberto: sai de gaps_detected
+    pending_gaps: PendingGaps,
+    gap_fill_window: u64,
+    late_filled: u64,
+
+    // --- aggTrade: continuidade de agregados ("a") ---
+    last_agg_id: u64,
+    missed_aggregates: u64,
+
+    // --- recv_ts deve ser monotônico: voltar no tempo = step do relógio ---
+    last_recv_ts_us: u64,
+    clock_regressions: u64,
+    max_clock_regression_us: u64,
+}
+
+impl LatencyStats {
+    pub fn new(max_samples: usize) -> Self {
+        let max_samples = max_samples.max(1);
+        Self {
+            count: 0,
+            total_latency: 0,
+            min_latency: i64::MAX,
+            max_latency: i64::MIN,
+            tick_min: i64::MAX,
+            tick_max: i64::MIN,
+            recent_latencies: VecDeque::with_capacity(max_samples),
+            max_samples,
+            sample_mode: SampleMode::Window,
+            log_histogram: None,
+            rng_state: rng_seed(),
+            percentile_method: PercentileMethod::Nearest,
+            histogram_spec: HistogramSpec::Log,
+            negative_latency: 0,
+            keep_negative: false,
+            warmup_remaining: 0,
+            warmup_skipped: 0,
+            jitter_us: 0.0,
+            last_latency_us: None,
+            welford_mean: 0.0,
+            welford_m2: 0.0,
+            last_trade_id: 0,
+            resync_pending: true,
+            resyncs: 0,
+            id_reset_threshold: DEFAULT_ID_RESET_THRESHOLD,
+            id_resets: 0,
+            gaps_detected: 0,
+            ooo_tolerance: 0,
+            out_of_order: 0,
+            out_of_order_strict: 0,
+            gap_sizes: [0; 4],
+            pending_gaps: PendingGaps::default(),
+            gap_fill_window: DEFAULT_GAP_FILL_WINDOW,
+            late_filled: 0,
+            last_agg_id: 0,
+            missed_aggregates: 0,
+            last_recv_ts_us: 0,
+            clock_regressions: 0,
+            max_clock_regression_us: 0,
+        }
+    }
+
+    /// Records one trade: sequence check on `trade_id` + latency sample.
+    #[inline(always)]
+    pub fn update(&mut self, trade_id: u64, latency_us: i64) {
+        self.check_sequence(trade_id);
+        self.record(latency_us);
+    }
+
+    /// Records one aggTrade. Integrity is range-based: the next aggregate must
+    /// start at `prev.l + 1`; any trade ids in between were missed. Aggregate
+    /// ids ("a") are checked separately for missed aggregates.
+    #[inline(always)]
+    pub fn update_agg(&mut self, agg_id: u64, first_id: u64, last_id: u64, latency_us: i64) {
+        if self.resync_pending || self.is_counter_reset(self.last_agg_id, agg_id) {
+            self.last_agg_id = agg_id;
+        } else if agg_id > self.last_agg_id + 1 {
+            self.missed_aggregates += agg_id - self.last_agg_id - 1;
+        }
+        self.last_agg_id = self.last_agg_id.max(agg_id);
+        self.update_range(first_id, last_id, latency_us);
+    }
+
+    /// Records one message covering ids `first_id..=last_id` (aggTrade f/l,
+    /// diff depth U/u). The next message must start at `prev last + 1`.
+    #[inline(always)]
+    pub fn update_range(&mut self, first_id: u64, last_id: u64, latency_us: i64) {
+        if self.take_resync() {
+            self.pending_gaps.clear();
+            self.last_trade_id = last_id;
+        } else if self.is_counter_reset(self.last_trade_id, first_id) {
+            self.id_resets += 1;
+            self.pending_gaps.clear();
+            self.last_trade_id = last_id;
+        } else if first_id <= self.last_trade_id {
+            self.fill_late(first_id, last_id);
+            self.count_out_of_order(first_id);
+            // Faixa sobreposta que passa do maior id: a próxima começa depois dela
+            self.last_trade_id = self.last_trade_id.max(last_id);
+        } else {
+            self.count_gap(first_id);
+            self.last_trade_id = last_id;
+        }
+
+        self.record(latency_us);
+    }
+
+    /// Checks that receive timestamps never go backwards. Returns the size of
+    /// the backward jump (µs) when `recv_ts_us` is earlier than the previous one.
+    #[inline(always)]
+    pub fn check_recv_ts(&mut self, recv_ts_us: u64) -> Option<u64> {
+        let last = std::mem::replace(&mut self.last_recv_ts_us, recv_ts_us);
+        if recv_ts_us >= last {
+            return None;
+        }
+        let back_us = last - recv_ts_us;
+        self.clock_regressions += 1;
+        self.max_clock_regression_us = self.max_clock_regression_us.max(back_us);
+        Some(back_us)
+    }
+
+    /// Returns (clock_regressions, largest backward jump in µs).
+    pub fn clock_regressions(&self) -> (u64, u64) {
+        (self.clock_regressions, self.max_clock_regression_us)
+    }
+
+    /// Marks a (re)connect boundary: the next trade re-seeds the baseline and
+    /// is excluded from gap / out-of-order counting.
+    pub fn mark_resync(&mut self) {
+        self.resync_pending = true;
+    }
+
+    /// True until the first trade after `mark_resync` has been recorded.
+    pub fn resync_pending(&self) -> bool {
+        self.resync_pending
+    }
+
+    /// Baselines recorded after a (re)connect (the initial connect included).
+    pub fn resyncs(&self) -> u64 {
+        self.resyncs
+    }
+
+    /// Sets the id drop treated as a counter reset (0 disables).
+    pub fn set_id_reset_threshold(&mut self, threshold: u64) {
+        self.id_reset_threshold = threshold;
+    }
+
+    /// Times the id counter was seen restarting (re-baselined, not out-of-order).
+    pub fn id_resets(&self) -> u64 {
+        self.id_resets
+    }
+
+    /// A drop of more than the threshold is a restarted counter, not reordering.
+    #[inline(always)]
+    fn is_counter_reset(&self, last: u64, id: u64) -> bool {
+        self.id_reset_threshold > 0 && last.saturating_sub(id) > self.id_reset_threshold
+    }
+
+    /// `id` is at or below the highest id seen: always counted as strict;
+    /// counted as out-of-order only if it lags by more than the tolerance.
+    #[inline(always)]
+    fn count_out_of_order(&mut self, id: u64) {
+        self.out_of_order_strict += 1;
+        if self.ooo_tolerance == 0 || self.last_trade_id - id > self.ooo_tolerance {
+            self.out_of_order += 1;
+        }
+    }
+
+    /// Sets how far behind the highest id a trade may arrive before it counts
+    /// as out-of-order (0 = strict: any id <= highest seen).
+    pub fn set_ooo_tolerance(&mut self, tolerance: u64) {
+        self.ooo_tolerance = tolerance;
+    }
+
+    pub fn ooo_tolerance(&self) -> u64 {
+        self.ooo_tolerance
+    }
+
+    /// Out-of-order count ignoring `OOO_TOLERANCE` (every id <= highest seen).
+    pub fn out_of_order_strict(&self) -> u64 {
+        self.out_of_order_strict
+    }
+
+    #[inline(always)]
+    fn take_resync(&mut self) -> bool {
+        if !self.resync_pending {
+            return false;
+        }
+        self.resync_pending = false;
+        self.resyncs += 1;
+        true
+    }
+
+    /// Gap / out-of-order detection against the highest trade_id seen.
+    #[inline(always)]
+    fn check_sequence(&mut self, trade_id: u64) {
+        if self.take_resync() {
+            self.pending_gaps.clear();
+            self.last_trade_id = trade_id;
+            return;
+        }
+        if self.is_counter_reset(self.last_trade_id, trade_id) {
+            self.id_resets += 1;
+            self.pending_gaps.clear();
+            self.last_trade_id = trade_id;
+            return;
+        }
+        if trade_id <= self.last_trade_id {
+            self.fill_late(trade_id, trade_id);
+            self.count_out_of_order(trade_id);
+            return;
+        }
+        self.count_gap(trade_id);
+        self.last_trade_id = trade_id;
+    }
+
+    /// Counts the ids between the highest seen and `next` as missing (and
+    /// keeps them open for a late arrival).
+    #[inline(always)]
+    fn count_gap(&mut self, next: u64) {
+        let missing = next - self.last_trade_id - 1;
+        if missing == 0 {
+            return;
+        }
+        self.gaps_detected += missing;
+        self.gap_sizes[gap_bucket(missing)] += 1;
+        if self.gap_fill_window > 0 {
+            self.pending_gaps.open(self.last_trade_id + 1, next - 1);
+            self.pending_gaps.prune(next, self.gap_fill_window);
+        }
+    }
+
+    /// Ids arriving behind the highest seen: the ones still missing stop
+    /// counting as gaps.
+    fn fill_late(&mut self, first: u64, last: u64) {
+        if self.gap_fill_window == 0 {
+            return;
+        }
+        self.pending_gaps.prune(self.last_trade_id, self.gap_fill_window);
+        let filled = self.pending_gaps.fill(first, last, &mut self.gap_sizes);
+        self.gaps_detected -= filled;
+        self.late_filled += filled;
+    }
+
+    /// How far behind the highest id a missing id may arrive and still fill
+    /// its gap (0 = gaps are final as soon as they are seen).
+    pub fn set_gap_fill_window(&mut self, window: u64) {
+        self.gap_fill_window = window;
+        if window == 0 {
+            self.pending_gaps.clear();
+        }
+    }
+
+    /// Ids first counted as missing that arrived later (reordering); already
+    /// subtracted from the gaps in `integrity()`.
+    pub fn late_filled(&self) -> u64 {
+        self.late_filled
+    }
+
+    /// Records one latency sample (no sequence check). Negative values are
+    /// counted apart and left out of the statistics unless `keep_negative`.
+    #[inline(always)]
+    pub fn record(&mut self, latency_us: i64) {
+        if self.warmup_remaining > 0 {
+            self.warmup_remaining -= 1;
+            self.warmup_skipped += 1;
+            return;
+        }
+        if latency_us < 0 {
+            self.negative_latency += 1;
+            if !self.keep_negative {
+                return;
+            }
+        }
+        self.count += 1;
+        self.total_latency += latency_us as i128;
+        let delta = latency_us as f64 - self.welford_mean;
+        self.welford_mean += delta / self.count as f64;
+        self.welford_m2 += delta * (latency_us as f64 - self.welford_mean);
+        if let Some(prev) = self.last_latency_us {
+            self.jitter_us += (latency_us.abs_diff(prev) as f64 - self.jitter_us) / 16.0;
+        }
+        self.last_latency_us = Some(latency_us);
+        self.min_latency = self.min_latency.min(latency_us);
+        self.tick_min = self.tick_min.min(latency_us);
+        self.tick_max = self.tick_max.max(latency_us);
+        self.max_latency = self.max_latency.max(latency_us);
+
+        if let Some(hist) = self.log_histogram.as_mut() {
+            hist.record(latency_us);
+            return;
+        }
+        if self.recent_latencies.len() < self.max_samples {
+            self.recent_latencies.push_back(latency_us);
+            return;
+        }
+        match self.sample_mode {
+            SampleMode::Window => {
+                self.recent_latencies.pop_front();
+                self.recent_latencies.push_back(latency_us);
+            }
+            SampleMode::Reservoir => {
+                // Substitui um elemento com probabilidade max_samples / count
+                let j = self.next_random() % self.count;
+                if (j as usize) < self.max_samples {
+                    self.recent_latencies[j as usize] = latency_us;
+                }
+            }
+            // Já tratado acima (log_histogram)
+            SampleMode::Histogram => {}
+        }
+    }
+
+    /// xorshift64*: barato e suficiente para reservoir sampling.
+    #[inline(always)]
+    fn next_random(&mut self) -> u64 {
+        let mut x = self.rng_state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng_state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Histogram mode drops the sample buffer (percentiles then cover the
+    /// whole run in fixed memory); call before recording.
+    pub fn set_sample_mode(&mut self, mode: SampleMode) {
+        self.sample_mode = mode;
+        if mode == SampleMode::Histogram {
+            self.recent_latencies = VecDeque::new();
+            self.log_histogram = Some(Box::new(LogHistogram::new()));
+        }
+    }
+
+    pub fn set_percentile_method(&mut self, method: PercentileMethod) {
+        self.percentile_method = method;
+    }
+
+    pub fn set_histogram_spec(&mut self, spec: HistogramSpec) {
+        self.histogram_spec = spec;
+    }
+
+    pub fn histogram_spec(&self) -> HistogramSpec {
+        self.histogram_spec
+    }
+
+    /// Keeps negative latencies in min/max/avg/percentiles (they are
+    /// counted in `negative_latency` either way).
+    pub fn set_keep_negative(&mut self, keep: bool) {
+        self.keep_negative = keep;
+    }
+
+    pub fn keeps_negative(&self) -> bool {
+        self.keep_negative
+    }
+
+    /// Samples in the statistics (excluded negative latencies not included).
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Samples passed to `record`, including excluded negative latencies
+    /// and warm-up trades.
+    pub fn recorded(&self) -> u64 {
+        let excluded = if self.keep_negative { 0 } else { self.negative_latency };
+        self.count + excluded + self.warmup_skipped
+    }
+
+    /// RFC 3550 jitter (µs): smoothed difference between consecutive latencies.
+    pub fn jitter_us(&self) -> f64 {
+        self.jitter_us
+    }
+
+    /// Sample standard deviation (µs) of every recorded latency, not just the
+    /// sample window; None with fewer than two.
+    pub fn stddev_us(&self) -> Option<f64> {
+        (self.count >= 2).then(|| (self.welford_m2 / (self.count - 1) as f64).sqrt())
+    }
+
+    /// Leaves the next `trades` latencies out of the statistics (sequence
+    /// checks still see them); call before recording.
+    pub fn set_warmup(&mut self, trades: u64) {
+        self.warmup_remaining = trades;
+    }
+
+    /// Latencies left out as warm-up so far.
+    pub fn warmup_skipped(&self) -> u64 {
+        self.warmup_skipped
+    }
+
+    /// Negative latencies seen (local clock ahead of the server).
+    pub fn negative_latency(&self) -> u64 {
+        self.negative_latency
+    }
+
+    /// Clears all samples and counters, keeping the window size and sample mode.
+    pub fn reset(&mut self) {
+        *self = self.empty_like();
+    }
+
+    /// New, empty stats with the same configuration (window size, sample
+    /// mode, percentile method, histogram, id thresholds).
+    pub fn empty_like(&self) -> Self {
+        let mut fresh = Self::new(self.max_samples);
+        fresh.set_sample_mode(self.sample_mode);
+        fresh.percentile_method = self.percentile_method;
+        fresh.histogram_spec = self.histogram_spec;
+        fresh.keep_negative = self.keep_negative;
+        // Só o que falta: um reset depois do aquecimento não aquece de novo
+        fresh.warmup_remaining = self.warmup_remaining;
+        fresh.id_reset_threshold = self.id_reset_threshold;
+        fresh.ooo_tolerance = self.ooo_tolerance;
+        fresh.gap_fill_window = self.gap_fill_window;
+        fresh
+    }
+
+    /// Resets only min/max (per-interval extremes); count, average, samples
+    /// and integrity counters are kept.
+    pub fn reset_extremes(&mut self) {
+        self.min_latency = i64::MAX;
+        self.max_latency = i64::MIN;
+    }
+
+    /// (min, max) recorded since the previous call, None if nothing was;
+    /// unlike `reset_extremes`, the run's min/max are kept.
+    pub fn take_tick_extremes(&mut self) -> Option<(i64, i64)> {
+        let extremes = (self.tick_min <= self.tick_max).then_some((self.tick_min, self.tick_max));
+        self.tick_min = i64::MAX;
+        self.tick_max = i64::MIN;
+        extremes
+    }
+
+    /// Resizes the sample buffer, dropping the oldest samples if it shrinks
+    /// (no effect on the histogram mode, which keeps no samples).
+    pub fn set_max_samples(&mut self, max_samples: usize) {
+        self.max_samples = max_samples.max(1);
+        while self.recent_latencies.len() > self.max_samples {
+            self.recent_latencies.pop_front();
+        }
+    }
+
+    /// Aggregates skipped in aggTrade mode (gaps in "a").
+    pub fn missed_aggregates(&self) -> u64 {
+        self.missed_aggregates
+    }
+
+    /// Returns (gaps_detected, out_of_order, gap-size histogram); out_of_order
+    /// honours `OOO_TOLERANCE` (see `out_of_order_strict`).
+    pub fn integrity(&self) -> (u64, u64, [u64; 4]) {
+        (self.gaps_detected, self.out_of_order, self.gap_sizes)
+    }
+
+    /// Returns (count, avg_us, min_us, max_us) without touching the sample window.
+    /// O(1): safe to call from the realtime display between messages.
+    pub fn get_basic(&self) -> Option<(u64, f64, i64, i64)> {
+        if self.count == 0 {
+            return None;
+        }
+        let avg = self.total_latency as f64 / self.count as f64;
+        Some((self.count, avg, self.min_latency, self.max_latency))
+    }
+
+    /// Current sample set (window or reservoir), in storage order; empty in
+    /// histogram mode.
+    pub fn samples(&self) -> impl Iterator<Item = i64> + '_ {
+        self.recent_latencies.iter().copied()
+    }
+
+    /// Percentiles `qs` (0.0..=1.0) over the sample set; empty if no samples.
+    pub fn percentiles(&self, qs: &[f64]) -> Vec<i64> {
+        if let Some(hist) = &self.log_histogram {
+            return hist.percentiles(qs, self.percentile_method, self.min_latency, self.max_latency);
+        }
+        if self.recent_latencies.is_empty() {
+            return Vec::new();
+        }
+        let mut sorted: Vec<i64> = self.recent_latencies.iter().copied().collect();
+        sorted.sort_unstable();
+        qs.iter()
+            .map(|&q| percentile(&sorted, q, self.percentile_method))
+            .collect()
+    }
+
+    /// Histogram of the sample set using the configured `HistogramSpec`
+    /// (histogram mode: the whole run, re-bucketed from the log histogram).
+    pub fn histogram(&self) -> Histogram {
+        let bounds = self.histogram_spec.bounds();
+        let floor_us = self.histogram_spec.floor_us();
+        let mut counts = vec![0u64; bounds.len()];
+        let (mut underflow, mut overflow) = (0, 0);
+        let values: Box<dyn Iterator<Item = (i64, u64)> + '_> = match &self.log_histogram {
+            Some(hist) => Box::new(hist.buckets()),
+            None => Box::new(self.recent_latencies.iter().map(|&v| (v, 1))),
+        };
+        for (v, n) in values {
+            if v <= floor_us {
+                underflow += n;
+                continue;
+            }
+            match bounds.partition_point(|&b| b < v) {
+                i if i == bounds.len() => overflow += n,
+                i => counts[i] += n,
+            }
+        }
+        Histogram {
+            buckets: bounds.into_iter().zip(counts).collect(),
+            underflow,
+            floor_us,
+            overflow,
+        }
+    }
+
+    /// Returns (count, avg_us, min_us, max_us, p50_us, p95_us, p99_us).
+    /// Returns None if no sample was recorded yet.
+    pub fn get(&self) -> Option<LatencySummary> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let avg = self.total_latency as f64 / self.count as f64;
+        let (p50, p95, p99) = match &self.log_histogram {
+            Some(hist) => {
+                let p = hist.percentiles(
+                    &[0.50, 0.95, 0.99],
+                    self.percentile_method,
+                    self.min_latency,
+                    self.max_latency,
+                );
+                (p[0], p[1], p[2])
+            }
+            None => {
+                let mut sorted: Vec<i64> = self.recent_latencies.iter().copied().collect();
+                sorted.sort_unstable();
+                (
+                    percentile(&sorted, 0.50, self.percentile_method),
+                    percentile(&sorted, 0.95, self.percentile_method),
+                    percentile(&sorted, 0.99, self.percentile_method),
+                )
+            }
+        };
+
+        Some((
+            self.count,
+            avg,
+            self.min_latency,
+            self.max_latency,
+            p50,
+            p95,
+            p99,
+        ))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Estatísticas por símbolo (vários símbolos numa conexão combinada)
+// ---------------------------------------------------------------------------
+
+/// One `LatencyStats` per symbol, so interleaved symbols each keep their own
+/// trade_id sequence (a global sequence check would see gaps everywhere).
+///
+/// Sem Mutex/DashMap: o coletor roda numa thread só e é o único dono. Busca
+/// linear: poucos símbolos por conexão, e a ordem da linha de comando fica
+/// preservada no display.
+pub struct SymbolStats {
+    by_symbol: Vec<(String, LatencyStats)>,
+}
+
+impl SymbolStats {
+    /// `symbols` in display order; each gets a copy of `template`'s configuration.
+    pub fn new(symbols: &[String], template: &LatencyStats) -> Self {
+        Self {
+            by_symbol: symbols
+                .iter()
+                .map(|s| (s.to_uppercase(), template.empty_like()))
+                .collect(),
+        }
+    }
+
+    /// Stats of `symbol` (case-insensitive, no allocation).
+    #[inline(always)]
+    pub fn get_mut(&mut self, symbol: &[u8]) -> Option<&mut LatencyStats> {
+        self.by_symbol
+            .iter_mut()
+            .find(|(s, _)| s.as_bytes().eq_ignore_ascii_case(symbol))
+            .map(|(_, stats)| stats)
+    }
+
+    /// Records one trade of `symbol`. False if the symbol is not tracked.
+    #[inline(always)]
+    pub fn update(&mut self, symbol: &[u8], trade_id: u64, latency_us: i64) -> bool {
+        match self.get_mut(symbol) {
+            Some(stats) => {
+                stats.update(trade_id, latency_us);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Per-symbol `get()` tuples (count, avg, min, max, p50, p95, p99).
+    pub fn get_all(&self) -> BTreeMap<&str, Option<LatencySummary>> {
+        self.by_symbol
+            .iter()
+            .map(|(s, stats)| (s.as_str(), stats.get()))
+            .collect()
+    }
+
+    /// (symbol, stats) in command-line order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &LatencyStats)> {
+        self.by_symbol.iter().map(|(s, stats)| (s.as_str(), stats))
+    }
+
+    /// (symbol, stats) in command-line order, mutable.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut LatencyStats)> {
+        self.by_symbol.iter_mut().map(|(s, stats)| (s.as_str(), stats))
+    }
+
+    /// Connection boundary: every symbol re-seeds its sequence.
+    pub fn mark_resync(&mut self) {
+        for (_, stats) in &mut self.by_symbol {
+            stats.mark_resync();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for (_, stats) in &mut self.by_symbol {
+            stats.reset();
+        }
+    }
+
+    pub fn set_max_samples(&mut self, max_samples: usize) {
+        for (_, stats) in &mut self.by_symbol {
+            stats.set_max_samples(max_samples);
+        }
+    }
+
+    pub fn reset_extremes(&mut self) {
+        for (_, stats) in &mut self.by_symbol {
+            stats.reset_extremes();
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Série por segundo (TIMESERIES_FILE)
+// ---------------------------------------------------------------------------
+
+/// One second of the per-second series.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SecondRow {
+    pub unix_sec: i64,
+    pub count: u64,
+    /// None for a second without trades.
+    pub avg_us: Option<f64>,
+    pub p99_us: Option<i64>,
+    /// Trades per second of the part of the second the run covered (the
+    /// first and last seconds are partial).
+    pub tps: f64,
+}
+
+/// Rolling window keyed by the receive second: trades of one wall-clock
+/// second are aggregated and emitted as a `SecondRow` once a later second
+/// starts. Seconds without trades still get a row (count 0), so a hole in
+/// the series means the collector stopped, not the market.
+///
+/// A receive timestamp that goes back to an already closed second (clock
+/// regression) is counted in the current one.
+pub struct SecondSeries {
+    second: i64,
+    /// Start of the covered part of `second` (run start on the first one).
+    covered_from_us: i64,
+    /// Latencies of `second` only; histogram mode (fixed memory).
+    window: LatencyStats,
+}
+
+impl SecondSeries {
+    /// `template`: the run's stats (percentile method, negative latency and
+    /// warmup policy); `start_us`: start of the collection.
+    pub fn new(template: &LatencyStats, start_us: i64) -> Self {
+        let mut window = template.empty_like();
+        window.set_sample_mode(SampleMode::Histogram);
+        Self {
+            second: start_us.div_euclid(1_000_000),
+            covered_from_us: start_us,
+            window,
+        }
+    }
+
+    /// Adds one trade received at `recv_ts_us`; rows of the seconds closed
+    /// before it are appended to `rows`.
+    #[inline(always)]
+    pub fn record(&mut self, recv_ts_us: i64, latency_us: i64, rows: &mut Vec<SecondRow>) {
+        self.advance(recv_ts_us, rows);
+        self.window.record(latency_us);
+    }
+
+    /// Closes every second that ended by `now_us` (without trades too).
+    pub fn advance(&mut self, now_us: i64, rows: &mut Vec<SecondRow>) {
+        let now_sec = now_us.div_euclid(1_000_000);
+        while self.second < now_sec {
+            let end_us = (self.second + 1) * 1_000_000;
+            rows.push(self.close(end_us));
+            self.second += 1;
+            self.covered_from_us = end_us;
+        }
+    }
+
+    /// End of the run: closes the remaining seconds, the last one partial.
+    pub fn finish(&mut self, end_us: i64, rows: &mut Vec<SecondRow>) {
+        self.advance(end_us, rows);
+        if end_us > self.covered_from_us {
+            rows.push(self.close(end_us));
+            self.covered_from_us = end_us;
+        }
+    }
+
+    fn close(&mut self, end_us: i64) -> SecondRow {
+        let covered_s = (end_us - self.covered_from_us) as f64 / 1_000_000.0;
+        let basic = self.window.get_basic();
+        let count = basic.map_or(0, |(count, _, _, _)| count);
+        let row = SecondRow {
+            unix_sec: self.second,
+            count,
+            avg_us: basic.map(|(_, avg, _, _)| avg),
+            p99_us: basic.and_then(|_| self.window.percentiles(&[0.99]).first().copied()),
+            tps: count as f64 / covered_s.max(0.001),
+        };
+        self.window.reset();
+        row
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Processing Time (instrumentação do próprio hot path)
+// ---------------------------------------------------------------------------
+
+/// Max slow iterations kept for the final report.
+const MAX_SLOW_SAMPLES: usize = 20;
+
+/// Messages per saturation window.
+const CPU_WINDOW_MSGS: u64 = 1000;
+/// Busy fraction (processing / (processing + idle)) above which the collector
+/// is considered CPU-bound: messages are already waiting when we read them.
+const CPU_BOUND_UTILIZATION: f64 = 0.9;
+
+/// Per-message processing time (recv timestamp -> end of loop body), in nanoseconds.
+///
+/// Separa "o trade chegou atrasado" de "nós demoramos para processá-lo".
+pub struct ProcessingStats {
+    times: LatencyStats,
+    threshold_ns: i64,
+    slow_count: u64,
+    slow_samples: Vec<(u64, i64)>, // (trade_id, processing_ns)
+
+    // --- Saturação: tempo ocupado vs ocioso entre mensagens ---
+    last_done: Option<Instant>,
+    window_busy_ns: i64,
+    window_idle_ns: i64,
+    window_msgs: u64,
+    cpu_bound: bool,
+    cpu_bound_windows: u64,
+    max_utilization: f64,
+}
+
+impl ProcessingStats {
+    pub fn new(max_samples: usize, threshold_us: u64) -> Self {
+        Self {
+            times: LatencyStats::new(max_samples),
+            threshold_ns: threshold_us as i64 * 1000,
+            slow_count: 0,
+            slow_samples: Vec::with_capacity(MAX_SLOW_SAMPLES),
+            last_done: None,
+            window_busy_ns: 0,
+            window_idle_ns: 0,
+            window_msgs: 0,
+            cpu_bound: false,
+            cpu_bound_windows: 0,
+            max_utilization: 0.0,
+        }
+    }
+
+    /// Records one message received at `recv_instant` and finished now.
+    /// Returns the window utilization when the collector just became CPU-bound.
+    #[inline(always)]
+    pub fn update(&mut self, trade_id: u64, recv_instant: Instant) -> Option<f64> {
+        let done = Instant::now();
+        let processing_ns = done.duration_since(recv_instant).as_nanos() as i64;
+        // Ocioso = esperando o próximo read; ~0 quando a mensagem já estava na fila
+        if let Some(prev) = self.last_done {
+            self.window_idle_ns += recv_instant.saturating_duration_since(prev).as_nanos() as i64;
+        }
+        self.last_done = Some(done);
+        self.window_busy_ns += processing_ns;
+        self.window_msgs += 1;
+        self.record_time(trade_id, processing_ns);
+
+        if self.window_msgs < CPU_WINDOW_MSGS {
+            return None;
+        }
+        let total = (self.window_busy_ns + self.window_idle_ns).max(1) as f64;
+        let utilization = self.window_busy_ns as f64 / total;
+        (self.window_busy_ns, self.window_idle_ns, self.window_msgs) = (0, 0, 0);
+        self.max_utilization = self.max_utilization.max(utilization);
+
+        let was_bound = self.cpu_bound;
+        self.cpu_bound = utilization > CPU_BOUND_UTILIZATION;
+        if self.cpu_bound {
+            self.cpu_bound_windows += 1;
+        }
+        (self.cpu_bound && !was_bound).then_some(utilization)
+    }
+
+    #[inline(always)]
+    fn record_time(&mut self, trade_id: u64, processing_ns: i64) {
+        self.times.record(processing_ns);
+        if processing_ns > self.threshold_ns {
+            self.slow_count += 1;
+            if self.slow_samples.len() < MAX_SLOW_SAMPLES {
+                self.slow_samples.push((trade_id, processing_ns));
+            }
+        }
+    }
+
+    /// Average processing time per message (ns), if any was recorded.
+    pub fn avg_ns(&self) -> Option<f64> {
+        self.times.get_basic().map(|(_, avg, _, _)| avg)
+    }
+
+    pub fn report(&self) {
+        let Some((count, avg, min, max, p50, p95, p99)) = self.times.get() else {
+            return;
+        };
+        eprintln!("\n=== Processing Time (per message) ===");
+        eprintln!("Samples: {}", count);
+        eprintln!("Min:     {:.1}µs", min as f64 / 1000.0);
+        eprintln!("Max:     {:.1}µs", max as f64 / 1000.0);
+        eprintln!("Avg:     {:.1}µs", avg / 1000.0);
+        eprintln!("Median:  {:.1}µs", p50 as f64 / 1000.0);
+        eprintln!("P95:     {:.1}µs", p95 as f64 / 1000.0);
+        eprintln!("P99:     {:.1}µs", p99 as f64 / 1000.0);
+        eprintln!(
+            "Slow (>{}µs): {} ({:.3}%)",
+            self.threshold_ns / 1000,
+            self.slow_count,
+            self.slow_count as f64 * 100.0 / count as f64
+        );
+        for (trade_id, ns) in &self.slow_samples {
+            eprintln!("  trade_id={} took {:.1}µs", trade_id, *ns as f64 / 1000.0);
+        }
+        if self.slow_count > self.slow_samples.len() as u64 {
+            eprintln!("  ... ({} more)", self.slow_count - self.slow_samples.len() as u64);
+        }
+        eprintln!(
+            "Utilization: max {:.1}% per {} messages | CPU-bound windows: {}",
+            self.max_utilization * 100.0,
+            CPU_WINDOW_MSGS,
+            self.cpu_bound_windows
+        );
+        if self.cpu_bound_windows > 0 {
+            eprintln!("⚠️  Collector was CPU-bound at times: latency measurements may be inflated");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: i64 = 1_000_000;
+
+    #[test]
+    fn second_series_rows_across_boundaries() {
+        let mut series = SecondSeries::new(&LatencyStats::new(100), 10 * SEC + SEC / 2);
+        let mut rows = Vec::new();
+        // segundo 10 (meio segundo coberto): 2 trades
+        series.record(10 * SEC + 600_000, 100, &mut rows);
+        series.record(10 * SEC + 900_000, 300, &mut rows);
+        assert!(rows.is_empty());
+        // segundo 11 sem trades; segundo 12: 1 trade
+        series.record(12 * SEC + 1, 50, &mut rows);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].unix_sec, 10);
+        assert_eq!(rows[0].count, 2);
+        assert_eq!(rows[0].avg_us, Some(200.0));
+        assert!(rows[0].p99_us.unwrap() >= 295 && rows[0].p99_us.unwrap() <= 300);
+        assert!((rows[0].tps - 4.0).abs() < 1e-9);
+        assert_eq!(rows[1], SecondRow { unix_sec: 11, count: 0, avg_us: None, p99_us: None, tps: 0.0 });
+        // relógio voltou para um segundo já fechado: conta no atual
+        series.record(11 * SEC, 70, &mut rows);
+        assert_eq!(rows.len(), 2);
+        // fim no meio do segundo 12 (um quarto coberto)
+        series.finish(12 * SEC + SEC / 4, &mut rows);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[2].unix_sec, 12);
+        assert_eq!(rows[2].count, 2);
+        assert_eq!(rows[2].avg_us, Some(60.0));
+        assert!((rows[2].tps - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn second_series_tick_closes_idle_seconds() {
+        let mut series = SecondSeries::new(&LatencyStats::new(100), 5 * SEC);
+        let mut rows = Vec::new();
+        series.advance(5 * SEC + 999_999, &mut rows);
+        assert!(rows.is_empty());
+        series.advance(8 * SEC, &mut rows);
+        assert_eq!(rows.iter().map(|r| r.unix_sec).collect::<Vec<_>>(), vec![5, 6, 7]);
+        assert!(rows.iter().all(|r| r.count == 0));
+        // fim exatamente na fronteira: não sobra segundo parcial
+        series.finish(8 * SEC, &mut rows);
+        assert_eq!(rows.len(), 3);
+    }
+
+    fn stats_with(method: PercentileMethod, values: &[i64]) -> LatencyStats {
+        let mut stats = LatencyStats::new(1000);
+        stats.set_percentile_method(method);
+        for &v in values {
+            stats.record(v);
+        }
+        stats
+    }
+
+    #[test]
+    fn percentile_nearest_and_linear() {
+        let sorted: Vec<i64> = (1..=10).map(|v| v * 10).collect();
+        // nearest-rank: x[ceil(q·n) - 1], sempre um valor observado
+        assert_eq!(percentile(&sorted, 0.0, PercentileMethod::Nearest), 10);
+        assert_eq!(percentile(&sorted, 0.5, PercentileMethod::Nearest), 50);
+        assert_eq!(percentile(&sorted, 0.95, PercentileMethod::Nearest), 100);
+        // linear (tipo 7): h = (n-1)·q, interpolado e arredondado
+        assert_eq!(percentile(&sorted, 0.0, PercentileMethod::Linear), 10);
+        assert_eq!(percentile(&sorted, 0.5, PercentileMethod::Linear), 55);
+        assert_eq!(percentile(&sorted, 0.9, PercentileMethod::Linear), 91);
+        assert_eq!(percentile(&sorted, 1.0, PercentileMethod::Linear), 100);
+        // q fora de [0, 1] é limitado
+        assert_eq!(percentile(&sorted, 1.5, PercentileMethod::Linear), 100);
+        assert_eq!(percentile(&[7], 0.99, PercentileMethod::Linear), 7);
+    }
+
+    #[test]
+    fn percentile_method_drives_get() {
+        let values: Vec<i64> = (1..=10).map(|v| v * 10).collect();
+        let nearest = stats_with(PercentileMethod::Nearest, &values).get().unwrap();
+        assert_eq!((nearest.4, nearest.5, nearest.6), (50, 100, 100));
+        let linear = stats_with(PercentileMethod::Linear, &values).get().unwrap();
+        // p95: h = 9·0,95 = 8,549999… em f64 → 95,49… → 95
+        assert_eq!((linear.4, linear.5, linear.6), (55, 95, 99));
+    }
+
+    #[test]
+    fn log_histogram_bucket_edges() {
+        // Abaixo de 2·SUB cada valor tem bucket próprio
+        for v in 0..2 * LOG_HIST_SUB {
+            assert_eq!(LogHistogram::index(v), v as usize);
+            assert_eq!(LogHistogram::value(v as usize), v);
+        }
+        // Em cada potência de 2: buckets contíguos, largura < v/256, valor dentro do bucket
+        for bits in LOG_HIST_SUB_BITS + 1..40 {
+            let p = 1u64 << bits;
+            assert_eq!(LogHistogram::index(p), LogHistogram::index(p - 1) + 1, "edge at 2^{}", bits);
+            for v in [p - 1, p, p + 1, p + p / 3] {
+                let mid = LogHistogram::value(LogHistogram::index(v));
+                assert!(v.abs_diff(mid) * LOG_HIST_SUB < v, "v={} mid={}", v, mid);
+                assert_eq!(LogHistogram::index(mid), LogHistogram::index(v));
+            }
+        }
+        // Acima de LOG_HIST_MAX tudo cai no último bucket
+        assert_eq!(LogHistogram::index(u64::MAX), LogHistogram::index(LOG_HIST_MAX));
+        assert_eq!(LOG_HISTOGRAM_BYTES, (LogHistogram::index(LOG_HIST_MAX) + 1) * 8);
+    }
+
+    #[test]
+    fn histogram_mode_percentiles_track_exact_ones() {
+        for method in [PercentileMethod::Nearest, PercentileMethod::Linear] {
+            // Valores < 512: buckets exatos, mesmo resultado das amostras
+            let small: Vec<i64> = (0..1000).map(|i| (i * 37) % 500).collect();
+            let mut hist = stats_with(method, &[]);
+            hist.set_sample_mode(SampleMode::Histogram);
+            small.iter().for_each(|&v| hist.record(v));
+            let qs = [0.0, 0.5, 0.95, 0.99, 1.0];
+            assert_eq!(hist.percentiles(&qs), stats_with(method, &small).percentiles(&qs));
+
+            // Valores grandes: erro relativo < 1/256
+            let large: Vec<i64> = (1..=1000).map(|i| i * 1_003).collect();
+            let mut hist = stats_with(method, &[]);
+            hist.set_sample_mode(SampleMode::Histogram);
+            large.iter().for_each(|&v| hist.record(v));
+            let exact = stats_with(method, &large).percentiles(&qs);
+            for (approx, exact) in hist.percentiles(&qs).into_iter().zip(exact) {
+                assert!(approx.abs_diff(exact) * 256 < exact as u64, "{} vs {}", approx, exact);
+            }
+            // min/max exatos; percentis só limitados a eles
+            let (_, _, min, max, ..) = hist.get().unwrap();
+            assert_eq!((min, max), (1_003, 1_003_000));
+            assert_eq!(hist.percentiles(&[0.0]), vec![1_003]);
+        }
+    }
+
+    #[test]
+    fn histogram_percentiles_match_sort_on_heavy_tail() {
+        // 100k latências com cauda pesada (Pareto α=1.5 sobre 800µs): o grosso
+        // em ~1ms, p99 dezenas de ms, máximo em segundos
+        let mut x = 0x2545_F491_4F6C_DD1Du64;
+        let values: Vec<i64> = (0..100_000)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                let u = ((x >> 11) + 1) as f64 / (1u64 << 53) as f64;
+                (800.0 / u.powf(1.0 / 1.5)) as i64
+            })
+            .collect();
+        let mut sorted = values.clone();
+        sorted.sort_unstable();
+        assert!(sorted[sorted.len() - 1] > 1_000_000, "tail too light");
+
+        for method in [PercentileMethod::Nearest, PercentileMethod::Linear] {
+            let mut hist = LatencyStats::new(10);
+            hist.set_percentile_method(method);
+            hist.set_sample_mode(SampleMode::Histogram);
+            values.iter().for_each(|&v| hist.record(v));
+            let qs = [0.5, 0.95, 0.99];
+            for (q, approx) in qs.into_iter().zip(hist.percentiles(&qs)) {
+                let exact = percentile(&sorted, q, method);
+                assert!(
+                    approx.abs_diff(exact) * 256 <= exact as u64,
+                    "{:?} q={}: {} vs {}",
+                    method,
+                    q,
+                    approx,
+                    exact
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_rank_index_has_no_off_by_one() {
+        let sorted: Vec<i64> = (1..=100).collect();
+        for (q, expected) in [(0.01, 1), (0.5, 50), (0.95, 95), (0.99, 99), (1.0, 100)] {
+            assert_eq!(percentile(&sorted, q, PercentileMethod::Nearest), expected, "q={}", q);
+        }
+        // q·n logo acima de um inteiro sobe para o próximo rank
+        assert_eq!(percentile(&sorted, 0.501, PercentileMethod::Nearest), 51);
+        assert_eq!(percentile(&[3, 4], 0.5, PercentileMethod::Nearest), 3);
+        assert_eq!(percentile(&[3, 4], 0.51, PercentileMethod::Nearest), 4);
+    }
+
+    #[test]
+    fn negative_latency_is_counted_apart() {
+        let mut stats = stats_with(PercentileMethod::Nearest, &[-5, 10, 20]);
+        assert_eq!(stats.negative_latency(), 1);
+        assert_eq!(stats.count(), 2);
+        assert_eq!(stats.recorded(), 3);
+        assert_eq!(stats.get_basic(), Some((2, 15.0, 10, 20)));
+        assert_eq!(stats.percentiles(&[0.0]), vec![10]);
+
+        // KEEP_NEGATIVE_LATENCY=1: entra em tudo e continua contada
+        stats.reset();
+        stats.set_keep_negative(true);
+        for v in [-5, 10, 20] {
+            stats.record(v);
+        }
+        assert_eq!(stats.negative_latency(), 1);
+        assert_eq!(stats.recorded(), 3);
+        assert_eq!(stats.get_basic(), Some((3, 25.0 / 3.0, -5, 20)));
+        assert_eq!(stats.percentiles(&[0.0]), vec![-5]);
+
+        // Histograma: lado negativo espelhado
+        let mut hist = stats.empty_like();
+        hist.set_sample_mode(SampleMode::Histogram);
+        for v in [-300, -5, 10, 20] {
+            hist.record(v);
+        }
+        assert_eq!(hist.percentiles(&[0.0, 0.5, 1.0]), vec![-300, -5, 20]);
+    }
+
+    #[test]
+    fn totals_do_not_overflow() {
+        // Soma em i64 estouraria na segunda amostra
+        let stats = stats_with(PercentileMethod::Nearest, &[i64::MAX; 4]);
+        assert_eq!(stats.get_basic(), Some((4, i64::MAX as f64, i64::MAX, i64::MAX)));
+
+        let mut stats = LatencyStats::new(10);
+        stats.set_keep_negative(true);
+        for v in [i64::MAX, i64::MIN + 1, i64::MAX, i64::MIN + 1] {
+            stats.record(v);
+        }
+        let (count, avg, min, max) = stats.get_basic().unwrap();
+        assert_eq!((count, avg, min, max), (4, 0.0, i64::MIN + 1, i64::MAX));
+    }
+
+    #[test]
+    fn mean_stays_accurate_over_10m_samples() {
+        // Pares m ± d: média exata m. A soma (~2·10¹⁶µs) já passa do que um
+        // acumulador f64 guarda exato (2⁵³); a média tem de sair exata mesmo assim
+        const MEAN: i64 = 2_000_000_000;
+        let mut x = 0x9E37_79B9_7F4A_7C15u64;
+        let mut stats = LatencyStats::new(10);
+        for _ in 0..5_000_000 {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            let d = (x % MEAN as u64) as i64;
+            stats.record(MEAN + d);
+            stats.record(MEAN - d);
+        }
+        let (count, avg, ..) = stats.get_basic().unwrap();
+        assert_eq!(count, 10_000_000);
+        assert_eq!(avg, MEAN as f64);
+        let welford = stats.welford_mean;
+        assert!((welford - MEAN as f64).abs() < 1e-6 * MEAN as f64, "{}", welford);
+    }
+
+    #[test]
+    fn welford_matches_two_pass_stddev() {
+        assert_eq!(stats_with(PercentileMethod::Nearest, &[42]).stddev_us(), None);
+
+        // Média grande e variância pequena: onde a fórmula ingênua perde precisão
+        let mut x = 0x9E37_79B9_7F4A_7C15u64;
+        let values: Vec<i64> = (0..10_000)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                1_000_000_000 + (x % 2_000) as i64
+            })
+            .collect();
+        // Janela pequena: o desvio cobre a execução inteira, não as amostras
+        let mut stats = LatencyStats::new(10);
+        values.iter().for_each(|&v| stats.record(v));
+
+        let n = values.len() as f64;
+        let mean = values.iter().map(|&v| v as f64).sum::<f64>() / n;
+        let m2: f64 = values.iter().map(|&v| (v as f64 - mean).powi(2)).sum();
+        let two_pass = (m2 / (n - 1.0)).sqrt();
+        let welford = stats.stddev_us().unwrap();
+        assert!((welford - two_pass).abs() < 1e-6 * two_pass, "{} vs {}", welford, two_pass);
+    }
+
+    fn feed(stats: &mut LatencyStats, ids: &[u64]) {
+        for &id in ids {
+            stats.update(id, 100);
+        }
+    }
+
+    #[test]
+    fn late_ids_fill_open_gaps() {
+        let mut stats = LatencyStats::new(100);
+        feed(&mut stats, &[1, 2, 5]);
+        assert_eq!(stats.integrity(), (2, 0, [0, 1, 0, 0]));
+        // 3 atrasado: sai dos gaps, o gap de tamanho 2 segue aberto
+        feed(&mut stats, &[3]);
+        assert_eq!(stats.integrity(), (1, 1, [0, 1, 0, 0]));
+        // 4 fecha o gap: some do histograma de tamanhos
+        feed(&mut stats, &[4]);
+        assert_eq!(stats.integrity(), (0, 2, [0, 0, 0, 0]));
+        assert_eq!(stats.late_filled(), 2);
+        // Repetido não preenche nada
+        feed(&mut stats, &[4]);
+        assert_eq!(stats.integrity().0, 0);
+        assert_eq!(stats.late_filled(), 2);
+    }
+
+    #[test]
+    fn late_ranges_fill_partially() {
+        let mut stats = LatencyStats::new(100);
+        stats.update_range(1, 1, 100);
+        stats.update_range(10, 12, 100);
+        assert_eq!(stats.integrity(), (8, 0, [0, 1, 0, 0]));
+        // Faixa atrasada no meio do gap: divide o que falta em dois
+        stats.update_range(4, 6, 100);
+        assert_eq!(stats.integrity(), (5, 1, [0, 1, 0, 0]));
+        stats.update_range(2, 3, 100);
+        stats.update_range(7, 9, 100);
+        assert_eq!(stats.integrity(), (0, 3, [0, 0, 0, 0]));
+        assert_eq!(stats.late_filled(), 8);
+    }
+
+    #[test]
+    fn overlapping_range_moves_the_highest_id() {
+        let mut stats = LatencyStats::new(100);
+        stats.update_range(1, 10, 100);
+        stats.update_range(5, 15, 100);
+        // Começa logo depois do fim da faixa sobreposta: sem gap
+        stats.update_range(16, 20, 100);
+        let (gaps, _, gap_sizes) = stats.integrity();
+        assert_eq!(gaps, 0);
+        assert_eq!(gap_sizes, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn gaps_beyond_the_fill_window_are_final() {
+        let mut stats = LatencyStats::new(100);
+        stats.set_gap_fill_window(10);
+        feed(&mut stats, &[1, 3, 20]);
+        assert_eq!(stats.integrity().0, 17);
+        // 2 chega 18 ids atrás do maior: fora da janela, o gap fica
+        feed(&mut stats, &[2]);
+        assert_eq!(stats.integrity(), (17, 1, [1, 0, 1, 0]));
+        // 15 ainda está dentro da janela
+        feed(&mut stats, &[15]);
+        assert_eq!(stats.integrity().0, 16);
+        assert_eq!(stats.late_filled(), 1);
+
+        // GAP_FILL_WINDOW=0: gaps finais assim que vistos
+        let mut stats = LatencyStats::new(100);
+        stats.set_gap_fill_window(0);
+        feed(&mut stats, &[1, 3, 2]);
+        assert_eq!(stats.integrity(), (1, 1, [1, 0, 0, 0]));
+        assert_eq!(stats.late_filled(), 0);
+    }
+
+    #[test]
+    fn warmup_skips_latency_but_not_sequence() {
+        let mut stats = LatencyStats::new(100);
+        stats.set_warmup(3);
+        stats.update(1, 5_000);
+        stats.update(2, 5_000);
+        stats.update(4, 5_000);
+        stats.update(5, 10);
+        stats.update(6, 20);
+        assert_eq!(stats.warmup_skipped(), 3);
+        assert_eq!(stats.recorded(), 5);
+        assert_eq!(stats.get_basic(), Some((2, 15.0, 10, 20)));
+        // O gap durante o aquecimento conta
+        assert_eq!(stats.integrity().0, 1);
+
+        // Reset depois do aquecimento não aquece de novo; no meio, só o que falta
+        stats.reset();
+        stats.record(7);
+        assert_eq!(stats.count(), 1);
+        let mut stats = LatencyStats::new(100);
+        stats.set_warmup(2);
+        stats.record(1);
+        let mut fresh = stats.empty_like();
+        fresh.record(1);
+        fresh.record(2);
+        assert_eq!((fresh.warmup_skipped(), fresh.count()), (1, 1));
+    }
+
+    #[test]
+    fn depth_sequence_breaks() {
+        let mut depth = DepthSequence::new();
+        // Primeiro evento só define a base
+        assert_eq!(depth.check(100, 109, true), None);
+        assert_eq!(depth.check(110, 119, false), None);
+        // U adiante: updates perdidos
+        assert_eq!(depth.check(125, 130, false), Some(120));
+        // U atrás: sobreposto/repetido
+        assert_eq!(depth.check(128, 135, false), Some(131));
+        assert_eq!(depth.check(136, 140, false), None);
+        assert_eq!(depth.breaks(), (2, 1, 1));
+        assert_eq!(depth.first_break(), Some((120, 125)));
+        // Reconexão: nova base sem contar quebra
+        assert_eq!(depth.check(900, 910, true), None);
+        assert_eq!(depth.breaks().0, 2);
+        assert_eq!(depth.events(), 6);
+    }
+}
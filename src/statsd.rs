@@ -0,0 +1,128 @@
+//! Envio opcional de métricas para statsd/DogStatsD por UDP (STATSD_ADDR).
+//!
+//! Um pacote por tick do display (não um por trade), com as métricas do
+//! intervalo separadas por `\n`:
+//!   binance.trades   (count)  trades no intervalo
+//!   binance.gaps     (count)  ids faltando no intervalo
+//!   binance.latency  (timing) média do intervalo em ms, com `@1/trades`
+//!                             para o agregador contar um valor por trade
+//! Tags DogStatsD: `machine_id` e `symbol`. Fire-and-forget: falha de envio
+//! só é contada, nunca interrompe a coleta.
+
+use std::net::UdpSocket;
+
+use crate::stats::LatencyStats;
+
+const DEFAULT_PREFIX: &str = "binance";
+
+pub struct StatsdSink {
+    socket: UdpSocket,
+    addr: String,
+    prefix: String,
+    /// `|#machine_id:...,symbol:...`
+    tags: String,
+    last_count: u64,
+    last_total_us: f64,
+    last_gaps: u64,
+    sent: u64,
+    failed: u64,
+}
+
+/// DogStatsD tag values cannot contain `,` `|` `#` or `:`.
+fn tag_value(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if matches!(c, ',' | '|' | '#' | ':') || c.is_whitespace() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+impl StatsdSink {
+    /// None when STATSD_ADDR is unset; the address is resolved once here.
+    pub fn from_env(machine_id: &str, symbol: &str) -> Option<Self> {
+        let addr = std::env::var("STATSD_ADDR").ok()?;
+        let socket = match UdpSocket::bind("0.0.0.0:0").and_then(|s| {
+            s.connect(&addr)?;
+            s.set_nonblocking(true)?;
+            Ok(s)
+        }) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("  WARNING: STATSD_ADDR {}: {}; statsd disabled", addr, e);
+                return None;
+            }
+        };
+        let prefix = std::env::var("STATSD_PREFIX").unwrap_or_else(|_| DEFAULT_PREFIX.to_string());
+        eprintln!("StatsD:     udp://{} ({}.*)", addr, prefix);
+        Some(Self {
+            socket,
+            addr,
+            prefix,
+            tags: format!(
+                "|#machine_id:{},symbol:{}",
+                tag_value(machine_id),
+                tag_value(symbol)
+            ),
+            last_count: 0,
+            last_total_us: 0.0,
+            last_gaps: 0,
+            sent: 0,
+            failed: 0,
+        })
+    }
+
+    /// Sends the aggregate for the interval since the previous call.
+    pub fn emit(&mut self, stats: &LatencyStats) {
+        let (count, total_us) = stats
+            .get_basic()
+            .map(|(count, avg, _, _)| (count, avg * count as f64))
+            .unwrap_or_default();
+        let gaps = stats.integrity().0;
+        // reset pelo control socket: contadores recomeçam do zero
+        if count < self.last_count {
+            self.last_count = 0;
+            self.last_total_us = 0.0;
+            self.last_gaps = 0;
+        }
+        let trades = count - self.last_count;
+        let new_gaps = gaps.saturating_sub(self.last_gaps);
+
+        let mut packet = format!(
+            "{p}.trades:{}|c{t}\n{p}.gaps:{}|c{t}",
+            trades,
+            new_gaps,
+            p = self.prefix,
+            t = self.tags
+        );
+        if trades > 0 {
+            let avg_ms = (total_us - self.last_total_us) / trades as f64 / 1000.0;
+            packet.push_str(&format!(
+                "\n{}.latency:{:.3}|ms|@{:.6}{}",
+                self.prefix,
+                avg_ms,
+                1.0 / trades as f64,
+                self.tags
+            ));
+        }
+        self.last_count = count;
+        self.last_total_us = total_us;
+        self.last_gaps = gaps;
+
+        match self.socket.send(packet.as_bytes()) {
+            Ok(_) => self.sent += 1,
+            Err(_) => self.failed += 1,
+        }
+    }
+
+    /// One-line summary for the final report.
+    pub fn summary(&self) -> String {
+        format!(
+            "udp://{}: {} packets sent, {} failed",
+            self.addr, self.sent, self.failed
+        )
+    }
+}
@@ -0,0 +1,370 @@
+//! Resumo compacto da execução (SUMMARY_ONLY=1): em vez do CSV por trade,
+//! grava um único JSON com contagens, percentis, integridade e o histograma
+//! de latência. Útil para agregar muitas execuções pelos seus resumos.
+//! O mesmo objeto sai em stdout/arquivo com STATS_JSON / STATS_JSON_FILE.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{json, Value};
+
+use crate::reconnect::ReconnectStats;
+use crate::stats::{DepthSequence, LatencyStats, SymbolStats, GAP_BUCKET_LABELS};
+
+const SUMMARY_PERCENTILES: [(&str, f64); 6] = [
+    ("p50", 0.50),
+    ("p90", 0.90),
+    ("p95", 0.95),
+    ("p99", 0.99),
+    ("p999", 0.999),
+    ("p9999", 0.9999),
+];
+
+/// Run identity written at the top of the summary.
+pub struct RunInfo<'a> {
+    pub label: &'a str,
+    pub machine_id: &'a str,
+    pub symbol: &'a str,
+    pub duration_s: f64,
+    /// Wall-clock start/end of the collection (µs since the epoch).
+    pub start_us: i64,
+    pub end_us: i64,
+    pub clock_offset_us: i64,
+    /// Why the run ended before the target count (None = complete).
+    pub stop_reason: Option<&'a str>,
+    pub reconnects: &'a ReconnectStats,
+    /// Multi-symbol run: per-symbol latency under `"symbols"`.
+    pub symbols: Option<&'a SymbolStats>,
+    /// Diff depth run: U/u contiguity under `"depth_breaks"`.
+    pub depth: Option<&'a DepthSequence>,
+    /// CSV rows dropped on a full writer queue (`CSV_QUEUE_ROWS`).
+    pub csv_dropped: u64,
+}
+
+/// Rounds to `places` decimals: the summary keeps µs/s values readable
+/// (`{:.1}`/`{:.3}` before serde) without losing what matters.
+pub(crate) fn round_to(v: f64, places: i32) -> f64 {
+    let scale = 10f64.powi(places);
+    (v * scale).round() / scale
+}
+
+/// Summary object written by `to_json`. Serialized by hand (no serde derive
+/// in the build): the keys depend on the run (multi-symbol, depth, ...).
+struct Summary<'a> {
+    info: &'a RunInfo<'a>,
+    stats: &'a LatencyStats,
+}
+
+/// `gap_sizes`: `{"<label>":count}` in `GAP_BUCKET_LABELS` order.
+struct GapSizes([u64; 4]);
+
+impl Serialize for GapSizes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(GAP_BUCKET_LABELS.len()))?;
+        for (label, n) in GAP_BUCKET_LABELS.iter().zip(self.0) {
+            map.serialize_entry(label, &n)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for Summary<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (info, stats) = (self.info, self.stats);
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("label", info.label)?;
+        map.serialize_entry("machine_id", info.machine_id)?;
+        map.serialize_entry("symbol", info.symbol)?;
+        map.serialize_entry("duration_s", &round_to(info.duration_s, 3))?;
+        map.serialize_entry("start_us", &info.start_us)?;
+        map.serialize_entry("end_us", &info.end_us)?;
+        map.serialize_entry("clock_offset_us", &info.clock_offset_us)?;
+        map.serialize_entry("stop_reason", &info.stop_reason)?;
+
+        match stats.get_basic() {
+            Some((count, avg, min, max)) => {
+                map.serialize_entry("count", &count)?;
+                map.serialize_entry("avg_us", &round_to(avg, 1))?;
+                map.serialize_entry("min_us", &min)?;
+                map.serialize_entry("max_us", &max)?;
+                if let Some(stddev) = stats.stddev_us() {
+                    map.serialize_entry("stddev_us", &round_to(stddev, 1))?;
+                }
+                let qs: Vec<f64> = SUMMARY_PERCENTILES.iter().map(|(_, q)| *q).collect();
+                for ((name, _), v) in SUMMARY_PERCENTILES.iter().zip(stats.percentiles(&qs)) {
+                    map.serialize_entry(&format!("{}_us", name), &v)?;
+                }
+            }
+            None => map.serialize_entry("count", &0)?,
+        }
+
+        let (gaps, out_of_order, gap_sizes) = stats.integrity();
+        map.serialize_entry("gaps", &gaps)?;
+        map.serialize_entry("late_filled", &stats.late_filled())?;
+        map.serialize_entry("out_of_order", &out_of_order)?;
+        map.serialize_entry("out_of_order_strict", &stats.out_of_order_strict())?;
+        map.serialize_entry("ooo_tolerance", &stats.ooo_tolerance())?;
+        map.serialize_entry("resets", &stats.id_resets())?;
+        map.serialize_entry("missed_aggregates", &stats.missed_aggregates())?;
+        map.serialize_entry("gap_sizes", &GapSizes(gap_sizes))?;
+        map.serialize_entry("reconnects", &info.reconnects.count())?;
+        map.serialize_entry("downtime_s", &round_to(info.reconnects.total_downtime().as_secs_f64(), 3))?;
+        map.serialize_entry(
+            "longest_outage_s",
+            &round_to(info.reconnects.longest_outage().as_secs_f64(), 3),
+        )?;
+        if let Some(depth) = info.depth {
+            let (total, ahead, behind) = depth.breaks();
+            map.serialize_entry(
+                "depth_breaks",
+                &json!({"total": total, "ahead": ahead, "behind": behind}),
+            )?;
+        }
+        let (clock_regressions, max_back_us) = stats.clock_regressions();
+        map.serialize_entry("clock_regressions", &clock_regressions)?;
+        map.serialize_entry("max_clock_regression_us", &max_back_us)?;
+        map.serialize_entry("negative_latency", &stats.negative_latency())?;
+        map.serialize_entry("warmup_trades", &stats.warmup_skipped())?;
+        map.serialize_entry("csv_dropped", &info.csv_dropped)?;
+
+        if let Some(per_symbol) = info.symbols {
+            let entries: BTreeMap<&str, Value> = per_symbol
+                .get_all()
+                .into_iter()
+                .map(|(symbol, latency)| {
+                    let entry = match latency {
+                        Some((count, avg, min, max, p50, p95, p99)) => json!({
+                            "count": count,
+                            "avg_us": round_to(avg, 1),
+                            "min_us": min,
+                            "max_us": max,
+                            "p50_us": p50,
+                            "p95_us": p95,
+                            "p99_us": p99,
+                        }),
+                        None => json!({"count": 0}),
+                    };
+                    (symbol, entry)
+                })
+                .collect();
+            map.serialize_entry("symbols", &entries)?;
+        }
+
+        // Histograma: [limite superior µs (null = sem limite), contagem]; só buckets
+        // não vazios. O primeiro ([piso, n]) é o underflow, o último ([null, n]) o overflow
+        let hist = stats.histogram();
+        map.serialize_entry("histogram_underflow", &hist.underflow)?;
+        map.serialize_entry("histogram_overflow", &hist.overflow)?;
+        let histogram: Vec<(Option<i64>, u64)> = std::iter::once((Some(hist.floor_us), hist.underflow))
+            .chain(hist.buckets.iter().map(|&(le, n)| (Some(le), n)))
+            .chain(std::iter::once((None, hist.overflow)))
+            .filter(|&(_, n)| n > 0)
+            .collect();
+        map.serialize_entry("histogram", &histogram)?;
+        map.end()
+    }
+}
+
+/// Serializes `stats` (plus run identity) as one JSON object.
+pub fn to_json(info: &RunInfo, stats: &LatencyStats) -> String {
+    serde_json::to_string(&Summary { info, stats }).expect("summary serializes to JSON")
+}
+
+/// Raw sample export (SAMPLES_FILE): one latency_ms per line, or with
+/// `histogram` a `le_us,count` CSV of the sample histogram: the first row
+/// (the floor) is the underflow, `inf` the overflow.
+pub fn save_samples(path: &str, stats: &LatencyStats, histogram: bool) -> std::io::Result<()> {
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    if histogram {
+        let hist = stats.histogram();
+        writeln!(out, "le_us,count")?;
+        writeln!(out, "{},{}", hist.floor_us, hist.underflow)?;
+        for (le, n) in &hist.buckets {
+            writeln!(out, "{},{}", le, n)?;
+        }
+        writeln!(out, "inf,{}", hist.overflow)?;
+    } else {
+        for v in stats.samples() {
+            writeln!(out, "{:.3}", v as f64 / 1000.0)?;
+        }
+    }
+    out.into_inner().map_err(|e| e.into_error())?.sync_data()
+}
+
+pub fn save(path: &str, info: &RunInfo, stats: &LatencyStats) -> std::io::Result<()> {
+    let mut file = std::fs::File::create(path)?;
+    writeln!(file, "{}", to_json(info, stats))?;
+    file.sync_data()
+}
+
+// ---------------------------------------------------------------------------
+// Baseline (--baseline summary.json --tolerance 10%)
+// ---------------------------------------------------------------------------
+
+/// Exit code when the run regressed against the baseline.
+pub const REGRESSION_EXIT_CODE: i32 = 3;
+
+const BASELINE_PERCENTILES: [(&str, f64); 3] = [("p50", 0.50), ("p95", 0.95), ("p99", 0.99)];
+
+/// Percentiles (µs) read from a previously saved summary.
+pub struct Baseline {
+    pub path: String,
+    values: Vec<i64>,
+}
+
+impl Baseline {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let summary: Value = serde_json::from_str(&json).map_err(|e| format!("{}: {}", path, e))?;
+        let values = BASELINE_PERCENTILES
+            .iter()
+            .map(|(name, _)| {
+                // Só chaves do topo: "p99_us" de "symbols" não conta como baseline
+                summary
+                    .get(format!("{}_us", name))
+                    .and_then(Value::as_i64)
+                    .ok_or_else(|| format!("{}: no {}_us in summary", path, name))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { path: path.to_string(), values })
+    }
+
+    /// Prints the deltas; true if any percentile regressed beyond `tolerance_pct`.
+    pub fn check(&self, stats: &LatencyStats, tolerance_pct: f64) -> bool {
+        let qs: Vec<f64> = BASELINE_PERCENTILES.iter().map(|(_, q)| *q).collect();
+        let current = stats.percentiles(&qs);
+        eprintln!("\n=== Baseline Check ({}, tolerance {}%) ===", self.path, tolerance_pct);
+        if current.is_empty() {
+            eprintln!("No samples in this run: treating as regression");
+            return true;
+        }
+
+        eprintln!("{:<6} {:>12} {:>12} {:>9}", "", "Base (µs)", "Now (µs)", "Δ");
+        let mut regressed = false;
+        for (((name, _), &base), &now) in BASELINE_PERCENTILES.iter().zip(&self.values).zip(&current) {
+            // |base| no denominador: o baseline pode ser negativo (offset de relógio)
+            let delta_pct = (now - base) as f64 * 100.0 / (base.abs().max(1)) as f64;
+            let bad = delta_pct > tolerance_pct;
+            regressed |= bad;
+            eprintln!(
+                "{:<6} {:>12} {:>12} {:>+8.1}% {}",
+                name.to_uppercase(),
+                base,
+                now,
+                delta_pct,
+                if bad { "❌ REGRESSED" } else { "✅" }
+            );
+        }
+        eprintln!(
+            "Result: {}",
+            if regressed { "latency REGRESSED beyond tolerance" } else { "within tolerance" }
+        );
+        regressed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info<'a>(reconnects: &'a ReconnectStats, symbols: Option<&'a SymbolStats>) -> RunInfo<'a> {
+        RunInfo {
+            label: "lab \"a\"\\b",
+            machine_id: "host\n1\t\u{1}",
+            symbol: "BTCUSDT",
+            duration_s: 1.23456,
+            start_us: 1,
+            end_us: 2,
+            clock_offset_us: -5,
+            stop_reason: None,
+            reconnects,
+            symbols,
+            depth: None,
+            csv_dropped: 3,
+        }
+    }
+
+    fn stats(latencies: &[i64]) -> LatencyStats {
+        let mut stats = LatencyStats::new(1000);
+        for (i, &latency) in latencies.iter().enumerate() {
+            stats.update(i as u64 + 1, latency);
+        }
+        stats
+    }
+
+    #[test]
+    fn to_json_is_valid_json_with_escaped_strings() {
+        let reconnects = ReconnectStats::default();
+        let stats = stats(&[100, 200, 300]);
+        let json = to_json(&info(&reconnects, None), &stats);
+        let v: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["label"], "lab \"a\"\\b");
+        assert_eq!(v["machine_id"], "host\n1\t\u{1}");
+        assert_eq!(v["duration_s"], 1.235);
+        assert_eq!(v["stop_reason"], Value::Null);
+        assert_eq!(v["count"], 3);
+        assert_eq!(v["avg_us"], 200.0);
+        assert_eq!(v["min_us"], 100);
+        assert_eq!(v["p50_us"], 200);
+        assert_eq!(v["gap_sizes"].as_object().unwrap().len(), GAP_BUCKET_LABELS.len());
+        assert_eq!(v["csv_dropped"], 3);
+        let histogram = v["histogram"].as_array().unwrap();
+        let total: u64 = histogram.iter().map(|b| b[1].as_u64().unwrap()).sum();
+        assert_eq!(total, 3);
+    }
+
+    #[test]
+    fn to_json_without_samples_has_count_zero() {
+        let reconnects = ReconnectStats::default();
+        let v: Value = serde_json::from_str(&to_json(&info(&reconnects, None), &stats(&[]))).unwrap();
+        assert_eq!(v["count"], 0);
+        assert!(v.get("p50_us").is_none());
+        assert_eq!(v["histogram"], json!([]));
+    }
+
+    #[test]
+    fn to_json_per_symbol_entries() {
+        let reconnects = ReconnectStats::default();
+        let template = LatencyStats::new(100);
+        let mut per_symbol = SymbolStats::new(&["btcusdt".to_string(), "ethusdt".to_string()], &template);
+        per_symbol.update(b"BTCUSDT", 1, 500);
+        let json = to_json(&info(&reconnects, Some(&per_symbol)), &stats(&[500]));
+        let v: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["symbols"]["BTCUSDT"]["p99_us"], 500);
+        assert_eq!(v["symbols"]["ETHUSDT"], json!({"count": 0}));
+    }
+
+    #[test]
+    fn baseline_reads_top_level_percentiles_only() {
+        let path = std::env::temp_dir().join(format!("baseline_test_{}.json", std::process::id()));
+        // "p99_us" aninhado vem antes do do topo: não pode ser usado
+        std::fs::write(
+            &path,
+            r#"{"symbols":{"BTCUSDT":{"p50_us":1,"p95_us":2,"p99_us":3}},"p50_us":10,"p95_us":20,"p99_us":30}"#,
+        )
+        .unwrap();
+        let baseline = Baseline::load(path.to_str().unwrap()).unwrap();
+        assert_eq!(baseline.values, vec![10, 20, 30]);
+
+        std::fs::write(&path, r#"{"symbols":{"BTCUSDT":{"p50_us":1,"p95_us":2,"p99_us":3}}}"#).unwrap();
+        let err = Baseline::load(path.to_str().unwrap()).err().unwrap();
+        assert!(err.contains("no p50_us"), "{}", err);
+
+        std::fs::write(&path, "p50_us: 1").unwrap();
+        assert!(Baseline::load(path.to_str().unwrap()).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn baseline_round_trips_saved_summary() {
+        let path = std::env::temp_dir().join(format!("summary_test_{}.json", std::process::id()));
+        let reconnects = ReconnectStats::default();
+        let stats = stats(&[100, 200, 300, 400]);
+        save(path.to_str().unwrap(), &info(&reconnects, None), &stats).unwrap();
+        let baseline = Baseline::load(path.to_str().unwrap()).unwrap();
+        assert_eq!(baseline.values, stats.percentiles(&[0.50, 0.95, 0.99]));
+        assert!(!baseline.check(&stats, 0.0));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,212 @@
+//! Varredura sequencial de símbolos: mapa de latência do mercado.
+//!
+//! Conecta em um símbolo por vez (`<symbol>@trade`), coleta N trades, guarda
+//! o resumo e passa para o próximo; no fim imprime uma tabela única ordenada
+//! pela mediana. Um símbolo por vez: uma conexão só, sem disputa de CPU/rede
+//! entre streams. Todos usam o mesmo relógio local, então o ranking não
+//! depende do offset de relógio.
+//!
+//! Uso:
+//!   cargo run --release -- --sweep symbols.txt [--per-symbol-trades 10000]
+
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::clock::{ms_to_us, RecvClock};
+use crate::collector::{try_connect_ws, BINANCE_WS_BASE};
+use crate::display;
+use crate::extract::extract_trade_data;
+use crate::stats::LatencyStats;
+
+pub const DEFAULT_PER_SYMBOL_TRADES: usize = 10_000;
+const DEFAULT_TIMEOUT_S: f64 = 600.0;
+
+struct SymbolResult {
+    symbol: String,
+    trades: u64,
+    /// (p50, p95, p99, max) in µs.
+    latency: Option<(i64, i64, i64, i64)>,
+    missing_ids: u64,
+    secs: f64,
+    /// Why the symbol stopped short of the target (connect error, timeout...).
+    note: Option<String>,
+}
+
+/// Symbols file: one per line (or comma-separated), `#` starts a comment.
+fn load_symbols(path: &str) -> std::io::Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .flat_map(|line| line.split(','))
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect())
+}
+
+/// Collects up to `count` trades from one symbol, stopping at `timeout`.
+async fn measure(symbol: &str, count: usize, timeout: Duration, clock: &RecvClock) -> SymbolResult {
+    let mut stats = LatencyStats::new(count);
+    let start = Instant::now();
+    let url = format!("{}/{}@trade", BINANCE_WS_BASE, symbol);
+
+    let note = match try_connect_ws(&url).await {
+        Err(e) => Some(e),
+        Ok(ws) => {
+            let (_write, mut read) = ws.split();
+            let deadline = tokio::time::sleep(timeout);
+            tokio::pin!(deadline);
+            loop {
+                if stats.recorded() >= count as u64 {
+                    break None;
+                }
+                let msg = tokio::select! {
+                    msg = read.next() => msg,
+                    () = &mut deadline => break Some(format!("timeout after {:?}", timeout)),
+                };
+                let recv_instant = Instant::now();
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => break Some(format!("WebSocket error: {}", e)),
+                    None => break Some("stream ended".to_string()),
+                };
+                if let Some((trade_id, ts_us)) =
+                    extract_trade_data(text.as_bytes()).and_then(|(id, ts_ms)| Some((id, ms_to_us(ts_ms)?)))
+                {
+                    let latency_us = clock.to_epoch_us(recv_instant) as i64 - ts_us;
+                    stats.update(trade_id, latency_us);
+                }
+            }
+        }
+    };
+
+    let latency = stats
+        .get()
+        .map(|(_, _, _, max, p50, p95, p99)| (p50, p95, p99, max));
+    eprintln!(
+        "{:<12} {}{}",
+        symbol.to_uppercase(),
+        display::checkpoint_line(stats.recorded() as usize, &stats),
+        note.as_ref()
+            .map(|n| format!(" ({})", n))
+            .unwrap_or_default()
+    );
+    SymbolResult {
+        symbol: symbol.to_uppercase(),
+        trades: stats.recorded(),
+        latency,
+        missing_ids: stats.integrity().0,
+        secs: start.elapsed().as_secs_f64(),
+        note,
+    }
+}
+
+/// Ranked table (lowest median first); symbols without trades go last.
+fn print_ranking(results: &mut [SymbolResult]) {
+    results.sort_by_key(|r| r.latency.map(|(p50, ..)| p50).unwrap_or(i64::MAX));
+    let ms = |us: i64| us as f64 / 1000.0;
+
+    eprintln!("\n=== Sweep Ranking (by median latency) ===");
+    eprintln!(
+        "{:>4}  {:<12} {:>8} {:>10} {:>10} {:>10} {:>10} {:>8} {:>8}",
+        "#", "Symbol", "Trades", "P50 (ms)", "P95 (ms)", "P99 (ms)", "Max (ms)", "Missing", "Secs"
+    );
+    for (rank, r) in results.iter().enumerate() {
+        match r.latency {
+            Some((p50, p95, p99, max)) => eprintln!(
+                "{:>4}  {:<12} {:>8} {:>10.3} {:>10.3} {:>10.3} {:>10.3} {:>8} {:>8.1}{}",
+                rank + 1,
+                r.symbol,
+                r.trades,
+                ms(p50),
+                ms(p95),
+                ms(p99),
+                ms(max),
+                r.missing_ids,
+                r.secs,
+                if r.note.is_some() { "  *" } else { "" }
+            ),
+            None => eprintln!(
+                "{:>4}  {:<12} {:>8} {}",
+                "-",
+                r.symbol,
+                0,
+                r.note.as_deref().unwrap_or("no trades")
+            ),
+        }
+    }
+    if results
+        .iter()
+        .any(|r| r.latency.is_some() && r.note.is_some())
+    {
+        eprintln!("  * stopped before the target count (see the per-symbol line above)");
+    }
+}
+
+/// Writes the ranking as CSV (SWEEP_CSV).
+fn save_ranking(path: &str, results: &[SymbolResult]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(
+        file,
+        "rank,symbol,trades,p50_us,p95_us,p99_us,max_us,missing_ids,secs,note"
+    )?;
+    for (rank, r) in results.iter().enumerate() {
+        let (p50, p95, p99, max) = match r.latency {
+            Some((p50, p95, p99, max)) => (
+                p50.to_string(),
+                p95.to_string(),
+                p99.to_string(),
+                max.to_string(),
+            ),
+            None => Default::default(),
+        };
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{},{:.1},{}",
+            rank + 1,
+            r.symbol,
+            r.trades,
+            p50,
+            p95,
+            p99,
+            max,
+            r.missing_ids,
+            r.secs,
+            crate::csv::escape_field(r.note.as_deref().unwrap_or(""))
+        )?;
+    }
+    file.flush()
+}
+
+pub async fn run(symbols_path: &str, per_symbol: usize) -> Result<(), String> {
+    let symbols = load_symbols(symbols_path).map_err(|e| format!("{}: {}", symbols_path, e))?;
+    if symbols.is_empty() {
+        return Err(format!("{}: no symbols", symbols_path));
+    }
+    let timeout =
+        Duration::from_secs_f64(crate::env_parse("SWEEP_TIMEOUT_S", DEFAULT_TIMEOUT_S).max(1.0));
+    let clock = RecvClock::from_env();
+
+    eprintln!("=== Symbol Sweep ===");
+    eprintln!("Symbols:    {} (from {})", symbols.len(), symbols_path);
+    eprintln!("Per symbol: {} trades (timeout {:?})", per_symbol, timeout);
+    eprintln!("Clock:      {}\n", clock.name());
+
+    let mut results = Vec::with_capacity(symbols.len());
+    for symbol in &symbols {
+        results.push(measure(symbol, per_symbol, timeout, &clock).await);
+    }
+    print_ranking(&mut results);
+
+    if let Ok(path) = std::env::var("SWEEP_CSV") {
+        match save_ranking(&path, &results) {
+            Ok(()) => eprintln!("\n✅ Ranking saved to: {}", path),
+            Err(e) => eprintln!("\n❌ Error saving ranking: {}", e),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,164 @@
+//! Estatísticas de rede do kernel via `getsockopt(TCP_INFO)` (TCP_INFO=1).
+//!
+//! Amostra periodicamente o socket da conexão WebSocket: RTT e variância
+//! medidos pelo próprio TCP, retransmissões e segmentos perdidos. Permite
+//! correlacionar picos de latência da aplicação com retransmissões reais na
+//! camada de rede. Só Linux; em outros sistemas o monitor fica desligado.
+
+use std::time::Duration;
+
+use crate::stats::LatencyStats;
+use crate::collector::SocketInfo;
+
+/// Sampling period (`TCP_INFO_INTERVAL_S`, default 1s, at least 10ms).
+pub fn interval_from_env() -> Duration {
+    Duration::from_secs_f64(crate::env_parse("TCP_INFO_INTERVAL_S", 1.0f64).max(0.01))
+}
+
+/// One `TCP_INFO` reading.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TcpSample {
+    pub rtt_us: u32,
+    pub rttvar_us: u32,
+    pub total_retrans: u32,
+    pub lost: u32,
+}
+
+pub struct TcpInfoMonitor {
+    #[cfg(target_os = "linux")]
+    fd: std::os::unix::io::RawFd,
+    first: Option<TcpSample>,
+    last: Option<TcpSample>,
+    rtt: LatencyStats,
+    /// Samples where `total_retrans` grew since the previous one.
+    intervals_with_retrans: u64,
+    /// Retransmits of sockets from previous connections (before reconnects).
+    prior_retrans: u32,
+}
+
+impl TcpInfoMonitor {
+    /// Monitor for the connection's socket. Must be sampled only while the
+    /// connection is alive.
+    #[cfg(target_os = "linux")]
+    pub fn new(socket: SocketInfo) -> Option<Self> {
+        let mut monitor = Self {
+            fd: socket.raw_fd?,
+            first: None,
+            last: None,
+            rtt: LatencyStats::new(100_000),
+            intervals_with_retrans: 0,
+            prior_retrans: 0,
+        };
+        monitor.sample()?;
+        Some(monitor)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(_socket: SocketInfo) -> Option<Self> {
+        None
+    }
+
+    /// Switches to a new connection's socket, keeping the accumulated totals.
+    #[cfg(target_os = "linux")]
+    pub fn attach(&mut self, socket: SocketInfo) {
+        let Some(fd) = socket.raw_fd else { return };
+        self.prior_retrans += self.retrans_current_socket();
+        self.fd = fd;
+        self.first = None;
+        self.last = None;
+        self.sample();
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn attach(&mut self, _socket: SocketInfo) {}
+
+    fn retrans_current_socket(&self) -> u32 {
+        match (self.first, self.last) {
+            (Some(first), Some(last)) => last.total_retrans.saturating_sub(first.total_retrans),
+            _ => 0,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn read(&self) -> Option<TcpSample> {
+        // SAFETY: tcp_info é POD; zeros é um valor válido
+        let mut info: libc::tcp_info = unsafe { std::mem::zeroed() };
+        let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+        // SAFETY: `info`/`len` are valid and writable; `fd` is the live socket.
+        let rc = unsafe {
+            libc::getsockopt(
+                self.fd,
+                libc::IPPROTO_TCP,
+                libc::TCP_INFO,
+                &mut info as *mut libc::tcp_info as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if rc != 0 {
+            return None;
+        }
+        Some(TcpSample {
+            rtt_us: info.tcpi_rtt,
+            rttvar_us: info.tcpi_rttvar,
+            total_retrans: info.tcpi_total_retrans,
+            lost: info.tcpi_lost,
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn read(&self) -> Option<TcpSample> {
+        None
+    }
+
+    /// Takes one reading; returns it with the retransmits since the previous one.
+    pub fn sample(&mut self) -> Option<(TcpSample, u32)> {
+        let s = self.read()?;
+        let new_retrans = self
+            .last
+            .map_or(0, |prev| s.total_retrans.saturating_sub(prev.total_retrans));
+        if new_retrans > 0 {
+            self.intervals_with_retrans += 1;
+        }
+        self.rtt.record(s.rtt_us as i64);
+        self.first.get_or_insert(s);
+        self.last = Some(s);
+        Some((s, new_retrans))
+    }
+
+    /// Per-interval line (TCP_INFO_LOG=1).
+    pub fn interval_line(s: &TcpSample, new_retrans: u32) -> String {
+        format!(
+            "TCP: rtt {:.2}ms ± {:.2}ms | retrans +{} (total {}) | lost {}",
+            s.rtt_us as f64 / 1000.0,
+            s.rttvar_us as f64 / 1000.0,
+            new_retrans,
+            s.total_retrans,
+            s.lost
+        )
+    }
+
+    pub fn report(&self) {
+        let Some(last) = self.last else {
+            return;
+        };
+        eprintln!("\n=== TCP (kernel TCP_INFO) ===");
+        if let Some((n, avg, min, max, p50, _, p99)) = self.rtt.get() {
+            eprintln!(
+                "RTT:          avg {:.2}ms | min {:.2}ms | p50 {:.2}ms | p99 {:.2}ms | max {:.2}ms ({} samples)",
+                avg / 1000.0,
+                min as f64 / 1000.0,
+                p50 as f64 / 1000.0,
+                p99 as f64 / 1000.0,
+                max as f64 / 1000.0,
+                n
+            );
+        }
+        eprintln!("RTT var:      {:.2}ms (last)", last.rttvar_us as f64 / 1000.0);
+        eprintln!(
+            "Retransmits:  {} during capture ({} intervals affected)",
+            self.prior_retrans + self.retrans_current_socket(),
+            self.intervals_with_retrans
+        );
+        eprintln!("Lost:         {} (last)", last.lost);
+    }
+}